@@ -0,0 +1,580 @@
+//! Derive macro for `speakeasy_rust_sdk::masking::SpeakeasyMask`, use it through the `derive` feature of `speakeasy-rust-sdk`
+//!
+//! ```ignore
+//! use serde::Deserialize;
+//! use speakeasy_rust_sdk::masking::SpeakeasyMask;
+//!
+//! #[derive(Deserialize, SpeakeasyMask)]
+//! #[serde(rename_all = "camelCase")]
+//! struct CreateUser {
+//!     email: String,
+//!     // masked as `cardNumber` with the default mask
+//!     #[speakeasy(mask)]
+//!     card_number: String,
+//!     #[speakeasy(mask = "****")]
+//!     #[serde(rename = "pwd")]
+//!     password: String,
+//!     #[speakeasy(mask = 0)]
+//!     pin: u32,
+//!     #[speakeasy(mask = -1.5)]
+//!     balance: f64,
+//!     // the masked fields of Address are masked too
+//!     #[speakeasy(nested)]
+//!     address: Option<Address>,
+//! }
+//! ```
+//!
+//! Masks must match the type of their field, string masks can't mask numbers and number masks can't mask strings.
+//! Fields skipped by serde aren't masked under the skipped direction's name,
+//! and flattened fields have no name of their own so they can only be `nested`
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Expr, ExprLit, ExprUnary,
+    Fields, GenericArgument, Lit, LitStr, PathArguments, Type, UnOp,
+};
+
+const NUMBER_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32",
+    "f64",
+];
+
+// wrappers that serialize as their inner type or a list of it
+const WRAPPER_TYPES: &[&str] = &["Option", "Vec", "Box", "Arc", "Rc"];
+
+#[proc_macro_derive(SpeakeasyMask, attributes(speakeasy))]
+pub fn derive_speakeasy_mask(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+enum Mask {
+    Default,
+    String(LitStr),
+    Number(TokenStream2),
+}
+
+struct FieldAttrs {
+    mask: Option<Mask>,
+    nested: bool,
+}
+
+/// The serde attributes of a field that change where it is in the body
+#[derive(Default)]
+struct SerdeFlags {
+    skip_serializing: bool,
+    skip_deserializing: bool,
+    flatten: bool,
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    input.span(),
+                    "SpeakeasyMask can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                input.span(),
+                "SpeakeasyMask can only be derived for structs",
+            ))
+        }
+    };
+
+    let rename_all = serde_rename_all(&input)?;
+
+    let mut string_masks = Vec::new();
+    let mut number_masks = Vec::new();
+    let mut nested = Vec::new();
+
+    for field in fields {
+        let attrs = speakeasy_attrs(field)?;
+        let flags = serde_flags(field)?;
+
+        if flags.skip_serializing && flags.skip_deserializing {
+            if attrs.mask.is_some() || attrs.nested {
+                return Err(Error::new(
+                    field.span(),
+                    "fields with `#[serde(skip)]` are never in the body, remove their `#[speakeasy]` attribute",
+                ));
+            }
+            continue;
+        }
+
+        if flags.flatten && attrs.mask.is_some() {
+            return Err(Error::new(
+                field.span(),
+                "flattened fields have no name of their own to mask, use `#[speakeasy(nested)]` to mask the fields of their type",
+            ));
+        }
+
+        if attrs.nested {
+            let ty = unwrap_type(&field.ty);
+            nested.push(quote!(#ty));
+        }
+
+        let mask = match attrs.mask {
+            Some(mask) => mask,
+            None => continue,
+        };
+
+        let ident = field.ident.as_ref().expect("named field");
+        check_mask_type(&mask, ident, &field.ty)?;
+
+        let ident = ident.to_string();
+        let ident = ident.trim_start_matches("r#");
+        let renames = serde_renames(field)?;
+
+        // request bodies are deserialized and response bodies serialized, every name is masked
+        // unless serde skips the field in that direction
+        let serialize = renames
+            .serialize
+            .unwrap_or_else(|| rename(ident, rename_all.serialize.as_deref()));
+        let deserialize = renames
+            .deserialize
+            .unwrap_or_else(|| rename(ident, rename_all.deserialize.as_deref()));
+
+        let serialized = Some(serialize).filter(|_| !flags.skip_serializing);
+        let deserialized = if flags.skip_deserializing {
+            vec![]
+        } else {
+            [deserialize].into_iter().chain(renames.aliases).collect()
+        };
+
+        let mut names: Vec<String> = Vec::new();
+        for name in serialized.into_iter().chain(deserialized) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        for name in names {
+            match &mask {
+                Mask::String(mask) => string_masks.push(quote!((#name, Some(#mask)))),
+                Mask::Number(mask) => number_masks.push(quote!((#name, Some(#mask)))),
+                Mask::Default if is_number(unwrap_type(&field.ty)) => {
+                    number_masks.push(quote!((#name, None)))
+                }
+                Mask::Default => string_masks.push(quote!((#name, None))),
+            }
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::speakeasy_rust_sdk::masking::SpeakeasyMask for #name #ty_generics #where_clause {
+            fn string_field_masks() -> ::std::vec::Vec<(&'static str, ::std::option::Option<&'static str>)> {
+                #[allow(unused_mut)]
+                let mut masks = ::std::vec![#(#string_masks),*];
+                #(masks.extend(::speakeasy_rust_sdk::masking::nested_string_field_masks::<#nested>());)*
+                masks
+            }
+
+            fn number_field_masks() -> ::std::vec::Vec<(&'static str, ::std::option::Option<::speakeasy_rust_sdk::masking::NumberMask>)> {
+                #[allow(unused_mut)]
+                let mut masks = ::std::vec![#(#number_masks),*];
+                #(masks.extend(::speakeasy_rust_sdk::masking::nested_number_field_masks::<#nested>());)*
+                masks
+            }
+        }
+    })
+}
+
+fn speakeasy_attrs(field: &syn::Field) -> Result<FieldAttrs, Error> {
+    let mut attrs = FieldAttrs {
+        mask: None,
+        nested: false,
+    };
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("speakeasy"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("nested") {
+                attrs.nested = true;
+                return Ok(());
+            }
+
+            if !meta.path.is_ident("mask") {
+                return Err(meta.error("expected `mask`, `mask = ...` or `nested`"));
+            }
+
+            if meta.input.is_empty() || meta.input.peek(syn::Token![,]) {
+                attrs.mask = Some(Mask::Default);
+                return Ok(());
+            }
+
+            let mask: Expr = meta.value()?.parse()?;
+            attrs.mask = Some(match mask {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(mask),
+                    ..
+                }) => Mask::String(mask),
+                mask => number_mask(mask)?,
+            });
+
+            Ok(())
+        })?;
+    }
+
+    Ok(attrs)
+}
+
+/// Rejects string masks on number fields and number masks on string fields
+fn check_mask_type(mask: &Mask, ident: &syn::Ident, ty: &Type) -> Result<(), Error> {
+    let inner = unwrap_type(ty);
+
+    match mask {
+        Mask::String(mask) if is_number(inner) => Err(Error::new(
+            mask.span(),
+            format!(
+                "`{}` is a number, use a number mask such as `#[speakeasy(mask = 0)]`",
+                ident
+            ),
+        )),
+        Mask::Number(_) if is_string(inner) => Err(Error::new(
+            ty.span(),
+            format!(
+                "`{}` is a string, use a string mask such as `#[speakeasy(mask = \"****\")]`",
+                ident
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// An integer or float mask, possibly negative, as a `NumberMask`
+fn number_mask(mask: Expr) -> Result<Mask, Error> {
+    let (lit, negative) = match &mask {
+        Expr::Lit(ExprLit { lit, .. }) => (lit, false),
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => match expr.as_ref() {
+            Expr::Lit(ExprLit { lit, .. }) => (lit, true),
+            _ => return Err(Error::new(mask.span(), "expected a string or number mask")),
+        },
+        _ => return Err(Error::new(mask.span(), "expected a string or number mask")),
+    };
+
+    let mask = match lit {
+        Lit::Int(int) => {
+            let value = int.base10_parse::<i64>()?;
+            let value = if negative { -value } else { value };
+            quote!(::speakeasy_rust_sdk::masking::NumberMask::Int(#value))
+        }
+        Lit::Float(float) => {
+            let value = float.base10_parse::<f64>()?;
+            let value = if negative { -value } else { value };
+            quote!(::speakeasy_rust_sdk::masking::NumberMask::Float(#value))
+        }
+        _ => return Err(Error::new(lit.span(), "expected a string or number mask")),
+    };
+
+    Ok(Mask::Number(mask))
+}
+
+/// The serialized and deserialized names of a struct or field, `None` if they aren't renamed
+#[derive(Default)]
+struct Renames {
+    serialize: Option<String>,
+    deserialize: Option<String>,
+    aliases: Vec<String>,
+}
+
+/// The `serde(rename_all = "...")` or `serde(rename_all(serialize = "...", deserialize = "..."))` of the struct
+fn serde_rename_all(input: &DeriveInput) -> Result<Renames, Error> {
+    serde_names(&input.attrs, "rename_all")
+}
+
+/// The names of a field from `serde(rename = "...")`, `serde(rename(serialize = "...", deserialize = "..."))`
+/// and `serde(alias = "...")`
+fn serde_renames(field: &syn::Field) -> Result<Renames, Error> {
+    serde_names(&field.attrs, "rename")
+}
+
+fn serde_names(attrs: &[syn::Attribute], rename: &str) -> Result<Renames, Error> {
+    let mut renames = Renames::default();
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(rename) {
+                if meta.input.peek(syn::Token![=]) {
+                    let value: LitStr = meta.value()?.parse()?;
+                    renames.serialize = Some(value.value());
+                    renames.deserialize = Some(value.value());
+                } else {
+                    meta.parse_nested_meta(|meta| {
+                        let value: LitStr = meta.value()?.parse()?;
+                        if meta.path.is_ident("serialize") {
+                            renames.serialize = Some(value.value());
+                        } else if meta.path.is_ident("deserialize") {
+                            renames.deserialize = Some(value.value());
+                        }
+                        Ok(())
+                    })?;
+                }
+            } else if meta.path.is_ident("alias") {
+                let value: LitStr = meta.value()?.parse()?;
+                renames.aliases.push(value.value());
+            } else {
+                skip_meta(&meta)?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(renames)
+}
+
+/// The `serde(skip)`, `serde(skip_serializing)`, `serde(skip_deserializing)` and `serde(flatten)` of a field
+fn serde_flags(field: &syn::Field) -> Result<SerdeFlags, Error> {
+    let mut flags = SerdeFlags::default();
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("serde"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                flags.skip_serializing = true;
+                flags.skip_deserializing = true;
+            } else if meta.path.is_ident("skip_serializing") {
+                flags.skip_serializing = true;
+            } else if meta.path.is_ident("skip_deserializing") {
+                flags.skip_deserializing = true;
+            } else if meta.path.is_ident("flatten") {
+                flags.flatten = true;
+            } else {
+                skip_meta(&meta)?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(flags)
+}
+
+// consumes the value of serde attributes that aren't about naming
+fn skip_meta(meta: &syn::meta::ParseNestedMeta) -> Result<(), Error> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|meta| skip_meta(&meta))?;
+    }
+
+    Ok(())
+}
+
+/// Applies a serde `rename_all` rule to a snake_case field name
+fn rename(field: &str, rename_all: Option<&str>) -> String {
+    let words = || field.split('_').filter(|word| !word.is_empty());
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+            None => String::new(),
+        }
+    };
+
+    match rename_all {
+        Some("lowercase") => field.to_lowercase(),
+        Some("UPPERCASE") => field.to_uppercase(),
+        Some("PascalCase") => words().map(capitalize).collect(),
+        Some("camelCase") => words()
+            .enumerate()
+            .map(|(i, word)| {
+                if i == 0 {
+                    word.to_string()
+                } else {
+                    capitalize(word)
+                }
+            })
+            .collect(),
+        Some("SCREAMING_SNAKE_CASE") => field.to_uppercase(),
+        Some("kebab-case") => field.replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => field.replace('_', "-").to_uppercase(),
+        _ => field.to_string(),
+    }
+}
+
+/// `Option<T>`, `Vec<T>`, `Box<T>`, ... to `T`
+fn unwrap_type(ty: &Type) -> &Type {
+    if let Type::Path(path) = ty {
+        if let Some(segment) = path.path.segments.last() {
+            if WRAPPER_TYPES.iter().any(|wrapper| segment.ident == wrapper) {
+                if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                    if let Some(GenericArgument::Type(inner)) = args.args.first() {
+                        return unwrap_type(inner);
+                    }
+                }
+            }
+        }
+    }
+
+    ty
+}
+
+fn is_string(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "String" || segment.ident == "str"),
+        Type::Reference(reference) => is_string(&reference.elem),
+        _ => false,
+    }
+}
+
+fn is_number(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .get_ident()
+            .is_some_and(|ident| NUMBER_TYPES.iter().any(|number| ident == number)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        input: DeriveInput,
+        expected: Option<&'static str>,
+    }
+
+    #[test]
+    fn checks_fields() {
+        let tests = vec![
+            Test {
+                name: "string mask on a number",
+                input: parse_quote! {
+                    struct User {
+                        #[speakeasy(mask = "****")]
+                        pin: Option<u32>,
+                    }
+                },
+                expected: Some("`pin` is a number, use a number mask such as `#[speakeasy(mask = 0)]`"),
+            },
+            Test {
+                name: "number mask on a string",
+                input: parse_quote! {
+                    struct User {
+                        #[speakeasy(mask = 0)]
+                        card_number: Vec<String>,
+                    }
+                },
+                expected: Some(
+                    "`card_number` is a string, use a string mask such as `#[speakeasy(mask = \"****\")]`",
+                ),
+            },
+            Test {
+                name: "default masks follow the type",
+                input: parse_quote! {
+                    struct User {
+                        #[speakeasy(mask)]
+                        pin: u32,
+                        #[speakeasy(mask)]
+                        card_number: String,
+                    }
+                },
+                expected: None,
+            },
+            Test {
+                name: "masked skipped field",
+                input: parse_quote! {
+                    struct User {
+                        #[speakeasy(mask)]
+                        #[serde(skip)]
+                        password: String,
+                    }
+                },
+                expected: Some(
+                    "fields with `#[serde(skip)]` are never in the body, remove their `#[speakeasy]` attribute",
+                ),
+            },
+            Test {
+                name: "nested skipped field",
+                input: parse_quote! {
+                    struct User {
+                        #[speakeasy(nested)]
+                        #[serde(skip_serializing, skip_deserializing)]
+                        address: Address,
+                    }
+                },
+                expected: Some(
+                    "fields with `#[serde(skip)]` are never in the body, remove their `#[speakeasy]` attribute",
+                ),
+            },
+            Test {
+                name: "skipped in one direction",
+                input: parse_quote! {
+                    struct User {
+                        #[speakeasy(mask)]
+                        #[serde(skip_serializing_if = "Option::is_none", skip_serializing)]
+                        password: Option<String>,
+                    }
+                },
+                expected: None,
+            },
+            Test {
+                name: "masked flattened field",
+                input: parse_quote! {
+                    struct User {
+                        #[speakeasy(mask)]
+                        #[serde(flatten)]
+                        address: Address,
+                    }
+                },
+                expected: Some(
+                    "flattened fields have no name of their own to mask, use `#[speakeasy(nested)]` to mask the fields of their type",
+                ),
+            },
+            Test {
+                name: "nested flattened field",
+                input: parse_quote! {
+                    struct User {
+                        #[speakeasy(nested)]
+                        #[serde(flatten)]
+                        address: Address,
+                    }
+                },
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(
+                expand(test.input)
+                    .err()
+                    .map(|err| err.to_string())
+                    .as_deref(),
+                test.expected
+            );
+        }
+    }
+}
@@ -99,41 +99,41 @@ async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
     info!("starting HTTP server at http://localhost:8080");
 
-    HttpServer::new(|| {
-        let config = Config {
-            // retrieve from Speakeasy API dashboard.
-            api_key: "YOUR API KEY HERE".to_string(),
-            // enter a name that you'd like to associate captured requests with.
-            // This name will show up in the Speakeasy dashboard. e.g. "PetStore" might be a good ApiID for a Pet Store's API.
-            // No spaces allowed.
-            api_id: "YOUR API ID HERE".to_string(),
-            // enter a version that you would like to associate captured requests with.
-            // The combination of ApiID (name) and VersionID will uniquely identify your requests in the Speakeasy Dashboard.
-            // e.g. "v1.0.0". You can have multiple versions for the same ApiID (if running multiple versions of your API)
-            version_id: "YOUR VERSION ID HERE".to_string(),
-        };
+    let config = Config {
+        // retrieve from Speakeasy API dashboard.
+        api_key: "YOUR API KEY HERE".to_string(),
+        // enter a name that you'd like to associate captured requests with.
+        // This name will show up in the Speakeasy dashboard. e.g. "PetStore" might be a good ApiID for a Pet Store's API.
+        // No spaces allowed.
+        api_id: "YOUR API ID HERE".to_string(),
+        // enter a version that you would like to associate captured requests with.
+        // The combination of ApiID (name) and VersionID will uniquely identify your requests in the Speakeasy Dashboard.
+        // e.g. "v1.0.0". You can have multiple versions for the same ApiID (if running multiple versions of your API)
+        version_id: "YOUR VERSION ID HERE".to_string(),
+    };
 
-        // Create a new Speakeasy SDK instance
-        let mut sdk = SpeakeasySdk::try_new(config).expect("API key is valid");
+    // Create a new Speakeasy SDK instance once, its clones share its masking and capture settings
+    let mut sdk = SpeakeasySdk::try_new(config).expect("API key is valid");
 
-        // Configure masking for query
-        sdk.masking().with_query_string_mask("secret", "********");
-        sdk.masking()
-            .with_query_string_mask("password", StringMaskingOption::default());
+    // Configure masking for query
+    sdk.masking().with_query_string_mask("secret", "********");
+    sdk.masking()
+        .with_query_string_mask("password", StringMaskingOption::default());
 
-        // Configure masking for request
-        sdk.masking()
-            .with_request_field_mask_string("password", StringMaskingOption::default());
+    // Configure masking for request
+    sdk.masking()
+        .with_request_field_mask_string("password", StringMaskingOption::default());
 
-        // Configure masking for response
-        sdk.masking()
-            .with_response_field_mask_string("secret", StringMaskingOption::default());
+    // Configure masking for response
+    sdk.masking()
+        .with_response_field_mask_string("secret", StringMaskingOption::default());
 
+    HttpServer::new(move || {
         let app_state = AppState {
             speakeasy_sdk: sdk.clone(),
         };
 
-        let speakeasy_middleware = Middleware::new(sdk);
+        let speakeasy_middleware = Middleware::new(sdk.clone());
         let (request_capture, response_capture) = speakeasy_middleware.into();
 
         App::new()
@@ -0,0 +1,258 @@
+//! Rules choosing how much of a response is captured by its status and route,
+//! e.g. to drop the token carrying success responses of authentication endpoints but keep their errors
+
+use http::StatusCode;
+use log::error;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::masking::route::RoutePattern;
+
+/// Errors for adding capture rules
+#[derive(Debug, Clone, Error)]
+pub enum Error {
+    #[error("invalid route: {0:?}, expected an optional method and a path starting with `/`")]
+    InvalidRoute(String),
+}
+
+/// How much of a response is captured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureLevel {
+    /// The response body is captured as is, without the body masks, removals, PII detection or allowlist.
+    /// Headers and cookies are still masked
+    Full,
+    /// Everything is captured and masked
+    #[default]
+    Masked,
+    /// The response body is dropped, headers and cookies are captured and masked
+    HeadersOnly,
+    /// Only the status of the response is captured
+    None,
+}
+
+/// A class of response statuses, deserialized from `"1xx"` to `"5xx"` or `"any"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum StatusClass {
+    #[serde(rename = "1xx")]
+    Informational,
+    #[serde(rename = "2xx")]
+    Success,
+    #[serde(rename = "3xx")]
+    Redirection,
+    #[serde(rename = "4xx")]
+    ClientError,
+    #[serde(rename = "5xx")]
+    ServerError,
+    #[serde(rename = "any")]
+    Any,
+}
+
+impl StatusClass {
+    pub fn matches(&self, status: StatusCode) -> bool {
+        match self {
+            Self::Informational => status.is_informational(),
+            Self::Success => status.is_success(),
+            Self::Redirection => status.is_redirection(),
+            Self::ClientError => status.is_client_error(),
+            Self::ServerError => status.is_server_error(),
+            Self::Any => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CaptureRule {
+    route: Option<RoutePattern>,
+    status: StatusClass,
+    level: CaptureLevel,
+}
+
+/// The capture level of responses by their status class and route, responses matching no rule are [masked](CaptureLevel::Masked).
+/// The first matching rule is used, so more specific rules have to be added first.
+///
+/// Routes are an optional method and a path matched against the path hint of the request, as for
+/// [Masking::with_route_masking](crate::Masking::with_route_masking), rules with a route never match requests without a path hint.
+///
+/// Can be deserialized from a list of `{ "route": "POST /auth/*", "status": "2xx", "level": "headers_only" }` rules,
+/// the route is optional
+///
+/// # Examples
+/// ```rust
+/// use speakeasy_rust_sdk::capture::{CaptureLevel, CaptureRules, StatusClass};
+///
+/// let mut capture_rules = CaptureRules::default();
+/// // success responses carry tokens, errors are kept for debugging
+/// capture_rules.with_route_level("POST /auth/*", StatusClass::Success, CaptureLevel::HeadersOnly);
+/// capture_rules.with_route_level("POST /auth/*", StatusClass::ClientError, CaptureLevel::Full);
+/// // nothing but the status of server errors
+/// capture_rules.with_status_level(StatusClass::ServerError, CaptureLevel::None);
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "Vec<CaptureRuleConfig>")]
+pub struct CaptureRules {
+    rules: Vec<CaptureRule>,
+}
+
+impl CaptureRules {
+    /// with_status_level sets the capture level of responses with the status class on every route
+    pub fn with_status_level(&mut self, status: StatusClass, level: CaptureLevel) {
+        self.rules.push(CaptureRule {
+            route: None,
+            status,
+            level,
+        });
+    }
+
+    /// with_route_level sets the capture level of responses with the status class on requests matching the route,
+    /// invalid routes are logged and ignored
+    pub fn with_route_level(&mut self, route: &str, status: StatusClass, level: CaptureLevel) {
+        if let Err(err) = self.try_with_route_level(route, status, level) {
+            error!("{}", err);
+        }
+    }
+
+    /// Same as [with_route_level](CaptureRules::with_route_level) but returns an error for invalid routes
+    pub fn try_with_route_level(
+        &mut self,
+        route: &str,
+        status: StatusClass,
+        level: CaptureLevel,
+    ) -> Result<(), Error> {
+        let route =
+            RoutePattern::parse(route).ok_or_else(|| Error::InvalidRoute(route.to_string()))?;
+
+        self.rules.push(CaptureRule {
+            route: Some(route),
+            status,
+            level,
+        });
+
+        Ok(())
+    }
+
+    /// The capture level of a response, `path_hint` is the normalized path hint of the request if there is one
+    pub(crate) fn level(
+        &self,
+        method: &str,
+        path_hint: Option<&str>,
+        status: StatusCode,
+    ) -> CaptureLevel {
+        self.rules
+            .iter()
+            .find(|rule| {
+                let matches_route = match (&rule.route, path_hint) {
+                    (None, _) => true,
+                    (Some(route), Some(path_hint)) => route.matches(method, path_hint),
+                    (Some(_), None) => false,
+                };
+
+                matches_route && rule.status.matches(status)
+            })
+            .map(|rule| rule.level)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CaptureRuleConfig {
+    route: Option<String>,
+    status: StatusClass,
+    level: CaptureLevel,
+}
+
+impl TryFrom<Vec<CaptureRuleConfig>> for CaptureRules {
+    type Error = Error;
+
+    fn try_from(configs: Vec<CaptureRuleConfig>) -> Result<Self, Self::Error> {
+        let mut capture_rules = CaptureRules::default();
+
+        for config in configs {
+            match config.route {
+                Some(route) => {
+                    capture_rules.try_with_route_level(&route, config.status, config.level)?
+                }
+                None => capture_rules.with_status_level(config.status, config.level),
+            }
+        }
+
+        Ok(capture_rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        method: &'static str,
+        path_hint: Option<&'static str>,
+        status: u16,
+        expected: CaptureLevel,
+    }
+
+    #[test]
+    fn chooses_capture_level() {
+        let capture_rules: CaptureRules = serde_json::from_str(
+            r#"[
+                { "route": "POST /auth/*", "status": "2xx", "level": "headers_only" },
+                { "route": "/auth/*", "status": "4xx", "level": "full" },
+                { "status": "5xx", "level": "none" }
+            ]"#,
+        )
+        .unwrap();
+
+        let tests = vec![
+            Test {
+                name: "drops auth success bodies",
+                method: "POST",
+                path_hint: Some("/auth/login"),
+                status: 200,
+                expected: CaptureLevel::HeadersOnly,
+            },
+            Test {
+                name: "keeps auth errors",
+                method: "POST",
+                path_hint: Some("/auth/login"),
+                status: 401,
+                expected: CaptureLevel::Full,
+            },
+            Test {
+                name: "method has to match",
+                method: "GET",
+                path_hint: Some("/auth/login"),
+                status: 200,
+                expected: CaptureLevel::Masked,
+            },
+            Test {
+                name: "routes don't match without a path hint",
+                method: "POST",
+                path_hint: None,
+                status: 200,
+                expected: CaptureLevel::Masked,
+            },
+            Test {
+                name: "status rules match every route",
+                method: "GET",
+                path_hint: None,
+                status: 503,
+                expected: CaptureLevel::None,
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(
+                capture_rules.level(
+                    test.method,
+                    test.path_hint,
+                    StatusCode::from_u16(test.status).unwrap()
+                ),
+                test.expected
+            );
+        }
+    }
+}
@@ -1,20 +1,25 @@
-//! Control [masking](Controller::set_masking()), [path_hint](Controller::set_path_hint()) and [customer_id](Controller::set_customer_id()) on a per request basis
+//! Control [masking](Controller::set_masking()), [per request masks](Controller::masking_mut()), [capture rules](Controller::set_capture_rules()), [path_hint](Controller::set_path_hint()) and [customer_id](Controller::set_customer_id()) on a per request basis
 
 // 1MB
 pub(crate) const MAX_SIZE: usize = 1024 * 1024;
 
-use crate::speakeasy_protos::ingest::IngestRequest;
+use crate::speakeasy_protos::ingest::{ingest_request::MaskingMetadata, IngestRequest};
 
 use crate::{
     async_runtime,
+    capture::{CaptureLevel, CaptureRules},
     generic_http::{GenericRequest, GenericResponse},
-    har_builder::HarBuilder,
+    har_builder::{self, HarBuilder},
+    masking::{stream::StreamingMasker, Fields, Location},
     path_hint,
     transport::Transport,
     Error, GenericSpeakeasySdk, Masking, RequestConfig,
 };
 
-// Control [masking](Controller::set_masking()), [path_hint](Controller::set_path_hint()) and [customer_id](Controller::set_customer_id()) on a per request basis
+#[cfg(feature = "protobuf")]
+use crate::protobuf::ProtobufDecoding;
+
+// Control [masking](Controller::set_masking()), [per request masks](Controller::masking_mut()), [capture rules](Controller::set_capture_rules()), [path_hint](Controller::set_path_hint()) and [customer_id](Controller::set_customer_id()) on a per request basis
 #[derive(Debug, Clone)]
 pub struct Controller<T: Transport> {
     transport: T,
@@ -22,11 +27,19 @@ pub struct Controller<T: Transport> {
 
     request: Option<GenericRequest>,
 
-    masking: Masking,
+    base_masking: Masking,
+    // masks layered on top of the SDK's masks and the names opted out of them, for this request only
+    request_masking: Masking,
+    removed_masks: Vec<(Location, Fields)>,
+    capture_rules: CaptureRules,
+    #[cfg(feature = "protobuf")]
+    protobuf: ProtobufDecoding,
     path_hint: Option<String>,
     customer_id: Option<String>,
 
     pub(crate) max_capture_size: usize,
+    max_file_capture_size: usize,
+    har_masking_report: bool,
 }
 
 // Public
@@ -36,14 +49,23 @@ where
 {
     #[doc(hidden)]
     pub fn new(sdk: &GenericSpeakeasySdk<T>) -> Self {
+        let settings = sdk.settings.load();
+
         Self {
             transport: sdk.transport.clone(),
             config: sdk.config.clone(),
             request: None,
-            masking: sdk.masking.clone(),
+            base_masking: settings.masking.clone(),
+            request_masking: Masking::default(),
+            removed_masks: Vec::new(),
+            capture_rules: settings.capture_rules.clone(),
+            #[cfg(feature = "protobuf")]
+            protobuf: settings.protobuf.clone(),
             path_hint: None,
             customer_id: None,
-            max_capture_size: MAX_SIZE,
+            max_capture_size: settings.max_capture_size,
+            max_file_capture_size: settings.max_file_capture_size,
+            har_masking_report: settings.har_masking_report,
         }
     }
 
@@ -55,7 +77,43 @@ where
 
     /// Set new masking for the request, see [Masking](crate::masking::Masking) for more
     pub fn set_masking(&mut self, masking: Masking) {
-        self.masking = masking
+        self.base_masking = masking
+    }
+
+    /// Masks set on the returned [Masking] are layered on top of the SDK's masks for this request only,
+    /// they win for names masked by both
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::{controller::Controller, masking::StringMaskingOption, transport::Transport};
+    ///
+    /// fn handler<T: Transport + Send + Clone + 'static>(controller: &mut Controller<T>) {
+    ///     controller
+    ///         .masking_mut()
+    ///         .with_request_field_mask_string("card_number", StringMaskingOption::KeepLast(4));
+    /// }
+    /// ```
+    pub fn masking_mut(&mut self) -> &mut Masking {
+        &mut self.request_masking
+    }
+
+    /// Layers the masks on top of the SDK's masks and the masks already added for this request,
+    /// see [masking_mut](Controller::masking_mut)
+    pub fn add_masking(&mut self, masking: Masking) {
+        self.request_masking.merge(masking)
+    }
+
+    /// Stops masking and removing the given names at the location for this request only,
+    /// only the SDK's masks are changed, masks added for this request still apply.
+    /// See [Masking::without]
+    pub fn remove_masking(&mut self, location: Location, fields: impl Into<Fields>) {
+        self.removed_masks.push((location, fields.into()))
+    }
+
+    /// Set new capture rules for the request, they choose how much of the response is captured by its status,
+    /// see [CaptureRules](crate::capture::CaptureRules) for more
+    pub fn set_capture_rules(&mut self, capture_rules: CaptureRules) {
+        self.capture_rules = capture_rules
     }
 
     /// Set new customer_id for the request
@@ -67,6 +125,12 @@ where
     pub fn set_max_capture_size(&mut self, max_capture_size: usize) {
         self.max_capture_size = max_capture_size
     }
+
+    /// Set the max size of multipart file uploads whose content is captured, defaults to 0.
+    /// Files above this only have their name, content type and size captured
+    pub fn set_max_file_capture_size(&mut self, max_file_capture_size: usize) {
+        self.max_file_capture_size = max_file_capture_size
+    }
 }
 
 // Crate use only
@@ -78,34 +142,80 @@ where
         self.request = Some(request)
     }
 
+    /// A masker for the response body if it can be masked while it is sent,
+    /// bodies that are captured unmasked or need the allowlist or masking report are masked once they are captured
+    pub(crate) fn response_body_masker(
+        &self,
+        response: &GenericResponse,
+    ) -> Option<StreamingMasker> {
+        let request = self.request.as_ref()?;
+        let path_hint = self.path_hint(request);
+
+        let is_json = response
+            .headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|content_type| content_type.contains("application/json"));
+
+        let capture_level = self.capture_rules.level(
+            &request.method,
+            Some(path_hint.as_str()).filter(|path_hint| !path_hint.is_empty()),
+            response.status,
+        );
+
+        if !is_json || capture_level != CaptureLevel::Masked {
+            return None;
+        }
+
+        let masking = self.masking_for(request, &path_hint);
+
+        if masking.allowlist.is_some()
+            || !masking.report_hooks.is_empty()
+            || self.har_masking_report
+        {
+            return None;
+        }
+
+        Some(StreamingMasker::new(&masking))
+    }
+
     pub(crate) fn build_and_send_har(self, response: GenericResponse) -> Result<(), Error> {
         let request = self.request.clone().ok_or(Error::RequestNotSaved)?;
 
-        // look for path hint for request, if not look in the request
-        let path_hint = self
-            .path_hint
-            .as_ref()
-            .or(request.path_hint.as_ref())
-            .map(ToString::to_string)
-            .unwrap_or_else(|| "".to_string());
-
-        let masking = self.masking.clone();
+        let path_hint = self.path_hint(&request);
+        let masking = self.masking_for(&request, &path_hint);
 
-        let customer_id = self.customer_id.clone().unwrap_or_default();
+        let customer_id = self.customer_id.clone();
+        let capture_rules = self.capture_rules;
+        let har_masking_report = self.har_masking_report;
+        #[cfg(feature = "protobuf")]
+        let protobuf = self.protobuf;
 
         let max_capture_size = self.max_capture_size;
+        let max_file_capture_size = self.max_file_capture_size;
 
         let config = self.config.clone();
         let transport = self.transport;
 
         async_runtime::spawn_task(async move {
-            let har = HarBuilder::new(request, response, max_capture_size).build(&masking);
-            let har_json = serde_json::to_string(&har).expect("har will serialize to json");
+            let har_builder = HarBuilder::new(request, response, max_capture_size)
+                .with_max_file_capture_size(max_file_capture_size)
+                .with_path_hint(path_hint.clone())
+                .with_customer_id(customer_id.clone())
+                .with_capture_rules(capture_rules)
+                .with_har_masking_report(har_masking_report);
+            #[cfg(feature = "protobuf")]
+            let har_builder = har_builder.with_protobuf_decoding(protobuf);
+
+            let (har, masked, masking_report) = har_builder.build(&masking);
+            let har_json = har_builder::to_json(&har, masking_report.as_ref());
 
             let masking_metadata = if masking.is_empty() {
                 None
             } else {
-                Some(masking.into())
+                let mut masking_metadata: MaskingMetadata = masking.into();
+                masked.extend_metadata(&mut masking_metadata);
+                Some(masking_metadata)
             };
 
             let ingest = IngestRequest {
@@ -113,7 +223,7 @@ where
                 path_hint,
                 api_id: config.api_id,
                 version_id: config.version_id,
-                customer_id,
+                customer_id: customer_id.unwrap_or_default(),
                 masking_metadata,
             };
 
@@ -122,4 +232,23 @@ where
 
         Ok(())
     }
+
+    // look for path hint for request, if not look in the request
+    fn path_hint(&self, request: &GenericRequest) -> String {
+        self.path_hint
+            .as_ref()
+            .or(request.path_hint.as_ref())
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "".to_string())
+    }
+
+    /// The SDK's masks for the route with the masks added and removed for this request
+    fn masking_for(&self, request: &GenericRequest, path_hint: &str) -> Masking {
+        let mut masking = self.base_masking.for_route(&request.method, path_hint);
+        for (location, fields) in &self.removed_masks {
+            masking.without(*location, fields.clone());
+        }
+        masking.merge(self.request_masking.clone());
+        masking
+    }
 }
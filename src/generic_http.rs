@@ -2,7 +2,7 @@ use chrono::{DateTime, SecondsFormat, Utc};
 use har::v1_2::Cookies as HarCookie;
 use http::{version::Version, HeaderMap};
 
-use crate::masking::generic_mask::GenericMask;
+use crate::masking::stream::StreamingMasker;
 
 // len => 11
 pub(crate) const DROPPED_TEXT: &str = "--dropped--";
@@ -19,10 +19,11 @@ pub(crate) struct GenericCookie {
 }
 
 impl GenericCookie {
-    pub(crate) fn into_har_cookie<T>(self, masker: &GenericMask<T>) -> HarCookie {
+    /// The HAR cookie with its masked value
+    pub(crate) fn into_har_cookie(self, value: String) -> HarCookie {
         HarCookie {
             name: self.name.clone(),
-            value: masker.mask(&self.name, &self.value),
+            value,
             path: self.path.clone(),
             domain: self.domain.clone(),
             expires: self
@@ -44,6 +45,83 @@ pub enum BodyCapture {
     Captured(bytes::Bytes),
 }
 
+/// A captured response body, JSON bodies can be masked while they are sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ResponseBody {
+    Unmasked(BodyCapture),
+    // masked while it was sent, size is the size of the body before masking
+    Masked { text: String, size: usize },
+}
+
+impl From<BodyCapture> for ResponseBody {
+    fn from(body: BodyCapture) -> Self {
+        Self::Unmasked(body)
+    }
+}
+
+/// Captures a response body as it is sent, JSON bodies are masked chunk by chunk
+/// when the masking doesn't need the whole body.
+/// The body is kept until it is sent completely, bodies that turn out not to be valid JSON are masked once they are captured
+#[derive(Debug, Default)]
+pub(crate) struct ResponseBodyCapture {
+    body_accum: bytes::BytesMut,
+    masker: Option<StreamingMasker>,
+    body_dropped: bool,
+}
+
+impl ResponseBodyCapture {
+    pub(crate) fn new(masker: Option<StreamingMasker>) -> Self {
+        Self {
+            masker,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn push(&mut self, chunk: &[u8], max_size: usize) {
+        if self.body_dropped {
+            return;
+        }
+
+        self.body_accum.extend_from_slice(chunk);
+
+        if let Some(masker) = &mut self.masker {
+            masker.push(chunk);
+
+            if masker.is_invalid() {
+                self.masker = None;
+            }
+        }
+
+        if self.body_accum.len() > max_size {
+            self.body_dropped = true;
+            self.body_accum = bytes::BytesMut::new();
+            self.masker = None;
+        }
+    }
+
+    /// The captured body, the capture is empty afterwards
+    pub(crate) fn finish(&mut self) -> ResponseBody {
+        if self.body_dropped {
+            return BodyCapture::Dropped.into();
+        }
+
+        if self.body_accum.is_empty() {
+            return BodyCapture::Empty.into();
+        }
+
+        let body = std::mem::take(&mut self.body_accum).freeze();
+
+        match self.masker.take().and_then(|masker| masker.finish()) {
+            Some(text) => ResponseBody::Masked {
+                text,
+                size: body.len(),
+            },
+            // bodies that aren't valid JSON are masked like any other captured body
+            None => BodyCapture::Captured(body).into(),
+        }
+    }
+}
+
 /// A generic HTTP request, which can be converted to a HAR request
 /// A generic HTTP request, can be created from a request from a web framework
 #[derive(Debug, Clone)]
@@ -69,5 +147,5 @@ pub struct GenericResponse {
     pub(crate) http_version: Version,
     pub(crate) headers: HeaderMap,
     pub(crate) cookies: Vec<GenericCookie>,
-    pub(crate) body: BodyCapture,
+    pub(crate) body: ResponseBody,
 }
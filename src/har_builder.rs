@@ -1,37 +1,63 @@
+use std::{borrow::Cow, cell::RefCell};
+
 use chrono::Utc;
 use har::{
     v1_2::{
         Cache, Content, Cookies as HarCookie, Creator, Entries as HarEntry, Headers as HarHeader,
-        Log, PostData, QueryString, Request as HarRequest, Response as HarResponse, Timings,
+        Log, Params, PostData, QueryString, Request as HarRequest, Response as HarResponse,
+        Timings,
     },
     Har,
 };
-use http::{HeaderMap, StatusCode};
+use http::{header, HeaderMap, HeaderName, StatusCode};
 use url::Url;
 
 use crate::{
-    generic_http::{BodyCapture, GenericRequest, GenericResponse, DROPPED_TEXT},
-    masking::{
-        body_mask::RequestMask,
-        generic_mask::{GenericMask, QueryStringMask, RequestCookieMask, RequestHeaderMask},
-    },
+    capture::{CaptureLevel, CaptureRules},
+    generic_http::{BodyCapture, GenericRequest, GenericResponse, ResponseBody, DROPPED_TEXT},
     masking::{
-        body_mask::{BodyMask, ResponseMask},
-        generic_mask::{ResponseCookieMask, ResponseHeaderMask},
+        allowlist::{Decision, MaskedFields},
+        body_mask::BodyMask,
+        generic_mask::GenericMask,
+        masker::MaskingContext,
+        pii::PiiDetection,
+        removal::BodyRemoval,
+        report::{MaskingReport, MatchedRule},
+        xml, Location, DEFAULT_STRING_MASK, REMOVED_MASK,
     },
-    Masking,
+    multipart, path_hint, Masking,
 };
 
+#[cfg(feature = "protobuf")]
+use crate::protobuf::{self, ProtobufDecoding};
+
+// headers whose value is a URL, the query string masks are applied to them
+const URL_HEADERS: [HeaderName; 3] = [header::LOCATION, header::REFERER, header::CONTENT_LOCATION];
+
 #[derive(Debug, Clone)]
 pub struct HarBuilder {
     request: GenericRequest,
     response: GenericResponse,
 
     max_capture_size: usize,
+    max_file_capture_size: usize,
+    path_hint: Option<String>,
+    customer_id: Option<String>,
+    capture_rules: CaptureRules,
+    har_masking_report: bool,
+    #[cfg(feature = "protobuf")]
+    protobuf: ProtobufDecoding,
+    // the content type and message of bodies decoded from protobuf, added to the HAR as comments
+    decoded_request: Option<String>,
+    decoded_response: Option<String>,
 
     // helper to avoid cloning
     masked_full_url: Option<Url>,
     path_with_query: Option<String>,
+
+    // values masked by the allowlist, their masks aren't known before the HAR is built,
+    // and the masking report when the capture is reported
+    masked: RefCell<MaskedFields>,
 }
 
 impl HarBuilder {
@@ -44,19 +70,82 @@ impl HarBuilder {
             request,
             response,
             max_capture_size,
+            max_file_capture_size: 0,
+            path_hint: None,
+            customer_id: None,
+            capture_rules: CaptureRules::default(),
+            har_masking_report: false,
+            #[cfg(feature = "protobuf")]
+            protobuf: ProtobufDecoding::default(),
+            decoded_request: None,
+            decoded_response: None,
             masked_full_url: None,
             path_with_query: None,
+            masked: RefCell::new(MaskedFields::default()),
         }
     }
 
-    pub(crate) fn build(mut self, masking: &Masking) -> Har {
+    /// Capture the content of multipart file uploads up to this size, file parts only have their metadata captured by default
+    pub(crate) fn with_max_file_capture_size(mut self, max_file_capture_size: usize) -> Self {
+        self.max_file_capture_size = max_file_capture_size;
+        self
+    }
+
+    /// The normalized path hint of the request, used to mask path params
+    pub(crate) fn with_path_hint(mut self, path_hint: String) -> Self {
+        self.path_hint = Some(path_hint).filter(|path_hint| !path_hint.is_empty());
+        self
+    }
+
+    /// The customer id set on the controller, passed to the custom maskers
+    pub(crate) fn with_customer_id(mut self, customer_id: Option<String>) -> Self {
+        self.customer_id = customer_id;
+        self
+    }
+
+    /// The rules choosing how much of the response is captured by its status
+    pub(crate) fn with_capture_rules(mut self, capture_rules: CaptureRules) -> Self {
+        self.capture_rules = capture_rules;
+        self
+    }
+
+    /// Adds the masking report to the HAR, see [Settings::har_masking_report](crate::settings::Settings::har_masking_report)
+    pub(crate) fn with_har_masking_report(mut self, har_masking_report: bool) -> Self {
+        self.har_masking_report = har_masking_report;
+        self
+    }
+
+    /// The protobuf messages used to decode protobuf and gRPC bodies to JSON
+    #[cfg(feature = "protobuf")]
+    pub(crate) fn with_protobuf_decoding(mut self, protobuf: ProtobufDecoding) -> Self {
+        self.protobuf = protobuf;
+        self
+    }
+
+    /// Builds the HAR, also returns the values that were masked by the allowlist so they can be added to the masking metadata,
+    /// and the masking report if it is added to the HAR
+    pub(crate) fn build(mut self, masking: &Masking) -> (Har, MaskedFields, Option<MaskingReport>) {
+        // decoded bodies are masked and reported as JSON bodies
+        #[cfg(feature = "protobuf")]
+        self.decode_protobuf_bodies();
+
+        let capture_level = self.apply_capture_level();
+
+        // the report is recorded while the capture is masked, so it leaves out what is dropped
+        if !masking.report_hooks.is_empty() || self.har_masking_report {
+            self.masked = RefCell::new(MaskedFields::with_report());
+        }
+
         self.masked_full_url = self.get_masked_full_url(masking);
 
         let path = self
             .masked_full_url
             .as_ref()
             .map(|u| u.path().to_string())
-            .unwrap_or_else(|| self.request.path.clone());
+            .unwrap_or_else(|| {
+                self.mask_path(&self.request.path, masking)
+                    .unwrap_or_else(|| self.request.path.clone())
+            });
 
         let path_with_query =
             if let Some(query) = self.masked_full_url.as_ref().and_then(|u| u.query()) {
@@ -71,25 +160,48 @@ impl HarBuilder {
 
         self.path_with_query = Some(path_with_query);
 
-        Har {
+        let mut request = self.build_request(masking);
+        let mut response = self.build_response(masking, capture_level);
+
+        if let Some(post_data) = request.post_data.as_mut() {
+            post_data.comment = self.decoded_request.take();
+        }
+        response.content.comment = self.decoded_response.take();
+
+        let masking_report = self.masked.borrow_mut().take_report();
+
+        // custom maskers run after all of the declarative masks
+        let context = MaskingContext {
+            path_hint: self.path_hint.as_deref(),
+            customer_id: self.customer_id.as_deref(),
+            method: &self.request.method,
+            host: &self.request.host,
+            request_headers: &self.request.headers,
+            status: self.response.status,
+        };
+        masking.maskers.mask(&context, &mut request, &mut response);
+
+        if let Some(masking_report) = &masking_report {
+            masking.report_hooks.call(&context, masking_report);
+        }
+        let masking_report = masking_report.filter(|_| self.har_masking_report);
+
+        let har = Har {
             log: har::Spec::V1_2(Log {
                 creator: Creator {
                     name: "speakeasy-rust-sdk".to_string(),
                     version: env!("CARGO_PKG_VERSION").to_string(),
                     ..Default::default()
                 },
-                comment: Some(format!(
-                    "request capture for {}",
-                    &self.path_with_query.as_ref().expect("just set above")
-                )),
+                comment: Some(format!("request capture for {}", &request.url)),
                 entries: vec![HarEntry {
                     started_date_time: self.request.start_time.to_rfc3339(),
                     time: Utc::now()
                         .signed_duration_since(self.request.start_time)
                         .num_milliseconds()
                         .abs() as f64,
-                    request: self.build_request(masking),
-                    response: self.build_response(masking),
+                    request,
+                    response,
                     cache: Cache::default(),
                     timings: Timings {
                         send: -1.0,
@@ -103,6 +215,57 @@ impl HarBuilder {
                 }],
                 ..Default::default()
             }),
+        };
+
+        (har, self.masked.into_inner(), masking_report)
+    }
+
+    /// Replaces protobuf and gRPC bodies of registered messages with their JSON, they are then masked as JSON bodies
+    /// and get an `application/json` mime type while the captured `Content-Type` header is kept.
+    /// Bodies of registered messages that can't be decoded are dropped, they would only be masked for PII
+    #[cfg(feature = "protobuf")]
+    fn decode_protobuf_bodies(&mut self) {
+        if self.protobuf.is_empty() {
+            return;
+        }
+
+        let method = self.request.method.as_str();
+        let path_hint = self.path_hint.as_deref();
+
+        if let BodyCapture::Captured(body) = &self.request.body {
+            let content_type = content_type(&self.request.headers);
+
+            if let Some(message) = protobuf::is_protobuf(content_type)
+                .then(|| self.protobuf.request_message(method, path_hint))
+                .flatten()
+            {
+                match protobuf::decode(message, content_type, body) {
+                    Some(decoded) => {
+                        self.decoded_request =
+                            Some(decoded_comment(content_type, &decoded.message));
+                        self.request.body = BodyCapture::Captured(decoded.json.into());
+                    }
+                    None => self.request.body = BodyCapture::Dropped,
+                }
+            }
+        }
+
+        if let ResponseBody::Unmasked(BodyCapture::Captured(body)) = &self.response.body {
+            let content_type = content_type(&self.response.headers);
+
+            if let Some(message) = protobuf::is_protobuf(content_type)
+                .then(|| self.protobuf.response_message(method, path_hint))
+                .flatten()
+            {
+                match protobuf::decode(message, content_type, body) {
+                    Some(decoded) => {
+                        self.decoded_response =
+                            Some(decoded_comment(content_type, &decoded.message));
+                        self.response.body = BodyCapture::Captured(decoded.json.into()).into();
+                    }
+                    None => self.response.body = BodyCapture::Dropped.into(),
+                }
+            }
         }
     }
 
@@ -132,24 +295,56 @@ impl HarBuilder {
                 .expect("path_with_query should be set")
                 .clone(),
             http_version: format!("{:?}", self.request.http_version),
-            cookies: self.build_request_cookies(&masking.request_cookie_mask),
-            headers: self.build_request_headers(&masking.request_header_mask),
-            query_string: self.build_query_string(&masking.query_string_mask),
+            cookies: self.build_request_cookies(masking),
+            headers: self.build_request_headers(masking),
+            query_string: self.build_query_string(masking),
             headers_size: build_headers_size(&self.request.headers),
             body_size,
-            post_data: self.build_body_post_data(&masking.request_masks),
+            post_data: self.build_body_post_data(masking),
             comment: None,
         }
     }
 
-    fn build_response(&mut self, masking: &Masking) -> HarResponse {
+    /// Drops what the max capture size and the capture level of the response leave out, returns the capture level
+    fn apply_capture_level(&mut self) -> CaptureLevel {
         // drop body if controller was used to set a lower max capture size (response)
-        if let BodyCapture::Captured(body) = &self.response.body {
-            if body.len() > self.max_capture_size {
-                self.response.body = BodyCapture::Dropped
+        let body_size = match &self.response.body {
+            ResponseBody::Unmasked(BodyCapture::Captured(body)) => body.len(),
+            ResponseBody::Masked { size, .. } => *size,
+            ResponseBody::Unmasked(BodyCapture::Empty | BodyCapture::Dropped) => 0,
+        };
+        if body_size > self.max_capture_size {
+            self.response.body = BodyCapture::Dropped.into()
+        }
+
+        let capture_level = self.capture_rules.level(
+            &self.request.method,
+            self.path_hint.as_deref(),
+            self.response.status,
+        );
+
+        match capture_level {
+            CaptureLevel::HeadersOnly => self.response.body = BodyCapture::Empty.into(),
+            CaptureLevel::None => {
+                self.response.body = BodyCapture::Empty.into();
+                self.response.headers.clear();
+                self.response.cookies.clear();
             }
+            CaptureLevel::Full | CaptureLevel::Masked => {}
         }
 
+        capture_level
+    }
+
+    fn build_response(&mut self, masking: &Masking, capture_level: CaptureLevel) -> HarResponse {
+        let cookies = self.build_response_cookies(masking);
+        let headers = self.build_response_headers(masking);
+        let content = if capture_level == CaptureLevel::Full {
+            self.build_unmasked_response_content()
+        } else {
+            self.build_response_content(masking)
+        };
+
         HarResponse {
             status: self.response.status.as_u16() as i64,
             status_text: self
@@ -160,52 +355,95 @@ impl HarBuilder {
                 .map(ToString::to_string)
                 .unwrap_or_else(|| self.response.status.to_string()),
             http_version: format!("{:?}", &self.response.http_version),
-            cookies: self.build_response_cookies(&masking.response_cookie_mask),
-            headers: self.build_response_headers(&masking.response_header_mask),
-            content: self.build_response_content(&masking.response_masks),
+            cookies,
+            headers,
+            content,
             redirect_url: self
                 .response
                 .headers
                 .get("location")
                 .and_then(|v| v.to_str().ok())
                 .filter(|v| !v.is_empty())
-                .map(ToString::to_string),
+                .map(|v| mask_url(v, masking, &mut self.masked.borrow_mut())),
             headers_size: build_headers_size(&self.response.headers),
             body_size: self.build_response_body_size(),
             comment: None,
         }
     }
 
-    fn build_request_cookies(&self, masker: &GenericMask<RequestCookieMask>) -> Vec<HarCookie> {
+    fn build_request_cookies(&self, masking: &Masking) -> Vec<HarCookie> {
         self.request
             .cookies
             .clone()
             .into_iter()
-            .map(|c| c.into_har_cookie(masker))
+            .filter(|c| !self.is_removed(masking, Location::RequestCookie, &c.name))
+            .filter_map(|c| {
+                let value = match self.apply_allowlist(masking, Location::RequestCookie, &c.name) {
+                    Decision::Remove => return None,
+                    Decision::Mask => DEFAULT_STRING_MASK.to_string(),
+                    Decision::Allow => self.mask_value(
+                        masking,
+                        &masking.request_cookie_mask,
+                        Location::RequestCookie,
+                        &c.name,
+                        &c.value,
+                    ),
+                };
+
+                Some(c.into_har_cookie(value))
+            })
             .collect()
     }
 
-    fn build_request_headers(&self, masker: &GenericMask<RequestHeaderMask>) -> Vec<HarHeader> {
+    fn build_request_headers(&self, masking: &Masking) -> Vec<HarHeader> {
         self.request
             .headers
             .iter()
-            .map(|(name, value)| HarHeader {
-                name: name.to_string(),
-                value: masker.mask(name.as_str(), value.to_str().unwrap_or("")),
-                comment: None,
+            .filter(|(name, _)| !self.is_removed(masking, Location::RequestHeader, name.as_str()))
+            .filter_map(|(name, value)| {
+                let value =
+                    match self.apply_allowlist(masking, Location::RequestHeader, name.as_str()) {
+                        Decision::Remove => return None,
+                        Decision::Mask => DEFAULT_STRING_MASK.to_string(),
+                        Decision::Allow => {
+                            let masked = &mut self.masked.borrow_mut();
+                            let mut value = apply_mask(
+                                &masking.request_header_mask,
+                                Location::RequestHeader,
+                                name.as_str(),
+                                value.to_str().unwrap_or(""),
+                                masked,
+                            );
+
+                            if URL_HEADERS.contains(name) {
+                                value = mask_url(&value, masking, masked);
+                            }
+
+                            masked.redact(
+                                &masking.pii_detection,
+                                Location::RequestHeader,
+                                name.as_str(),
+                                &value,
+                            )
+                        }
+                    };
+
+                Some(HarHeader {
+                    name: name.to_string(),
+                    value,
+                    comment: None,
+                })
             })
             .collect()
     }
 
-    fn build_query_string(
-        &self,
-        query_string_mask: &GenericMask<QueryStringMask>,
-    ) -> Vec<QueryString> {
+    fn build_query_string(&self, masking: &Masking) -> Vec<QueryString> {
         if let Some(url) = &self.request.full_url {
-            url.query_pairs()
+            mask_query_pairs(url.query_pairs(), masking, &mut self.masked.borrow_mut())
+                .into_iter()
                 .map(|(name, value)| QueryString {
-                    name: name.to_string(),
-                    value: query_string_mask.mask(&name, &value),
+                    name,
+                    value,
                     comment: None,
                 })
                 .collect()
@@ -214,7 +452,7 @@ impl HarBuilder {
         }
     }
 
-    fn build_body_post_data(&self, masker: &BodyMask<RequestMask>) -> Option<PostData> {
+    fn build_body_post_data(&self, masking: &Masking) -> Option<PostData> {
         if self.request.body == BodyCapture::Empty {
             return None;
         }
@@ -222,25 +460,76 @@ impl HarBuilder {
         match self.request.body {
             BodyCapture::Empty => None,
             BodyCapture::Captured(ref text) => {
-                let content_type = self
-                    .request
-                    .headers
-                    .get(http::header::CONTENT_TYPE)
-                    .and_then(|value| value.to_str().ok())
-                    .unwrap_or("");
+                let content_type = self.request_mime_type();
+
+                if multipart::is_multipart(content_type) {
+                    let params = multipart::boundary(content_type)
+                        .and_then(|boundary| multipart::parse(text, boundary))
+                        .map(|parts| self.build_multipart_params(parts, masking));
+
+                    // bodies that can't be parsed are dropped, they can hold files and fields that would not be masked
+                    return Some(match params {
+                        Some(params) => PostData {
+                            mime_type: content_type.to_string(),
+                            text: None,
+                            params: Some(params),
+                            ..Default::default()
+                        },
+                        None => PostData {
+                            mime_type: content_type.to_string(),
+                            text: Some(DROPPED_TEXT.to_string()),
+                            params: Some(vec![]),
+                            ..Default::default()
+                        },
+                    });
+                }
 
                 let body_str = String::from_utf8_lossy(text);
 
-                let body_string = if content_type.contains("application/json") {
-                    masker.mask(&body_str)
+                let (body_string, params) = if content_type.contains("application/json") {
+                    self.report_json_body(masking, Location::RequestField, &body_str);
+
+                    let body_string = mask_json_body(
+                        &self.allowlist_json(&body_str, masking, Location::RequestField),
+                        &masking.request_masks,
+                        &masking.request_field_removal,
+                        &masking.pii_detection,
+                    );
+                    (body_string, vec![])
+                } else if content_type.contains("application/x-www-form-urlencoded") {
+                    mask_form_body(&body_str, masking, &mut self.masked.borrow_mut())
+                } else if masking.allowlist.is_some() {
+                    // bodies that can't be filtered by the allowlist are masked completely
+                    self.masked.borrow_mut().report(
+                        Location::RequestField,
+                        "$",
+                        MatchedRule::Allowlist,
+                    );
+                    (DEFAULT_STRING_MASK.to_string(), vec![])
+                } else if xml::is_xml(content_type) {
+                    self.report_xml_body(masking, Location::RequestField, &body_str);
+
+                    let body_string = mask_xml_body(
+                        &body_str,
+                        &masking.request_masks,
+                        &masking.request_field_removal,
+                        &masking.pii_detection,
+                    );
+                    (body_string, vec![])
                 } else {
-                    body_str.to_string()
+                    let body_string = self.masked.borrow_mut().redact(
+                        &masking.pii_detection,
+                        Location::RequestField,
+                        "$",
+                        &body_str,
+                    );
+                    (body_string, vec![])
                 };
 
                 Some(PostData {
                     mime_type: content_type.to_string(),
                     text: Some(body_string),
-                    params: Some(vec![]),
+                    params: Some(params),
                     ..Default::default()
                 })
             }
@@ -262,55 +551,192 @@ impl HarBuilder {
         }
     }
 
-    fn build_response_cookies(&self, masker: &GenericMask<ResponseCookieMask>) -> Vec<HarCookie> {
+    fn build_multipart_params(
+        &self,
+        parts: Vec<multipart::Part>,
+        masking: &Masking,
+    ) -> Vec<Params> {
+        parts
+            .into_iter()
+            .filter(|part| !self.is_removed(masking, Location::RequestField, &part.name))
+            .filter_map(|part| {
+                let decision = self.apply_allowlist(masking, Location::RequestField, &part.name);
+
+                if decision == Decision::Remove {
+                    return None;
+                }
+
+                let param = if part.is_file() {
+                    // file contents are only captured if they fit in the file capture size
+                    let value = if decision == Decision::Allow
+                        && part.body.len() <= self.max_file_capture_size
+                    {
+                        Some(String::from_utf8_lossy(part.body).into_owned())
+                    } else {
+                        None
+                    };
+
+                    Params {
+                        value,
+                        comment: Some(format!("{} bytes", part.body.len())),
+                        name: part.name,
+                        file_name: part.file_name,
+                        content_type: part.content_type,
+                    }
+                } else if decision == Decision::Mask {
+                    Params {
+                        name: part.name,
+                        value: Some(DEFAULT_STRING_MASK.to_string()),
+                        content_type: part.content_type,
+                        ..Default::default()
+                    }
+                } else {
+                    let value = mask_request_field(
+                        masking,
+                        &part.name,
+                        &String::from_utf8_lossy(part.body),
+                        &mut self.masked.borrow_mut(),
+                    );
+
+                    Params {
+                        name: part.name,
+                        value: Some(value),
+                        content_type: part.content_type,
+                        ..Default::default()
+                    }
+                };
+
+                Some(param)
+            })
+            .collect()
+    }
+
+    fn build_response_cookies(&self, masking: &Masking) -> Vec<HarCookie> {
         self.response
             .cookies
             .clone()
             .into_iter()
-            .map(|c| c.into_har_cookie(masker))
+            .filter(|c| !self.is_removed(masking, Location::ResponseCookie, &c.name))
+            .filter_map(|c| {
+                let value = match self.apply_allowlist(masking, Location::ResponseCookie, &c.name) {
+                    Decision::Remove => return None,
+                    Decision::Mask => DEFAULT_STRING_MASK.to_string(),
+                    Decision::Allow => self.mask_value(
+                        masking,
+                        &masking.response_cookie_mask,
+                        Location::ResponseCookie,
+                        &c.name,
+                        &c.value,
+                    ),
+                };
+
+                Some(c.into_har_cookie(value))
+            })
             .collect()
     }
 
-    fn build_response_headers(&self, masker: &GenericMask<ResponseHeaderMask>) -> Vec<HarHeader> {
+    fn build_response_headers(&self, masking: &Masking) -> Vec<HarHeader> {
         self.response
             .headers
             .iter()
-            .map(|(name, value)| HarHeader {
-                name: name.to_string(),
-                value: masker.mask(name.as_str(), value.to_str().unwrap_or("")),
-                comment: None,
+            .filter(|(name, _)| !self.is_removed(masking, Location::ResponseHeader, name.as_str()))
+            .filter_map(|(name, value)| {
+                let value =
+                    match self.apply_allowlist(masking, Location::ResponseHeader, name.as_str()) {
+                        Decision::Remove => return None,
+                        Decision::Mask => DEFAULT_STRING_MASK.to_string(),
+                        Decision::Allow => {
+                            let masked = &mut self.masked.borrow_mut();
+                            let mut value = apply_mask(
+                                &masking.response_header_mask,
+                                Location::ResponseHeader,
+                                name.as_str(),
+                                value.to_str().unwrap_or(""),
+                                masked,
+                            );
+
+                            if URL_HEADERS.contains(name) {
+                                value = mask_url(&value, masking, masked);
+                            }
+
+                            masked.redact(
+                                &masking.pii_detection,
+                                Location::ResponseHeader,
+                                name.as_str(),
+                                &value,
+                            )
+                        }
+                    };
+
+                Some(HarHeader {
+                    name: name.to_string(),
+                    value,
+                    comment: None,
+                })
             })
             .collect()
     }
 
-    fn build_response_content(&self, masker: &BodyMask<ResponseMask>) -> Content {
-        let mime_type = self
-            .response
-            .headers
-            .get("content-type")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("application/octet-stream")
-            .to_string();
+    fn build_response_content(&mut self, masking: &Masking) -> Content {
+        let mime_type = self.response_mime_type();
+
+        // the body is moved into the HAR, masked bodies can be as large as the max capture size
+        let body = std::mem::replace(&mut self.response.body, BodyCapture::Empty.into());
 
-        match self.response.body {
-            BodyCapture::Empty => Content {
+        match body {
+            ResponseBody::Unmasked(BodyCapture::Empty) => Content {
                 size: -1,
                 mime_type: Some(mime_type),
                 ..Default::default()
             },
-            BodyCapture::Dropped => Content {
+            ResponseBody::Unmasked(BodyCapture::Dropped) => Content {
                 size: -1,
                 text: Some(DROPPED_TEXT.to_string()),
                 mime_type: Some(mime_type),
                 ..Default::default()
             },
-            BodyCapture::Captured(ref text) => {
-                let body_str = String::from_utf8_lossy(text);
+            // masked while the response was sent
+            ResponseBody::Masked { text, size } => Content {
+                size: size as i64,
+                text: Some(text),
+                mime_type: Some(mime_type),
+                ..Default::default()
+            },
+            ResponseBody::Unmasked(BodyCapture::Captured(text)) => {
+                let body_str = String::from_utf8_lossy(&text);
 
                 let body_string = if mime_type.contains("application/json") {
-                    masker.mask(&body_str)
+                    self.report_json_body(masking, Location::ResponseField, &body_str);
+
+                    mask_json_body(
+                        &self.allowlist_json(&body_str, masking, Location::ResponseField),
+                        &masking.response_masks,
+                        &masking.response_field_removal,
+                        &masking.pii_detection,
+                    )
+                } else if masking.allowlist.is_some() {
+                    self.masked.borrow_mut().report(
+                        Location::ResponseField,
+                        "$",
+                        MatchedRule::Allowlist,
+                    );
+                    DEFAULT_STRING_MASK.to_string()
+                } else if xml::is_xml(&mime_type) {
+                    self.report_xml_body(masking, Location::ResponseField, &body_str);
+
+                    mask_xml_body(
+                        &body_str,
+                        &masking.response_masks,
+                        &masking.response_field_removal,
+                        &masking.pii_detection,
+                    )
                 } else {
-                    body_str.to_string()
+                    self.masked.borrow_mut().redact(
+                        &masking.pii_detection,
+                        Location::ResponseField,
+                        "$",
+                        &body_str,
+                    )
                 };
 
                 Content {
@@ -323,6 +749,45 @@ impl HarBuilder {
         }
     }
 
+    /// The response body as is, for responses captured at [CaptureLevel::Full]
+    fn build_unmasked_response_content(&mut self) -> Content {
+        match &self.response.body {
+            ResponseBody::Unmasked(BodyCapture::Captured(text)) => Content {
+                size: text.len() as i64,
+                text: Some(String::from_utf8_lossy(text).into_owned()),
+                mime_type: Some(self.response_mime_type()),
+                ..Default::default()
+            },
+            _ => self.build_response_content(&Masking::default()),
+        }
+    }
+
+    /// The content type of the captured request body, bodies decoded from protobuf are JSON
+    fn request_mime_type(&self) -> &str {
+        if self.decoded_request.is_some() {
+            return "application/json";
+        }
+
+        self.request
+            .headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("")
+    }
+
+    fn response_mime_type(&self) -> String {
+        if self.decoded_response.is_some() {
+            return "application/json".to_string();
+        }
+
+        self.response
+            .headers
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string()
+    }
+
     fn build_response_body_size(&self) -> i64 {
         if self.response.status == StatusCode::NOT_MODIFIED {
             0
@@ -338,18 +803,395 @@ impl HarBuilder {
     fn get_masked_full_url(&self, masking: &Masking) -> Option<Url> {
         let mut url = self.request.full_url.as_ref()?.clone();
 
-        let queries = url
-            .query_pairs()
-            .map(|(name, value)| {
-                let masked_value = masking.query_string_mask.mask(&name, &value);
-                (name.to_string(), masked_value)
-            })
-            .collect::<Vec<(String, String)>>();
+        if let Some(query) = url.query() {
+            // the query string is reported when it is masked for the HAR query string
+            let query = self
+                .masked
+                .borrow_mut()
+                .unreported(|masked| mask_url_pairs(query, masking, masked));
+            url.set_query(
+                Some(&query)
+                    .filter(|query| !query.is_empty())
+                    .map(String::as_str),
+            );
+        }
 
-        url.query_pairs_mut().clear().extend_pairs(queries);
+        if let Some(path) = self.mask_path(url.path(), masking) {
+            url.set_path(&path);
+        }
 
         Some(url)
     }
+
+    /// Checks a value against the allowlist (if any) and records it if it's masked or removed
+    fn apply_allowlist(&self, masking: &Masking, location: Location, name: &str) -> Decision {
+        apply_allowlist(masking, location, name, &mut self.masked.borrow_mut())
+    }
+
+    fn is_removed(&self, masking: &Masking, location: Location, name: &str) -> bool {
+        is_removed(masking, location, name, &mut self.masked.borrow_mut())
+    }
+
+    /// Masks a header or cookie value with its mask and PII detection
+    fn mask_value<T>(
+        &self,
+        masking: &Masking,
+        mask: &GenericMask<T>,
+        location: Location,
+        name: &str,
+        value: &str,
+    ) -> String {
+        let masked = &mut self.masked.borrow_mut();
+        let value = apply_mask(mask, location, name, value, masked);
+        masked.redact(&masking.pii_detection, location, name, &value)
+    }
+
+    fn report_json_body(&self, masking: &Masking, location: Location, body: &str) {
+        if let Some(report) = self.masked.borrow_mut().report_mut() {
+            report.json_body(masking, location, body);
+        }
+    }
+
+    /// XML bodies are only reported for PII
+    fn report_xml_body(&self, masking: &Masking, location: Location, body: &str) {
+        if let Some(report) = self.masked.borrow_mut().report_mut() {
+            report.pii(&masking.pii_detection, location, "$", body);
+        }
+    }
+
+    /// Applies the allowlist to a JSON body, bodies that are not valid JSON are masked completely
+    fn allowlist_json<'a>(
+        &self,
+        body: &'a str,
+        masking: &Masking,
+        location: Location,
+    ) -> Cow<'a, str> {
+        match &masking.allowlist {
+            Some(allowlist) => Cow::Owned(
+                allowlist
+                    .mask_json(body, location, &mut self.masked.borrow_mut())
+                    .unwrap_or_else(|| DEFAULT_STRING_MASK.to_string()),
+            ),
+            None => Cow::Borrowed(body),
+        }
+    }
+
+    fn mask_path(&self, path: &str, masking: &Masking) -> Option<String> {
+        if masking.path_param_mask.is_empty() {
+            return None;
+        }
+
+        path_hint::mask_params(self.path_hint.as_ref()?, path, |name, value| {
+            if masking.path_param_mask.is_masked(name) {
+                self.masked
+                    .borrow_mut()
+                    .report(Location::PathParam, name, MatchedRule::String);
+                Some(masking.path_param_mask.mask(name, value))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+pub(crate) fn mask_json_body<T: Default>(
+    body: &str,
+    masker: &BodyMask<T>,
+    removal: &BodyRemoval<T>,
+    pii_detection: &PiiDetection,
+) -> String {
+    let body = masker.mask(&removal.remove(body.to_string()));
+
+    if pii_detection.is_empty() {
+        body
+    } else {
+        pii_detection.redact_json(&body)
+    }
+}
+
+fn apply_allowlist(
+    masking: &Masking,
+    location: Location,
+    name: &str,
+    masked: &mut MaskedFields,
+) -> Decision {
+    let decision = match &masking.allowlist {
+        Some(allowlist) => allowlist.decide(location, name),
+        None => return Decision::Allow,
+    };
+
+    match decision {
+        Decision::Allow => return decision,
+        Decision::Mask => masked.record(location, name, DEFAULT_STRING_MASK),
+        Decision::Remove => masked.record(location, name, REMOVED_MASK),
+    }
+    masked.report(location, name, MatchedRule::Allowlist);
+
+    decision
+}
+
+/// Checks if a named value is removed, reporting the removal
+fn is_removed(
+    masking: &Masking,
+    location: Location,
+    name: &str,
+    masked: &mut MaskedFields,
+) -> bool {
+    let is_removed = match location {
+        Location::QueryString => masking.query_string_removal.is_removed(name),
+        Location::RequestHeader => masking.request_header_removal.is_removed(name),
+        Location::ResponseHeader => masking.response_header_removal.is_removed(name),
+        Location::RequestCookie => masking.request_cookie_removal.is_removed(name),
+        Location::ResponseCookie => masking.response_cookie_removal.is_removed(name),
+        Location::RequestField => masking.request_field_removal.is_removed(name),
+        Location::ResponseField => masking.response_field_removal.is_removed(name),
+        Location::PathParam => false,
+    };
+
+    if is_removed {
+        masked.report(location, name, MatchedRule::Removal);
+    }
+
+    is_removed
+}
+
+/// Masks a header, cookie or query string value, reporting the mask if the name is masked
+fn apply_mask<T>(
+    mask: &GenericMask<T>,
+    location: Location,
+    name: &str,
+    value: &str,
+    masked: &mut MaskedFields,
+) -> String {
+    if mask.is_masked(name) {
+        masked.report(location, name, MatchedRule::String);
+    }
+
+    mask.mask(name, value)
+}
+
+/// Masks a form or multipart field with the request field masks and PII detection,
+/// number masks only apply to values that parse as a number
+fn mask_request_field(
+    masking: &Masking,
+    name: &str,
+    value: &str,
+    masked: &mut MaskedFields,
+) -> String {
+    let masks = &masking.request_masks;
+
+    if masks.is_string_masked(name) {
+        masked.report(Location::RequestField, name, MatchedRule::String);
+    } else if masks.is_number_masked(name) && value.parse::<f64>().is_ok() {
+        masked.report(Location::RequestField, name, MatchedRule::Number);
+    }
+
+    let value = masks.mask_field(name, value);
+    masked.redact(&masking.pii_detection, Location::RequestField, name, &value)
+}
+
+fn mask_query_pairs<'a>(
+    pairs: impl Iterator<Item = (Cow<'a, str>, Cow<'a, str>)>,
+    masking: &Masking,
+    masked: &mut MaskedFields,
+) -> Vec<(String, String)> {
+    pairs
+        .filter_map(|(name, value)| {
+            let masked_value = mask_query_value(&name, &value, masking, masked)?;
+            Some((name.into_owned(), masked_value))
+        })
+        .collect()
+}
+
+/// Masks the value of a query string, `None` if it is removed
+fn mask_query_value(
+    name: &str,
+    value: &str,
+    masking: &Masking,
+    masked: &mut MaskedFields,
+) -> Option<String> {
+    if is_removed(masking, Location::QueryString, name, masked) {
+        return None;
+    }
+
+    match apply_allowlist(masking, Location::QueryString, name, masked) {
+        Decision::Remove => None,
+        Decision::Mask => Some(DEFAULT_STRING_MASK.to_string()),
+        Decision::Allow => {
+            let masked_value = apply_mask(
+                &masking.query_string_mask,
+                Location::QueryString,
+                name,
+                value,
+                masked,
+            );
+            Some(masked.redact(
+                &masking.pii_detection,
+                Location::QueryString,
+                name,
+                &masked_value,
+            ))
+        }
+    }
+}
+
+fn mask_url(url: &str, masking: &Masking, masked: &mut MaskedFields) -> String {
+    if masking.query_string_mask.is_empty()
+        && masking.query_string_removal.is_empty()
+        && masking.pii_detection.is_empty()
+        && masking.allowlist.is_none()
+    {
+        return url.to_string();
+    }
+
+    let (url, fragment) = match url.split_once('#') {
+        Some((url, fragment)) => (url, Some(fragment)),
+        None => (url, None),
+    };
+
+    let mut masked_url = match url.split_once('?') {
+        Some((path, query)) => {
+            let mut masked_url = path.to_string();
+            let query = mask_url_pairs(query, masking, masked);
+
+            if !query.is_empty() {
+                masked_url.push('?');
+                masked_url.push_str(&query);
+            }
+            masked_url
+        }
+        None => url.to_string(),
+    };
+
+    if let Some(fragment) = fragment {
+        masked_url.push('#');
+
+        if fragment.contains('=') {
+            masked_url.push_str(&mask_url_pairs(fragment, masking, masked));
+        } else {
+            masked_url.push_str(fragment);
+        }
+    }
+
+    masked_url
+}
+
+/// Masks `name=value` pairs of a query string or fragment,
+/// pairs that aren't masked are kept as they are so the URL only changes where it is masked
+fn mask_url_pairs(pairs: &str, masking: &Masking, masked: &mut MaskedFields) -> String {
+    let mut masked_pairs = Vec::new();
+
+    for pair in pairs.split('&') {
+        let (name, value) = match url::form_urlencoded::parse(pair.as_bytes()).next() {
+            Some(parsed) => parsed,
+            None => {
+                masked_pairs.push(Cow::Borrowed(pair));
+                continue;
+            }
+        };
+
+        match mask_query_value(&name, &value, masking, masked) {
+            None => {}
+            Some(masked_value) if masked_value == value => masked_pairs.push(Cow::Borrowed(pair)),
+            Some(masked_value) => {
+                let raw_name = pair.split_once('=').map_or(pair, |(name, _)| name);
+                let masked_value: String =
+                    url::form_urlencoded::byte_serialize(masked_value.as_bytes()).collect();
+                masked_pairs.push(Cow::Owned(format!("{}={}", raw_name, masked_value)));
+            }
+        }
+    }
+
+    masked_pairs.join("&")
+}
+
+/// Masks an XML body, bodies that are not valid XML are masked completely if there are field masks or removals,
+/// as the masked elements can't be found in them, otherwise only PII detection is applied
+fn mask_xml_body<T: Default>(
+    body: &str,
+    masker: &BodyMask<T>,
+    removal: &BodyRemoval<T>,
+    pii_detection: &PiiDetection,
+) -> String {
+    xml::mask(body, masker, removal, pii_detection).unwrap_or_else(|| {
+        if masker.is_empty() && removal.is_empty() {
+            pii_detection.redact(body).into_owned()
+        } else {
+            DEFAULT_STRING_MASK.to_string()
+        }
+    })
+}
+
+/// Parses a form-urlencoded body into HAR params with the request field masks applied,
+/// the returned text is the re-encoded masked body
+fn mask_form_body(
+    body: &str,
+    masking: &Masking,
+    masked: &mut MaskedFields,
+) -> (String, Vec<Params>) {
+    let params = url::form_urlencoded::parse(body.as_bytes())
+        .filter_map(|(name, value)| {
+            if is_removed(masking, Location::RequestField, &name, masked) {
+                return None;
+            }
+
+            let value = match apply_allowlist(masking, Location::RequestField, &name, masked) {
+                Decision::Remove => return None,
+                Decision::Mask => DEFAULT_STRING_MASK.to_string(),
+                Decision::Allow => mask_request_field(masking, &name, &value, masked),
+            };
+
+            Some(Params {
+                name: name.to_string(),
+                value: Some(value),
+                ..Default::default()
+            })
+        })
+        .collect::<Vec<Params>>();
+
+    let text = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(
+            params
+                .iter()
+                .map(|param| (&param.name, param.value.as_deref().unwrap_or_default())),
+        )
+        .finish();
+
+    (text, params)
+}
+
+/// Serializes the HAR, the masking report is added to the entry as the custom `_maskingReport` field
+pub(crate) fn to_json(har: &Har, masking_report: Option<&MaskingReport>) -> String {
+    let masking_report = match masking_report {
+        Some(masking_report) => masking_report,
+        None => return serde_json::to_string(har).expect("har will serialize to json"),
+    };
+
+    let mut har = serde_json::to_value(har).expect("har will serialize to json");
+    if let Some(entry) = har
+        .pointer_mut("/log/entries/0")
+        .and_then(|entry| entry.as_object_mut())
+    {
+        entry.insert(
+            "_maskingReport".to_string(),
+            serde_json::to_value(masking_report).expect("masking report will serialize to json"),
+        );
+    }
+
+    har.to_string()
+}
+
+#[cfg(feature = "protobuf")]
+fn content_type(headers: &HeaderMap) -> &str {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+}
+
+#[cfg(feature = "protobuf")]
+fn decoded_comment(content_type: &str, message: &str) -> String {
+    format!("decoded from {} as {}", content_type, message)
 }
 
 fn build_headers_size(headers: &HeaderMap) -> i64 {
@@ -364,3 +1206,659 @@ fn build_headers_size(headers: &HeaderMap) -> i64 {
 
     headers_size as i64
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        capture::StatusClass, controller::MAX_SIZE, generic_http::GenericCookie, masking::Location,
+    };
+    use http::{HeaderValue, Version};
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        body: &'static str,
+        expected_text: &'static str,
+        expected_params: Vec<(&'static str, &'static str)>,
+    }
+
+    pub(crate) fn request(content_type: &str, body: &'static [u8]) -> GenericRequest {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(content_type).unwrap(),
+        );
+
+        GenericRequest {
+            start_time: Utc::now(),
+            path_hint: None,
+            full_url: None,
+            method: "POST".to_string(),
+            host: "example.com".to_string(),
+            path: "/login".to_string(),
+            http_version: Version::HTTP_11,
+            headers,
+            cookies: vec![],
+            port: None,
+            body: BodyCapture::Captured(bytes::Bytes::from_static(body)),
+        }
+    }
+
+    pub(crate) fn response(content_type: &str, body: &'static [u8]) -> GenericResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(content_type).unwrap(),
+        );
+
+        GenericResponse {
+            status: StatusCode::OK,
+            http_version: Version::HTTP_11,
+            headers,
+            cookies: vec![],
+            body: BodyCapture::Captured(bytes::Bytes::from_static(body)).into(),
+        }
+    }
+
+    #[test]
+    fn masks_form_bodies() {
+        let mut masking = Masking::default();
+        masking.with_request_field_mask_string("password", "__masked__");
+        masking.with_request_field_mask_number("pin", -1);
+        masking.with_request_field_removal("client_secret");
+
+        let tests = vec![
+            Test {
+                name: "masks string and number fields",
+                body: "grant_type=password&username=jane&password=hunter%202&pin=1234",
+                expected_text: "grant_type=password&username=jane&password=__masked__&pin=-1",
+                expected_params: vec![
+                    ("grant_type", "password"),
+                    ("username", "jane"),
+                    ("password", "__masked__"),
+                    ("pin", "-1"),
+                ],
+            },
+            Test {
+                name: "removes fields and leaves non numeric number fields",
+                body: "client_secret=abc&pin=none&scope=read+write",
+                expected_text: "pin=none&scope=read+write",
+                expected_params: vec![("pin", "none"), ("scope", "read write")],
+            },
+        ];
+
+        for test in tests {
+            let (text, params) = mask_form_body(test.body, &masking, &mut Default::default());
+
+            assert_eq!(text, test.expected_text);
+            assert_eq!(
+                params
+                    .iter()
+                    .map(|p| (p.name.as_str(), p.value.as_deref().unwrap_or_default()))
+                    .collect::<Vec<_>>(),
+                test.expected_params
+            );
+        }
+    }
+
+    #[test]
+    fn masks_urls() {
+        struct Test {
+            #[allow(dead_code)]
+            name: &'static str,
+            url: &'static str,
+            expected: &'static str,
+        }
+
+        let mut masking = Masking::default();
+        masking.with_query_string_mask(vec!["code", "token", "access_token"], "__masked__");
+
+        let tests = vec![
+            Test {
+                name: "relative url with fragment",
+                url: "/cb?code=abc&state=1#done",
+                expected: "/cb?code=__masked__&state=1#done",
+            },
+            Test {
+                name: "absolute url",
+                url: "https://example.com/login?token=abc",
+                expected: "https://example.com/login?token=__masked__",
+            },
+            Test {
+                name: "url without query",
+                url: "https://example.com/login",
+                expected: "https://example.com/login",
+            },
+            Test {
+                name: "implicit flow fragment",
+                url: "https://example.com/cb#access_token=abc&token_type=bearer",
+                expected: "https://example.com/cb#access_token=__masked__&token_type=bearer",
+            },
+            Test {
+                name: "query and fragment",
+                url: "/cb?code=abc#token=def&state=1",
+                expected: "/cb?code=__masked__#token=__masked__&state=1",
+            },
+            Test {
+                name: "pairs that aren't masked are kept as they are",
+                url: "/search?q=a%20b&path=%2Fx%2Fy&code=a%20b&flag",
+                expected: "/search?q=a%20b&path=%2Fx%2Fy&code=__masked__&flag",
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(
+                mask_url(test.url, &masking, &mut Default::default()),
+                test.expected
+            );
+        }
+    }
+
+    #[test]
+    fn drops_malformed_multipart_bodies() {
+        struct Test {
+            #[allow(dead_code)]
+            name: &'static str,
+            content_type: &'static str,
+            body: &'static [u8],
+            expected_text: Option<&'static str>,
+            expected_params: Vec<(&'static str, &'static str)>,
+        }
+
+        let mut masking = Masking::default();
+        masking.with_request_field_mask_string("password", "__masked__");
+
+        let tests = vec![
+            Test {
+                name: "parses valid bodies",
+                content_type: "multipart/form-data; boundary=b",
+                body: b"--b\r\nContent-Disposition: form-data; name=\"password\"\r\n\r\nhunter2\r\n--b--\r\n",
+                expected_text: None,
+                expected_params: vec![("password", "__masked__")],
+            },
+            Test {
+                name: "truncated part",
+                content_type: "multipart/form-data; boundary=b",
+                body: b"--b\r\nContent-Disposition: form-data; name=\"password\"\r\n\r\nhunter2",
+                expected_text: Some(DROPPED_TEXT),
+                expected_params: vec![],
+            },
+            Test {
+                name: "wrong boundary",
+                content_type: "multipart/form-data; boundary=other",
+                body: b"--b\r\nContent-Disposition: form-data; name=\"password\"\r\n\r\nhunter2\r\n--b--\r\n",
+                expected_text: Some(DROPPED_TEXT),
+                expected_params: vec![],
+            },
+            Test {
+                name: "missing boundary",
+                content_type: "multipart/form-data",
+                body: b"--b\r\nContent-Disposition: form-data; name=\"password\"\r\n\r\nhunter2\r\n--b--\r\n",
+                expected_text: Some(DROPPED_TEXT),
+                expected_params: vec![],
+            },
+        ];
+
+        for test in tests {
+            let har_builder = HarBuilder::new(
+                request(test.content_type, test.body),
+                response("text/plain", b""),
+                MAX_SIZE,
+            );
+            let post_data = har_builder.build_body_post_data(&masking).unwrap();
+
+            assert_eq!(post_data.text.as_deref(), test.expected_text);
+            assert_eq!(
+                post_data
+                    .params
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|p| (p.name.as_str(), p.value.as_deref().unwrap_or_default()))
+                    .collect::<Vec<_>>(),
+                test.expected_params
+            );
+        }
+    }
+
+    #[test]
+    fn masks_invalid_xml_bodies() {
+        struct Test {
+            #[allow(dead_code)]
+            name: &'static str,
+            body: &'static str,
+            masked: bool,
+            expected: &'static str,
+        }
+
+        let mut masking = Masking::default();
+        masking.with_request_field_mask_string("Password", "__masked__");
+
+        let tests = vec![
+            Test {
+                name: "valid body",
+                body: "<Login><Password>hunter2</Password></Login>",
+                masked: true,
+                expected: "<Login><Password>__masked__</Password></Login>",
+            },
+            Test {
+                name: "truncated body",
+                body: "<Login><Password>hunter2</Pass",
+                masked: true,
+                expected: DEFAULT_STRING_MASK,
+            },
+            Test {
+                name: "truncated body without masks",
+                body: "<Login><Password>hunter2</Pass",
+                masked: false,
+                expected: "<Login><Password>hunter2</Pass",
+            },
+        ];
+
+        for test in tests {
+            let masking = if test.masked {
+                masking.clone()
+            } else {
+                Masking::default()
+            };
+
+            assert_eq!(
+                mask_xml_body(
+                    test.body,
+                    &masking.request_masks,
+                    &masking.request_field_removal,
+                    &masking.pii_detection
+                ),
+                test.expected
+            );
+        }
+    }
+
+    #[test]
+    fn reports_the_captured_response() {
+        struct Test {
+            #[allow(dead_code)]
+            name: &'static str,
+            level: CaptureLevel,
+            expected: Vec<(Location, &'static str)>,
+        }
+
+        let mut masking = Masking::default();
+        masking.with_response_header_mask("x-token", None);
+        masking.with_response_field_mask_string("password", None);
+
+        let tests = vec![
+            Test {
+                name: "masked",
+                level: CaptureLevel::Masked,
+                expected: vec![
+                    (Location::ResponseHeader, "x-token"),
+                    (Location::ResponseField, "$.password"),
+                ],
+            },
+            Test {
+                name: "full body isn't masked",
+                level: CaptureLevel::Full,
+                expected: vec![(Location::ResponseHeader, "x-token")],
+            },
+            Test {
+                name: "headers only",
+                level: CaptureLevel::HeadersOnly,
+                expected: vec![(Location::ResponseHeader, "x-token")],
+            },
+            Test {
+                name: "none",
+                level: CaptureLevel::None,
+                expected: vec![],
+            },
+        ];
+
+        for test in tests {
+            let mut capture_rules = CaptureRules::default();
+            capture_rules.with_status_level(StatusClass::Any, test.level);
+
+            let mut response = response("application/json", br#"{"password":"a"}"#);
+            response
+                .headers
+                .insert("x-token", HeaderValue::from_static("abc"));
+
+            let (_, _, report) = HarBuilder::new(request("text/plain", b""), response, MAX_SIZE)
+                .with_capture_rules(capture_rules)
+                .with_har_masking_report(true)
+                .build(&masking);
+
+            assert_eq!(
+                report
+                    .unwrap()
+                    .entries()
+                    .iter()
+                    .map(|entry| (entry.location, entry.path.as_str()))
+                    .collect::<Vec<_>>(),
+                test.expected
+            );
+        }
+    }
+
+    #[test]
+    fn applies_capture_levels() {
+        struct Test {
+            #[allow(dead_code)]
+            name: &'static str,
+            level: CaptureLevel,
+            expected_text: Option<&'static str>,
+            expected_headers: Vec<(&'static str, &'static str)>,
+            expected_cookies: Vec<(&'static str, &'static str)>,
+        }
+
+        let mut masking = Masking::default();
+        masking.with_response_header_mask("x-token", None);
+        masking.with_response_cookie_mask("session", None);
+        masking.with_response_field_mask_string("password", None);
+
+        let tests = vec![
+            Test {
+                name: "masked",
+                level: CaptureLevel::Masked,
+                expected_text: Some(r#"{"password":"__masked__"}"#),
+                expected_headers: vec![
+                    ("content-type", "application/json"),
+                    ("x-token", "__masked__"),
+                ],
+                expected_cookies: vec![("session", "__masked__")],
+            },
+            Test {
+                name: "full skips the body masking",
+                level: CaptureLevel::Full,
+                expected_text: Some(r#"{"password":"a"}"#),
+                expected_headers: vec![
+                    ("content-type", "application/json"),
+                    ("x-token", "__masked__"),
+                ],
+                expected_cookies: vec![("session", "__masked__")],
+            },
+            Test {
+                name: "headers only empties the content",
+                level: CaptureLevel::HeadersOnly,
+                expected_text: None,
+                expected_headers: vec![
+                    ("content-type", "application/json"),
+                    ("x-token", "__masked__"),
+                ],
+                expected_cookies: vec![("session", "__masked__")],
+            },
+            Test {
+                name: "none clears the headers and cookies",
+                level: CaptureLevel::None,
+                expected_text: None,
+                expected_headers: vec![],
+                expected_cookies: vec![],
+            },
+        ];
+
+        for test in tests {
+            let mut capture_rules = CaptureRules::default();
+            capture_rules.with_status_level(StatusClass::Any, test.level);
+
+            let mut response = response("application/json", br#"{"password":"a"}"#);
+            response
+                .headers
+                .insert("x-token", HeaderValue::from_static("abc"));
+            response.cookies.push(GenericCookie {
+                name: "session".to_string(),
+                value: "abc".to_string(),
+                path: None,
+                domain: None,
+                expires: None,
+                http_only: None,
+                secure: None,
+            });
+
+            let (har, _, _) = HarBuilder::new(request("text/plain", b""), response, MAX_SIZE)
+                .with_capture_rules(capture_rules)
+                .build(&masking);
+
+            let response = match har.log {
+                har::Spec::V1_2(log) => log.entries.into_iter().next().unwrap().response,
+                _ => unreachable!(),
+            };
+
+            assert_eq!(response.content.text.as_deref(), test.expected_text);
+            assert_eq!(
+                response
+                    .headers
+                    .iter()
+                    .map(|header| (header.name.as_str(), header.value.as_str()))
+                    .collect::<Vec<_>>(),
+                test.expected_headers
+            );
+            assert_eq!(
+                response
+                    .cookies
+                    .iter()
+                    .map(|cookie| (cookie.name.as_str(), cookie.value.as_str()))
+                    .collect::<Vec<_>>(),
+                test.expected_cookies
+            );
+        }
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn decodes_protobuf_bodies() {
+        use crate::protobuf::tests::{grpc_frame, user_message};
+        use prost_reflect::{prost::Message, DynamicMessage, Value};
+
+        struct Test {
+            #[allow(dead_code)]
+            name: &'static str,
+            path_hint: &'static str,
+            content_type: &'static str,
+            body: Vec<u8>,
+            expected_mime_type: &'static str,
+            expected_text: &'static str,
+            expected_comment: Option<&'static str>,
+        }
+
+        let user = user_message();
+
+        let mut message = DynamicMessage::new(user.clone());
+        message.set_field_by_name("user_name", Value::String("jane".to_string()));
+        let message = message.encode_to_vec();
+
+        let mut protobuf = ProtobufDecoding::default();
+        protobuf.with_route("POST /login", Some(user), None);
+
+        let mut masking = Masking::default();
+        masking.with_request_field_mask_string("user_name", None);
+
+        let tests = vec![
+            Test {
+                name: "decoded body",
+                path_hint: "/login",
+                content_type: "application/x-protobuf",
+                body: message.clone(),
+                expected_mime_type: "application/json",
+                expected_text: r#"{"user_name":"__masked__"}"#,
+                expected_comment: Some("decoded from application/x-protobuf as users.User"),
+            },
+            Test {
+                name: "body that can't be decoded",
+                path_hint: "/login",
+                content_type: "application/grpc",
+                body: grpc_frame(&message)[..4].to_vec(),
+                expected_mime_type: "application/grpc",
+                expected_text: DROPPED_TEXT,
+                expected_comment: None,
+            },
+            Test {
+                name: "other route",
+                path_hint: "/logout",
+                content_type: "application/x-protobuf",
+                body: b"user_name".to_vec(),
+                expected_mime_type: "application/x-protobuf",
+                expected_text: "user_name",
+                expected_comment: None,
+            },
+        ];
+
+        for test in tests {
+            let mut request = request(test.content_type, b"");
+            request.body = BodyCapture::Captured(test.body.into());
+
+            let (har, _, _) = HarBuilder::new(request, response("text/plain", b""), MAX_SIZE)
+                .with_path_hint(test.path_hint.to_string())
+                .with_protobuf_decoding(protobuf.clone())
+                .build(&masking);
+
+            let request = match har.log {
+                har::Spec::V1_2(log) => log.entries.into_iter().next().unwrap().request,
+                _ => unreachable!(),
+            };
+            let post_data = request.post_data.unwrap();
+
+            assert_eq!(post_data.mime_type, test.expected_mime_type);
+            assert_eq!(post_data.text.as_deref(), Some(test.expected_text));
+            assert_eq!(post_data.comment.as_deref(), test.expected_comment);
+            // the captured content type is kept
+            assert!(request
+                .headers
+                .iter()
+                .any(|header| header.name == "content-type" && header.value == test.content_type));
+        }
+    }
+
+    #[test]
+    fn runs_maskers_after_the_other_masks() {
+        use std::sync::{Arc, Mutex};
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+
+        let mut masking = Masking::default();
+        masking.with_request_header_mask("authorization", None);
+        masking.with_request_field_mask_string("password", None);
+        masking.with_response_field_mask_string("token", None);
+        masking.with_masker({
+            let seen = seen.clone();
+            move |context: &MaskingContext, request: &mut HarRequest, response: &mut HarResponse| {
+                let post_data = request.post_data.as_ref().unwrap();
+                seen.lock().unwrap().push(format!(
+                    "{} {} {:?} {:?} {}",
+                    context.method,
+                    context.path_hint.unwrap_or_default(),
+                    context.customer_id,
+                    context.request_headers.get(header::AUTHORIZATION),
+                    post_data.text.as_deref().unwrap_or_default(),
+                ));
+
+                response.content.text = Some(r#"{"token":"__first__"}"#.to_string());
+            }
+        });
+        // maskers run in the order they were added
+        masking.with_masker(
+            |_: &MaskingContext, _: &mut HarRequest, response: &mut HarResponse| {
+                let text = response.content.text.take().unwrap_or_default();
+                response.content.text = Some(text.replace("__first__", "__second__"));
+            },
+        );
+
+        let mut request = request("application/json", br#"{"password":"hunter2"}"#);
+        request.headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer abc"),
+        );
+
+        let (har, _, _) = HarBuilder::new(
+            request,
+            response("application/json", br#"{"token":"abc"}"#),
+            MAX_SIZE,
+        )
+        .with_path_hint("/login".to_string())
+        .with_customer_id(Some("customer".to_string()))
+        .build(&masking);
+
+        let entry = match har.log {
+            har::Spec::V1_2(log) => log.entries.into_iter().next().unwrap(),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            seen.lock().unwrap().as_slice(),
+            [r#"POST /login Some("customer") Some("Bearer abc") {"password":"__masked__"}"#]
+        );
+        assert_eq!(
+            entry.response.content.text.as_deref(),
+            Some(r#"{"token":"__second__"}"#)
+        );
+        assert!(entry
+            .request
+            .headers
+            .iter()
+            .any(|header| header.name == "authorization" && header.value == "__masked__"));
+    }
+
+    #[test]
+    fn maskers_see_dropped_and_binary_bodies() {
+        use std::sync::{Arc, Mutex};
+
+        struct Test {
+            #[allow(dead_code)]
+            name: &'static str,
+            content_type: &'static str,
+            body: &'static [u8],
+            max_capture_size: usize,
+            expected: Option<String>,
+        }
+
+        let tests = vec![
+            Test {
+                name: "body above the max capture size",
+                content_type: "application/json",
+                body: br#"{"password":"hunter2"}"#,
+                max_capture_size: 4,
+                expected: Some(DROPPED_TEXT.to_string()),
+            },
+            Test {
+                name: "binary body",
+                content_type: "image/png",
+                body: b"\x89PNG\r\n\x1a\n\xff",
+                max_capture_size: MAX_SIZE,
+                expected: Some(String::from_utf8_lossy(b"\x89PNG\r\n\x1a\n\xff").into_owned()),
+            },
+            Test {
+                name: "empty body",
+                content_type: "application/json",
+                body: b"",
+                max_capture_size: MAX_SIZE,
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            let seen = Arc::new(Mutex::new(Vec::new()));
+
+            let mut masking = Masking::default();
+            masking.with_masker({
+                let seen = seen.clone();
+                move |_: &MaskingContext, request: &mut HarRequest, _: &mut HarResponse| {
+                    seen.lock().unwrap().push(
+                        request
+                            .post_data
+                            .as_ref()
+                            .and_then(|post_data| post_data.text.clone()),
+                    );
+                }
+            });
+
+            let mut request = request(test.content_type, test.body);
+            if test.body.is_empty() {
+                request.body = BodyCapture::Empty;
+            }
+
+            HarBuilder::new(request, response("text/plain", b""), test.max_capture_size)
+                .build(&masking);
+
+            assert_eq!(seen.lock().unwrap().as_slice(), [test.expected]);
+        }
+    }
+}
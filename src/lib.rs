@@ -46,25 +46,25 @@ use speakeasy_rust_sdk::{middleware::actix3::Middleware, Config, SpeakeasySdk};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-   HttpServer::new(|| {
-        let config = Config {
-            // retrieve from Speakeasy API dashboard.
-            api_key: "YOUR API KEY HERE".to_string(),
-            // enter a name that you'd like to associate captured requests with.
-            // This name will show up in the Speakeasy dashboard. e.g. "PetStore" might be a good ApiID for a Pet Store's API.
-            // No spaces allowed.
-            api_id: "YOUR API ID HERE".to_string(),
-            // enter a version that you would like to associate captured requests with.
-            // The combination of ApiID (name) and VersionID will uniquely identify your requests in the Speakeasy Dashboard.
-            // e.g. "v1.0.0". You can have multiple versions for the same ApiID (if running multiple versions of your API)
-            version_id: "YOUR VERSION ID HERE".to_string(),
-        };
-
-        // Create a new Speakeasy SDK instance
-        let mut sdk = SpeakeasySdk::try_new(config).expect("API key is valid");
-
+    let config = Config {
+        // retrieve from Speakeasy API dashboard.
+        api_key: "YOUR API KEY HERE".to_string(),
+        // enter a name that you'd like to associate captured requests with.
+        // This name will show up in the Speakeasy dashboard. e.g. "PetStore" might be a good ApiID for a Pet Store's API.
+        // No spaces allowed.
+        api_id: "YOUR API ID HERE".to_string(),
+        // enter a version that you would like to associate captured requests with.
+        // The combination of ApiID (name) and VersionID will uniquely identify your requests in the Speakeasy Dashboard.
+        // e.g. "v1.0.0". You can have multiple versions for the same ApiID (if running multiple versions of your API)
+        version_id: "YOUR VERSION ID HERE".to_string(),
+    };
+
+    // Create a new Speakeasy SDK instance once, its clones share its masking and capture settings
+    let sdk = SpeakeasySdk::try_new(config).expect("API key is valid");
+
+   HttpServer::new(move || {
         // create middleware
-        let speakeasy_middleware = Middleware::new(sdk);
+        let speakeasy_middleware = Middleware::new(sdk.clone());
         let (request_capture, response_capture) = speakeasy_middleware.into();
 
         App::new()
@@ -162,38 +162,38 @@ use actix3::web::ReqData;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-   HttpServer::new(|| {
-        let config = Config {
-            // retrieve from Speakeasy API dashboard.
-            api_key: "YOUR API KEY HERE".to_string(),
-            // enter a name that you'd like to associate captured requests with.
-            // This name will show up in the Speakeasy dashboard. e.g. "PetStore" might be a good ApiID for a Pet Store's API.
-            // No spaces allowed.
-            api_id: "YOUR API ID HERE".to_string(),
-            // enter a version that you would like to associate captured requests with.
-            // The combination of ApiID (name) and VersionID will uniquely identify your requests in the Speakeasy Dashboard.
-            // e.g. "v1.0.0". You can have multiple versions for the same ApiID (if running multiple versions of your API)
-            version_id: "YOUR VERSION ID HERE".to_string(),
-        };
-
-        // Create a new Speakeasy SDK instance
-        let mut sdk = SpeakeasySdk::try_new(config).expect("API key is valid");
-
-        // Configure masking for query
-        sdk.masking().with_query_string_mask("secret", "********");
-        sdk.masking()
-            .with_query_string_mask("password", StringMaskingOption::default());
-
-        // Configure masking for request
-        sdk.masking()
-            .with_request_field_mask_string("password", StringMaskingOption::default());
-
-        // Configure masking for response
-        sdk.masking()
-            .with_response_field_mask_string("secret", StringMaskingOption::default());
-
+    let config = Config {
+        // retrieve from Speakeasy API dashboard.
+        api_key: "YOUR API KEY HERE".to_string(),
+        // enter a name that you'd like to associate captured requests with.
+        // This name will show up in the Speakeasy dashboard. e.g. "PetStore" might be a good ApiID for a Pet Store's API.
+        // No spaces allowed.
+        api_id: "YOUR API ID HERE".to_string(),
+        // enter a version that you would like to associate captured requests with.
+        // The combination of ApiID (name) and VersionID will uniquely identify your requests in the Speakeasy Dashboard.
+        // e.g. "v1.0.0". You can have multiple versions for the same ApiID (if running multiple versions of your API)
+        version_id: "YOUR VERSION ID HERE".to_string(),
+    };
+
+    // Create a new Speakeasy SDK instance once, its clones share its masking and capture settings
+    let mut sdk = SpeakeasySdk::try_new(config).expect("API key is valid");
+
+    // Configure masking for query
+    sdk.masking().with_query_string_mask("secret", "********");
+    sdk.masking()
+        .with_query_string_mask("password", StringMaskingOption::default());
+
+    // Configure masking for request
+    sdk.masking()
+        .with_request_field_mask_string("password", StringMaskingOption::default());
+
+    // Configure masking for response
+    sdk.masking()
+        .with_response_field_mask_string("secret", StringMaskingOption::default());
+
+   HttpServer::new(move || {
         // create middleware
-        let speakeasy_middleware = Middleware::new(sdk);
+        let speakeasy_middleware = Middleware::new(sdk.clone());
         let (request_capture, response_capture) = speakeasy_middleware.into();
 
         App::new()
@@ -225,18 +225,120 @@ async fn index(controller: ReqData<Arc<RwLock<MiddlewareController>>>) -> HttpRe
 }
 ```
 
+`set_masking` replaces the SDK's masks for the request, to only add to them or opt out of some of them use `masking_mut`, `add_masking` and `remove_masking` instead:
+
+```ignore
+use speakeasy_rust_sdk::{masking::Location, MiddlewareController, StringMaskingOption};
+
+#[post("/payments")]
+async fn payments(controller: ReqData<Arc<RwLock<MiddlewareController>>>) -> HttpResponse {
+    let mut controller = controller.write().unwrap();
+
+    // mask the card number on top of the SDK's masks for this request
+    controller
+        .masking_mut()
+        .with_request_field_mask_string("card_number", StringMaskingOption::KeepLast(4));
+
+    // the SDK masks the `token` query string, but it isn't sensitive here
+    controller.remove_masking(Location::QueryString, "token");
+
+    // rest of the handlers code
+}
+```
+
 The [Masking](crate::masking::Masking) struct can be set with a number of different options to mask sensitive data in the request:
 
 - `masking.with_query_string_mask` - **with_query_string_mask** will mask the specified query strings with an optional mask string.
+- `masking.with_path_param_mask` - **with_path_param_mask** will mask the URL path segments matching the specified path hint parameters (e.g. `email` for `/users/{email}`) with an optional mask string.
 - `masking.with_request_header_mask` - **with_request_header_mask** will mask the specified request headers with an optional mask string.
 - `masking.with_response_header_mask` - **with_response_header_mask** will mask the specified response headers with an optional mask string.
 - `masking.with_request_cookie_mask` - **with_request_cookie_mask** will mask the specified request cookies with an optional mask string.
 - `masking.with_response_cookie_mask` - **with_response_cookie_mask** will mask the specified response cookies with an optional mask string.
 - `masking.with_request_field_mask_string` - **with_request_field_mask_string** will mask the specified request body fields with an optional mask. Supports string fields only. Matches using regex.
-- `masking.with_request_field_mask_number` - **with_request_field_mask_number** will mask the specified request body fields with an optional mask. Supports number fields only. Matches using regex.
+- `masking.with_request_field_mask_number` - **with_request_field_mask_number** will mask the specified request body fields with an optional `i32`, `i64` or `f64` mask. Supports number fields only, including numbers in exponent notation. Matches using regex.
 - `masking.with_response_field_mask_string` - **with_response_field_mask_string** will mask the specified response body fields with an optional mask. Supports string fields only. Matches using regex.
-- `masking.with_response_field_mask_number` - **with_response_field_mask_number** will mask the specified response body fields with an optional mask. Supports number fields only. Matches using regex.
+- `masking.with_response_field_mask_number` - **with_response_field_mask_number** will mask the specified response body fields with an optional `i32`, `i64` or `f64` mask. Supports number fields only, including numbers in exponent notation. Matches using regex.
+- `masking.with_mask_numeric_strings` - **with_mask_numeric_strings** makes the number field masks also mask string values holding a number (e.g. `"amount": "123.45"`), the masked value stays a string.
+- `masking.with_pii_detector` - **with_pii_detector** will scan captured header, cookie, query string and body values with a detector (credit cards, emails, phone numbers, IBANs, SSNs, JWTs, API keys or a custom one) and replace any matches with a typed placeholder. JSON numbers are scanned too, a redacted number is replaced with the placeholder string.
+- `masking.with_*_removal` - **with_query_string_removal**, **with_request_header_removal**, **with_response_header_removal**, **with_request_cookie_removal**, **with_response_cookie_removal**, **with_request_field_removal** and **with_response_field_removal** will drop the matching query strings, headers, cookies or JSON body fields from the captured request/response entirely instead of replacing their values.
+- `masking.with_allowlist` - **with_allowlist** switches masking to default-deny, only the allowed headers, cookies, query strings and body fields are captured as is and everything else is masked or removed.
+- `Masking::recommended()` / `Masking::strict()` - secure by default presets that mask the common credential headers, session cookies and secret, token and key query strings and body fields, use `masking::Preset` to extend them or opt out of individual entries.
+- `masking.with_masker` - **with_masker** adds custom masking logic implementing the `masking::Masker` trait (or a closure), it runs after all of the other masks with access to the path hint, customer id and unmasked request headers.
+- `masking.with_route_masking` - **with_route_masking** sets masks for requests matching a route, an optional method and a path hint pattern (e.g. `/users/:id` or `POST /auth/login`, a trailing `*` segment matches the rest of the path), they are merged with the global masks and win for names masked by both.
+- `masking::MaskingConfig` - masking rules, including route masking, can be loaded from any serde format (TOML, YAML, JSON, ...), `Masking` deserializes from the same document and `MaskingConfig::validate` reports invalid names instead of logging them.
+- `masking.try_with_*` / `masking.validate()` - the `try_with_*` variants of the mask functions return a `masking::Error` for invalid names or masks instead of logging it, `validate` reports every mask that failed to be set or can never match and can be asserted on at startup.
+- `masking.rules()` / `masking.rules_for(location)` / `masking.routes()` - read-only view of the configured masks and removals by location, useful to audit masking configuration in tests.
+- `masking.with_request_type_masks::<T>()` / `masking.with_response_type_masks::<T>()` - masks the body fields of a type, with the `derive` feature `#[derive(SpeakeasyMask)]` generates the masks from `#[speakeasy(mask)]` / `#[speakeasy(mask = "****")]` / `#[speakeasy(mask = 0.5)]` field attributes using the `serde` names, so sensitive fields stay masked when the type changes.
+- `masking.with_report_hook` - masking report of every capture, listing which masks, removals, allowlist entries and PII detectors matched, at which header, cookie, query string or body path (e.g. `$.cards[0].number`) and how many times. The hook gets it for debugging, the `har_masking_report` setting (`sdk.settings().update(|settings| settings.har_masking_report = true)`) adds it to the HAR entry as `_maskingReport` and should only be enabled outside of production.
+
+
+### Updating settings at runtime
+
+The SDK's masking and capture settings can be replaced while the server is running, requests started afterwards use the new settings:
+
+```ignore
+let settings = sdk.settings();
+
+// e.g. from an admin endpoint
+settings.set_masking(Masking::strict());
+settings.update(|settings| settings.max_capture_size = 64 * 1024);
+```
+
+`sdk.masking()` is meant for setting up the masking before the middleware is created, it writes the whole masking back when it's dropped and would overwrite updates made in the meantime.
+
+Clones of the SDK share its settings, create the SDK once and clone it for every worker so updates reach all of them. `with_settings` builds an SDK that uses existing settings:
+
+```ignore
+let settings = SettingsHandle::default();
+let sdk = SpeakeasySdk::try_new(config)
+    .expect("API key is valid")
+    .with_settings(settings.clone());
+```
+
+With the `config_watcher` feature the settings can be reloaded from a config file whenever it changes, invalid files are logged and the current settings are kept. The masks of the file replace the current ones, PII detectors, removals, allowlists, maskers and report hooks can only be set in code and are kept:
+
+```ignore
+use speakeasy_rust_sdk::settings::ConfigWatcher;
+
+// speakeasy.json: { "masking": { "request_string_fields": { "fields": ["password"] } }, "max_capture_size": 65536 }
+let watcher = ConfigWatcher::json(sdk.settings(), "speakeasy.json").expect("valid settings");
+```
+
+### Capture levels by response status
+
+Capture rules choose how much of a response is captured by its status class and route: `full` (the body is captured without masking), `masked` (the default), `headers_only` or `none` (only the status). The first matching rule is used:
+
+```ignore
+use speakeasy_rust_sdk::capture::{CaptureLevel, CaptureRules, StatusClass};
+
+let mut capture_rules = CaptureRules::default();
+// successful logins return tokens, failed ones are kept for debugging
+capture_rules.with_route_level("POST /auth/login", StatusClass::Success, CaptureLevel::HeadersOnly);
+capture_rules.with_route_level("POST /auth/login", StatusClass::ClientError, CaptureLevel::Full);
+
+sdk.settings().update(|settings| settings.capture_rules = capture_rules.clone());
+```
+
+The controller can also set the capture rules of a single request with `controller.set_capture_rules`.
+
+### Decoding protobuf and gRPC bodies
+
+With the `protobuf` feature, `application/x-protobuf` and `application/grpc` bodies can be decoded to JSON with [prost-reflect](https://docs.rs/prost-reflect) message descriptors registered by route. Decoded bodies are masked as JSON with the request and response field masks and get an `application/json` mime type in the HAR, the captured `Content-Type` header is kept. Fields keep their `.proto` names:
+
+```ignore
+use speakeasy_rust_sdk::protobuf::{prost_reflect::DescriptorPool, ProtobufDecoding};
 
+let pool = DescriptorPool::decode(include_bytes!("file_descriptor_set.bin").as_ref()).unwrap();
+
+let mut protobuf = ProtobufDecoding::default();
+// every method of the service on `POST /greeter.Greeter/{method}`
+protobuf.with_service(&pool.get_service_by_name("greeter.Greeter").unwrap());
+protobuf.with_route("POST /v1/users", pool.get_message_by_name("users.CreateUserRequest"), pool.get_message_by_name("users.User"));
+
+sdk.settings().update(|settings| settings.protobuf = protobuf.clone());
+```
+
+Bodies of other routes are captured as they are. Protobuf bodies of registered messages that can't be decoded, such as compressed gRPC messages, are dropped. The protobuf messages can't be loaded from a config file, `load_file` and the `ConfigWatcher` keep the registered ones.
 
 ### Embedded Request Viewer Access Tokens
 
@@ -269,8 +371,13 @@ let token_response = app_state
 
 */
 
+// lets the derive macros refer to the crate by name in the crate's own tests
+#[cfg(test)]
+extern crate self as speakeasy_rust_sdk;
+
 mod generic_http;
 mod har_builder;
+mod multipart;
 mod path_hint;
 mod util;
 
@@ -282,11 +389,18 @@ pub mod speakeasy_protos;
 #[doc(hidden)]
 pub mod transport;
 
+pub mod capture;
 pub mod controller;
 pub mod masking;
 pub mod middleware;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+pub mod settings;
+
+pub use har;
 
 use http::header::InvalidHeaderValue;
+use settings::{MaskingMut, SettingsHandle};
 use thiserror::Error;
 use transport::GrpcClient;
 
@@ -391,20 +505,60 @@ impl SpeakeasySdk {
 
 #[cfg(not(feature = "custom_transport"))]
 impl SpeakeasySdk {
-    pub fn masking(&mut self) -> &mut Masking {
+    /// Changes the SDK's masking before the middleware is created, see [SettingsHandle::update] to change it at runtime
+    pub fn masking(&mut self) -> MaskingMut<'_> {
         match self {
-            SpeakeasySdk::Grpc(inner) => &mut inner.masking,
+            SpeakeasySdk::Grpc(inner) => inner.masking(),
             #[cfg(feature = "mock")]
-            SpeakeasySdk::Mock(inner) => &mut inner.masking,
+            SpeakeasySdk::Mock(inner) => inner.masking(),
+        }
+    }
+
+    /// A handle to update the SDK's masking and capture settings at runtime, including after the middleware is created
+    pub fn settings(&self) -> SettingsHandle {
+        match self {
+            SpeakeasySdk::Grpc(inner) => inner.settings(),
+            #[cfg(feature = "mock")]
+            SpeakeasySdk::Mock(inner) => inner.settings(),
+        }
+    }
+
+    /// Uses existing settings instead of the SDK's own, e.g. to share them between SDK instances.
+    /// Clones of the SDK share its settings already
+    pub fn with_settings(self, settings: SettingsHandle) -> Self {
+        match self {
+            SpeakeasySdk::Grpc(inner) => SpeakeasySdk::Grpc(inner.with_settings(settings)),
+            #[cfg(feature = "mock")]
+            SpeakeasySdk::Mock(inner) => {
+                SpeakeasySdk::Mock(Box::new(inner.with_settings(settings)))
+            }
         }
     }
 }
 
 #[cfg(feature = "custom_transport")]
 impl<T> SpeakeasySdk<T> {
-    pub fn masking(&mut self) -> &mut Masking {
+    /// Changes the SDK's masking before the middleware is created, see [SettingsHandle::update] to change it at runtime
+    pub fn masking(&mut self) -> MaskingMut<'_> {
+        match self {
+            SpeakeasySdk::CustomTransport(inner) => inner.masking(),
+        }
+    }
+
+    /// A handle to update the SDK's masking and capture settings at runtime, including after the middleware is created
+    pub fn settings(&self) -> SettingsHandle {
+        match self {
+            SpeakeasySdk::CustomTransport(inner) => inner.settings(),
+        }
+    }
+
+    /// Uses existing settings instead of the SDK's own, e.g. to share them between SDK instances.
+    /// Clones of the SDK share its settings already
+    pub fn with_settings(self, settings: SettingsHandle) -> Self {
         match self {
-            SpeakeasySdk::CustomTransport(inner) => &mut inner.masking,
+            SpeakeasySdk::CustomTransport(inner) => {
+                SpeakeasySdk::CustomTransport(inner.with_settings(settings))
+            }
         }
     }
 }
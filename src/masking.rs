@@ -1,9 +1,24 @@
 //! Contains logic for masking and setting masking options
+mod authorization;
+mod config;
 mod fields;
+mod json;
+mod names;
 mod option;
+pub(crate) mod route;
 
+pub(crate) mod allowlist;
 pub(crate) mod body_mask;
 pub(crate) mod generic_mask;
+pub(crate) mod masker;
+pub(crate) mod pii;
+pub(crate) mod preset;
+pub(crate) mod removal;
+pub(crate) mod report;
+pub(crate) mod rules;
+pub(crate) mod stream;
+pub(crate) mod typed;
+pub(crate) mod xml;
 
 /// A mask option for string fields, default is `__masked__`
 pub type StringMaskingOption = option::StringMaskingOption;
@@ -11,37 +26,143 @@ pub type StringMaskingOption = option::StringMaskingOption;
 /// A mask option for number fields, default is `-12321`
 pub type NumberMaskingOption = option::NumberMaskingOption;
 
+/// A replacement value for number fields, an integer or a float
+pub type NumberMask = option::NumberMask;
+
+/// A deterministic keyed hash mask, masked values can still be correlated across requests
+pub type KeyedHash = option::KeyedHash;
+
+/// A structured mask for `Authorization` headers that keeps the scheme and identity of the caller
+pub type AuthorizationMask = authorization::AuthorizationMask;
+
 pub(crate) type Fields = fields::Fields;
 
-use std::collections::HashMap;
+pub use self::allowlist::{Allowlist, AllowlistAction};
+pub use self::config::{MaskingConfig, NumberMaskConfig, RouteMaskingConfig, StringMaskConfig};
+pub use self::masker::{Masker, MaskingContext};
+pub use self::pii::{Detector, PiiKind, RegexDetector};
+pub use self::preset::Preset;
+pub use self::report::{MaskingReport, MatchedRule, ReportEntry};
+pub use self::rules::{Location, MaskingRule, RuleKind};
+pub use self::typed::SpeakeasyMask;
+#[doc(hidden)]
+pub use self::typed::{nested_number_field_masks, nested_string_field_masks};
+#[cfg(feature = "derive")]
+pub use speakeasy_rust_sdk_derive::SpeakeasyMask;
+
+use std::{collections::HashMap, sync::Arc};
 
 use crate::speakeasy_protos::ingest::ingest_request::MaskingMetadata;
 use log::error;
+use serde::Deserialize;
+use thiserror::Error;
 
 use self::{
     body_mask::{BodyMask, RequestMask, ResponseMask},
     generic_mask::{
-        GenericMask, QueryStringMask, RequestCookieMask, RequestHeaderMask, ResponseCookieMask,
-        ResponseHeaderMask,
+        GenericMask, PathParamMask, QueryStringMask, RequestCookieMask, RequestHeaderMask,
+        ResponseCookieMask, ResponseHeaderMask,
     },
+    masker::Maskers,
+    pii::PiiDetection,
+    removal::{BodyRemoval, GenericRemoval},
+    report::ReportHooks,
+    route::RoutePattern,
 };
 
 pub(crate) const DEFAULT_STRING_MASK: &str = "__masked__";
 pub(crate) const DEFAULT_NUMBER_MASK: i32 = -12321;
+pub(crate) const REMOVED_MASK: &str = "__removed__";
+
+/// Errors for invalid masking configuration
+#[derive(Debug, Clone, Error)]
+pub enum Error {
+    #[error("invalid {location} name: {name:?}")]
+    InvalidName {
+        location: &'static str,
+        name: String,
+    },
+    #[error("invalid route: {0:?}, expected an optional method and a path starting with `/`")]
+    InvalidRoute(String),
+    #[error("duplicate route masking for {0:?}")]
+    DuplicateRoute(String),
+    #[error("route masking can't have routes of its own")]
+    NestedRoute,
+    #[error("in route masking for {route:?}: {error}")]
+    Route { route: String, error: Box<Error> },
+    #[error(transparent)]
+    BodyMask(#[from] body_mask::Error),
+    #[error("invalid masking config: {}", display_errors(.0))]
+    Invalid(Vec<Error>),
+}
+
+fn display_errors(errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
 
 /// All masking options, see functions for more details on setting them
-#[derive(Debug, Clone, Default)]
+///
+/// Can also be deserialized from a [MaskingConfig] document
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "MaskingConfig")]
 pub struct Masking {
     pub(crate) query_string_mask: GenericMask<QueryStringMask>,
+    pub(crate) path_param_mask: GenericMask<PathParamMask>,
     pub(crate) request_header_mask: GenericMask<RequestHeaderMask>,
     pub(crate) response_header_mask: GenericMask<ResponseHeaderMask>,
     pub(crate) request_cookie_mask: GenericMask<RequestCookieMask>,
     pub(crate) response_cookie_mask: GenericMask<ResponseCookieMask>,
     pub(crate) request_masks: BodyMask<RequestMask>,
     pub(crate) response_masks: BodyMask<ResponseMask>,
+    pub(crate) pii_detection: PiiDetection,
+    pub(crate) query_string_removal: GenericRemoval<QueryStringMask>,
+    pub(crate) request_header_removal: GenericRemoval<RequestHeaderMask>,
+    pub(crate) response_header_removal: GenericRemoval<ResponseHeaderMask>,
+    pub(crate) request_cookie_removal: GenericRemoval<RequestCookieMask>,
+    pub(crate) response_cookie_removal: GenericRemoval<ResponseCookieMask>,
+    pub(crate) request_field_removal: BodyRemoval<RequestMask>,
+    pub(crate) response_field_removal: BodyRemoval<ResponseMask>,
+    pub(crate) allowlist: Option<Allowlist>,
+    pub(crate) maskers: Maskers,
+    pub(crate) routes: Vec<(RoutePattern, Masking)>,
+    pub(crate) report_hooks: ReportHooks,
+    // masks that failed to be set, reported by validate
+    pub(crate) errors: Vec<Error>,
 }
 
 impl Masking {
+    /// Secure by default masking, masks the common credential headers, session cookies
+    /// and secret, token and key query strings and body fields.
+    /// Setting a mask afterwards replaces the preset masks of the same kind,
+    /// use [Preset::recommended] to extend it or opt out of individual entries
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::{Config, Masking, SpeakeasySdk};
+    ///
+    /// let config = Config {
+    ///     api_key: "YOUR API KEY HERE".to_string(),
+    ///     api_id: "YOUR API ID HERE".to_string(),
+    ///     version_id: "YOUR VERSION ID HERE".to_string(),
+    /// };
+    ///
+    /// let mut sdk = SpeakeasySdk::try_new(config).expect("valid API key");
+    /// *sdk.masking() = Masking::recommended();
+    /// ```
+    pub fn recommended() -> Self {
+        Preset::recommended().into()
+    }
+
+    /// Everything in [recommended](Masking::recommended) with more headers and fields
+    /// and all of the built in PII detectors, see [Preset::strict]
+    pub fn strict() -> Self {
+        Preset::strict().into()
+    }
+
     /// Will mask the specified query strings with an optional mask string.
     /// If no mask is provided, the value will be masked with the default mask.
     /// If a single mask is provided, it will be used for all query strings.
@@ -98,6 +219,26 @@ impl Masking {
         self.query_string_mask = GenericMask::new(fields.into(), masking_option.into());
     }
 
+    /// with_path_param_mask will mask the URL path segments matching the specified path hint parameters with an optional mask string.
+    /// Parameters are named after the path hint of the request (e.g. `email` for `/users/{email}`),
+    /// the masked path is used for the request url and the capture comment.
+    /// Masks are applied the same way as [with_query_string_mask](Masking::with_query_string_mask)
+    /// (defaults to `"__masked__"`).
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::{Masking, masking::StringMaskingOption};
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_path_param_mask(vec!["email", "iban"], StringMaskingOption::KeepLast(4));
+    /// ```
+    pub fn with_path_param_mask(
+        &mut self,
+        fields: impl Into<Fields>,
+        masking_option: impl Into<StringMaskingOption>,
+    ) {
+        self.path_param_mask = GenericMask::new(fields.into(), masking_option.into());
+    }
+
     /// with_request_header_mask will mask the specified request headers with an optional mask string.
     /// If no mask is provided, the value will be masked with the default mask.
     /// If a single mask is provided, it will be used for all headers.
@@ -152,7 +293,10 @@ impl Masking {
         fields: impl Into<Fields>,
         masking_option: impl Into<StringMaskingOption>,
     ) {
-        self.request_header_mask = GenericMask::new(fields.into(), masking_option.into());
+        self.request_header_mask = GenericMask::new(
+            lowercase(fields.into()),
+            lowercase_masks(masking_option.into()),
+        );
     }
 
     /// with_response_cookie_mask will mask the specified response cookies with an optional mask string.
@@ -266,7 +410,10 @@ impl Masking {
         fields: impl Into<Fields>,
         masking_option: impl Into<StringMaskingOption>,
     ) {
-        self.response_header_mask = GenericMask::new(fields.into(), masking_option.into());
+        self.response_header_mask = GenericMask::new(
+            lowercase(fields.into()),
+            lowercase_masks(masking_option.into()),
+        );
     }
 
     /// with_request_cookie_mask will mask the specified request cookies with an optional mask string.
@@ -391,6 +538,7 @@ impl Masking {
                 "[SpeakeasySDK Internal error] - invalid request field mask string: {}",
                 err
             );
+            self.errors.push(err.into());
         }
     }
 
@@ -458,6 +606,7 @@ impl Masking {
                 "[SpeakeasySDK Internal error] - invalid request field mask string: {}",
                 err
             );
+            self.errors.push(err.into());
         }
     }
 
@@ -525,6 +674,7 @@ impl Masking {
                 "[SpeakeasySDK Internal error] - invalid response field mask string: {}",
                 err
             );
+            self.errors.push(err.into());
         }
     }
 
@@ -592,39 +742,1146 @@ impl Masking {
                 "[SpeakeasySDK Internal error] - invalid response field mask string: {}",
                 err
             );
+            self.errors.push(err.into());
+        }
+    }
+
+    /// with_mask_numeric_strings makes the request and response number field masks also mask
+    /// string values holding a number, for APIs that send numbers as strings (e.g. `"amount": "123.45"`).
+    /// The masked value stays a string
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::Masking;
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_request_field_mask_number("amount", 0.0);
+    /// masking.with_mask_numeric_strings(true);
+    /// ```
+    pub fn with_mask_numeric_strings(&mut self, enabled: bool) {
+        self.request_masks.set_mask_numeric_strings(enabled);
+        self.response_masks.set_mask_numeric_strings(enabled);
+    }
+
+    /// Will scan captured header, cookie, query string and body values with the given detector
+    /// and replace anything it finds with a typed placeholder (e.g. `__masked_email__`).
+    /// Detectors are opt-in, each one has to be enabled individually and they run in the order they were added.
+    /// Custom detectors can be added by implementing the [Detector] trait or using a [RegexDetector].
+    ///
+    /// For JSON bodies only string values are scanned, object keys and numbers are left untouched.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::{Masking, masking::{PiiKind, RegexDetector}};
+    ///
+    /// // Enable individual built-in detectors
+    /// let mut masking = Masking::default();
+    /// masking.with_pii_detector(PiiKind::CreditCard);
+    /// masking.with_pii_detector(PiiKind::Email);
+    ///
+    /// // Enable all built-in detectors
+    /// let mut masking = Masking::default();
+    /// for kind in PiiKind::ALL {
+    ///     masking.with_pii_detector(kind);
+    /// }
+    ///
+    /// // Add a custom detector
+    /// let mut masking = Masking::default();
+    /// masking.with_pii_detector(
+    ///     RegexDetector::new("employee_id", r"\bEMP-\d{6}\b", "__masked_employee_id__").unwrap(),
+    /// );
+    /// ```
+    pub fn with_pii_detector(&mut self, detector: impl Detector + 'static) {
+        self.pii_detection.add(Arc::new(detector));
+    }
+
+    /// Will drop the specified query strings from the captured request,
+    /// they are removed from both the HAR query string list and the request URL.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::Masking;
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_query_string_removal(vec!["signature", "session_blob"]);
+    /// ```
+    pub fn with_query_string_removal(&mut self, fields: impl Into<Fields>) {
+        self.query_string_removal = GenericRemoval::new(fields.into());
+    }
+
+    /// Will drop the specified request headers from the captured request, header names are matched case insensitively.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::Masking;
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_request_header_removal("x-client-certificate");
+    /// ```
+    pub fn with_request_header_removal(&mut self, fields: impl Into<Fields>) {
+        self.request_header_removal = GenericRemoval::new(lowercase(fields.into()));
+    }
+
+    /// Will drop the specified response headers from the captured response, header names are matched case insensitively.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::Masking;
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_response_header_removal("x-debug-trace");
+    /// ```
+    pub fn with_response_header_removal(&mut self, fields: impl Into<Fields>) {
+        self.response_header_removal = GenericRemoval::new(lowercase(fields.into()));
+    }
+
+    /// Will drop the specified request cookies from the captured request.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::Masking;
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_request_cookie_removal("session_state");
+    /// ```
+    pub fn with_request_cookie_removal(&mut self, fields: impl Into<Fields>) {
+        self.request_cookie_removal = GenericRemoval::new(fields.into());
+    }
+
+    /// Will drop the specified response cookies from the captured response.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::Masking;
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_response_cookie_removal("session_state");
+    /// ```
+    pub fn with_response_cookie_removal(&mut self, fields: impl Into<Fields>) {
+        self.response_cookie_removal = GenericRemoval::new(fields.into());
+    }
+
+    /// Will remove the specified fields, including their values, from captured JSON request bodies.
+    /// Fields are matched at any depth and nested objects or arrays are removed as a whole,
+    /// which makes this useful for large base64 blobs or embedded documents.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::Masking;
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_request_field_removal(vec!["attachment", "embedded_document"]);
+    /// ```
+    pub fn with_request_field_removal(&mut self, fields: impl Into<Fields>) {
+        self.request_field_removal = BodyRemoval::new(fields.into());
+    }
+
+    /// Will remove the specified fields, including their values, from captured JSON response bodies.
+    /// Fields are matched at any depth and nested objects or arrays are removed as a whole.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::Masking;
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_response_field_removal("thumbnail");
+    /// ```
+    pub fn with_response_field_removal(&mut self, fields: impl Into<Fields>) {
+        self.response_field_removal = BodyRemoval::new(fields.into());
+    }
+
+    /// with_allowlist switches masking to default-deny, only the values on the allowlist are captured as is.
+    /// Everything else is masked or removed, see [Allowlist] for more details
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::{Masking, masking::{Allowlist, AllowlistAction}};
+    ///
+    /// let mut allowlist = Allowlist::new(AllowlistAction::Remove);
+    /// allowlist.with_request_headers(vec!["content-type", "accept"]);
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_allowlist(allowlist);
+    /// ```
+    pub fn with_allowlist(&mut self, allowlist: Allowlist) {
+        self.allowlist = Some(allowlist);
+    }
+
+    /// with_masker adds custom masking logic that runs after all of the other masks,
+    /// maskers run in the order they were added, see [Masker] for more details
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::{Masking, har::v1_2::{Request, Response}, masking::{Masker, MaskingContext}};
+    ///
+    /// struct InternalOnly;
+    ///
+    /// impl Masker for InternalOnly {
+    ///     fn mask(&self, context: &MaskingContext, request: &mut Request, _response: &mut Response) {
+    ///         // only keep the request body of requests made from inside the VPC
+    ///         let internal = context
+    ///             .request_headers
+    ///             .get("x-forwarded-for")
+    ///             .and_then(|v| v.to_str().ok())
+    ///             .map_or(false, |ip| ip.starts_with("10."));
+    ///
+    ///         if !internal {
+    ///             if let Some(text) = request.post_data.as_mut().and_then(|p| p.text.as_mut()) {
+    ///                 *text = "__masked__".to_string();
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_masker(InternalOnly);
+    /// ```
+    pub fn with_masker(&mut self, masker: impl Masker + 'static) {
+        self.maskers.add(masker);
+    }
+
+    /// with_report_hook calls the hook with a [MaskingReport] of every capture,
+    /// listing which masks, removals and PII detectors matched, where and how many times.
+    /// Use it to debug a masking configuration, the report is built with a second pass over the unmasked capture.
+    /// See [Settings::har_masking_report](crate::settings::Settings::har_masking_report) to add it to the HAR
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::Masking;
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_request_field_mask_string("password", None);
+    /// masking.with_report_hook(|context, report| {
+    ///     for entry in report.entries() {
+    ///         println!(
+    ///             "{} {:?} {} {:?} x{}",
+    ///             context.method, entry.location, entry.path, entry.rule, entry.count
+    ///         );
+    ///     }
+    /// });
+    /// ```
+    pub fn with_report_hook(
+        &mut self,
+        hook: impl Fn(&MaskingContext, &MaskingReport) + Send + Sync + 'static,
+    ) {
+        self.report_hooks.add(hook);
+    }
+
+    /// with_route_masking sets masks that are only used for requests matching the route,
+    /// they are merged with the other masks, the route masks win for names masked by both.
+    ///
+    /// Routes are an optional method and a path matched against the path hint of the request, e.g. `POST /auth/*`.
+    /// Path params can be written as `:id` or `{id}`, a `*` segment matches any single segment
+    /// and a trailing `*` segment matches the rest of the path.
+    /// When multiple routes match a request they are merged in the order they were added,
+    /// setting the same route again replaces its masks.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::{Masking, masking::StringMaskingOption};
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_request_field_mask_string("password", None);
+    ///
+    /// let mut auth_masking = Masking::default();
+    /// auth_masking.with_request_field_mask_string(vec!["otp", "recovery_code"], None);
+    /// auth_masking.with_response_field_mask_string("email", StringMaskingOption::KeepEmailDomain);
+    ///
+    /// // `password`, `otp` and `recovery_code` are masked for all POST requests under /auth
+    /// masking.with_route_masking("POST /auth/*", auth_masking);
+    /// ```
+    pub fn with_route_masking(&mut self, route: &str, masking: Masking) {
+        if let Err(err) = self.try_with_route_masking(route, masking) {
+            error!("{}", err);
+            self.errors.push(err);
+        }
+    }
+
+    /// without stops masking and removing the given names at the location, including in route masking.
+    /// Useful to opt a single request out of some of the SDK's masks with
+    /// [Controller::remove_masking](crate::controller::Controller::remove_masking),
+    /// header names are case insensitive
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::{Masking, masking::Location};
+    ///
+    /// let mut masking = Masking::recommended();
+    ///
+    /// // the token query string is public for this API
+    /// masking.without(Location::QueryString, "token");
+    /// ```
+    pub fn without(&mut self, location: Location, fields: impl Into<Fields>) {
+        let fields = match location {
+            Location::RequestHeader | Location::ResponseHeader => lowercase(fields.into()),
+            _ => fields.into(),
+        };
+        let names: Vec<&str> = names_of(&fields).collect();
+
+        match location {
+            Location::QueryString => {
+                self.query_string_mask.remove(names.iter().copied());
+                self.query_string_removal.keep(names.iter().copied());
+            }
+            Location::PathParam => self.path_param_mask.remove(names.iter().copied()),
+            Location::RequestHeader => {
+                self.request_header_mask.remove(names.iter().copied());
+                self.request_header_removal.keep(names.iter().copied());
+            }
+            Location::ResponseHeader => {
+                self.response_header_mask.remove(names.iter().copied());
+                self.response_header_removal.keep(names.iter().copied());
+            }
+            Location::RequestCookie => {
+                self.request_cookie_mask.remove(names.iter().copied());
+                self.request_cookie_removal.keep(names.iter().copied());
+            }
+            Location::ResponseCookie => {
+                self.response_cookie_mask.remove(names.iter().copied());
+                self.response_cookie_removal.keep(names.iter().copied());
+            }
+            Location::RequestField => {
+                self.request_masks.remove(&names);
+                self.request_field_removal.keep(names.iter().copied());
+            }
+            Location::ResponseField => {
+                self.response_masks.remove(&names);
+                self.response_field_removal.keep(names.iter().copied());
+            }
+        }
+
+        for (_, masking) in &mut self.routes {
+            masking.without(location, fields.clone());
+        }
+    }
+
+    /// with_request_type_masks adds the request body field masks of a type, see [SpeakeasyMask].
+    /// They are merged with the request field masks already set and win for fields masked by both,
+    /// use [with_route_masking](Masking::with_route_masking) for types that are only used by some routes
+    ///
+    /// # Examples
+    /// ```rust,ignore
+    /// use speakeasy_rust_sdk::{Masking, masking::SpeakeasyMask};
+    ///
+    /// #[derive(serde::Deserialize, SpeakeasyMask)]
+    /// struct Login {
+    ///     username: String,
+    ///     #[speakeasy(mask)]
+    ///     password: String,
+    /// }
+    ///
+    /// let mut login_masking = Masking::default();
+    /// login_masking.with_request_type_masks::<Login>();
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_route_masking("POST /login", login_masking);
+    /// ```
+    pub fn with_request_type_masks<T: SpeakeasyMask + ?Sized>(&mut self) {
+        let mut masking = Masking::default();
+
+        let (fields, masking_option) = typed::string_field_masks::<T>();
+        masking.with_request_field_mask_string(fields, masking_option);
+
+        let (fields, masking_option) = typed::number_field_masks::<T>();
+        masking.with_request_field_mask_number(fields, masking_option);
+
+        self.merge(masking);
+    }
+
+    /// with_response_type_masks adds the response body field masks of a type, see [SpeakeasyMask]
+    /// and [with_request_type_masks](Masking::with_request_type_masks)
+    pub fn with_response_type_masks<T: SpeakeasyMask + ?Sized>(&mut self) {
+        let mut masking = Masking::default();
+
+        let (fields, masking_option) = typed::string_field_masks::<T>();
+        masking.with_response_field_mask_string(fields, masking_option);
+
+        let (fields, masking_option) = typed::number_field_masks::<T>();
+        masking.with_response_field_mask_number(fields, masking_option);
+
+        self.merge(masking);
+    }
+}
+
+// read-only view of the configured masks
+impl Masking {
+    /// Lists the configured masks and removals sorted by location and name,
+    /// the masks of routes are listed by [routes](Masking::routes)
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::{Masking, masking::{Location, RuleKind}};
+    ///
+    /// let masking = Masking::recommended();
+    ///
+    /// assert!(masking
+    ///     .rules()
+    ///     .iter()
+    ///     .any(|rule| rule.location == Location::RequestHeader && rule.name == "authorization"));
+    ///
+    /// for rule in masking.rules_for(Location::ResponseField) {
+    ///     assert_ne!(rule.kind, RuleKind::Removal);
+    /// }
+    /// ```
+    pub fn rules(&self) -> Vec<MaskingRule> {
+        let mut rules = Vec::new();
+
+        let generic_masks = [
+            (Location::QueryString, self.query_string_mask.masks()),
+            (Location::PathParam, self.path_param_mask.masks()),
+            (Location::RequestHeader, self.request_header_mask.masks()),
+            (Location::ResponseHeader, self.response_header_mask.masks()),
+            (Location::RequestCookie, self.request_cookie_mask.masks()),
+            (Location::ResponseCookie, self.response_cookie_mask.masks()),
+        ];
+
+        for (location, masks) in generic_masks {
+            for (name, mask) in masks {
+                rules.push(MaskingRule::new(location, name, RuleKind::String, &mask));
+            }
+        }
+
+        let body_masks = [
+            (
+                Location::RequestField,
+                &self.request_masks.string_field_masks(),
+                RuleKind::String,
+            ),
+            (
+                Location::RequestField,
+                &self.request_masks.number_field_masks(),
+                RuleKind::Number,
+            ),
+            (
+                Location::ResponseField,
+                &self.response_masks.string_field_masks(),
+                RuleKind::String,
+            ),
+            (
+                Location::ResponseField,
+                &self.response_masks.number_field_masks(),
+                RuleKind::Number,
+            ),
+        ];
+
+        for (location, masks, kind) in body_masks {
+            for (name, mask) in masks {
+                rules.push(MaskingRule::new(location, name, kind, mask));
+            }
+        }
+
+        let removals = [
+            (Location::QueryString, self.query_string_removal.fields()),
+            (
+                Location::RequestHeader,
+                self.request_header_removal.fields(),
+            ),
+            (
+                Location::ResponseHeader,
+                self.response_header_removal.fields(),
+            ),
+            (
+                Location::RequestCookie,
+                self.request_cookie_removal.fields(),
+            ),
+            (
+                Location::ResponseCookie,
+                self.response_cookie_removal.fields(),
+            ),
+            (Location::RequestField, self.request_field_removal.fields()),
+            (
+                Location::ResponseField,
+                self.response_field_removal.fields(),
+            ),
+        ];
+
+        for (location, names) in removals {
+            for name in names {
+                rules.push(MaskingRule::new(
+                    location,
+                    name,
+                    RuleKind::Removal,
+                    REMOVED_MASK,
+                ));
+            }
+        }
+
+        rules.sort_by(|a, b| (a.location, &a.name).cmp(&(b.location, &b.name)));
+        rules
+    }
+
+    /// Lists the configured masks and removals for a single location, sorted by name
+    pub fn rules_for(&self, location: Location) -> Vec<MaskingRule> {
+        self.rules()
+            .into_iter()
+            .filter(|rule| rule.location == location)
+            .collect()
+    }
+
+    /// Lists the routes with route masking and their masking, in the order they were added
+    pub fn routes(&self) -> Vec<(&str, &Masking)> {
+        self.routes
+            .iter()
+            .map(|(route, masking)| (route.as_str(), masking))
+            .collect()
+    }
+}
+
+// fallible masking functions, these return an error instead of logging it and continuing without the mask
+impl Masking {
+    /// Same as [with_query_string_mask](Masking::with_query_string_mask) but errors on empty query string names
+    pub fn try_with_query_string_mask(
+        &mut self,
+        fields: impl Into<Fields>,
+        masking_option: impl Into<StringMaskingOption>,
+    ) -> Result<&mut Masking, Error> {
+        let fields = fields.into();
+        names::check(
+            "query string",
+            names_of(&fields),
+            names::is_valid_query_string,
+        )?;
+
+        self.with_query_string_mask(fields, masking_option);
+        Ok(self)
+    }
+
+    /// Same as [with_path_param_mask](Masking::with_path_param_mask) but errors on invalid parameter names
+    pub fn try_with_path_param_mask(
+        &mut self,
+        fields: impl Into<Fields>,
+        masking_option: impl Into<StringMaskingOption>,
+    ) -> Result<&mut Masking, Error> {
+        let fields = fields.into();
+        names::check("path param", names_of(&fields), names::is_valid_path_param)?;
+
+        self.with_path_param_mask(fields, masking_option);
+        Ok(self)
+    }
+
+    /// Same as [with_request_header_mask](Masking::with_request_header_mask) but errors on invalid header names,
+    /// header names are case insensitive
+    pub fn try_with_request_header_mask(
+        &mut self,
+        fields: impl Into<Fields>,
+        masking_option: impl Into<StringMaskingOption>,
+    ) -> Result<&mut Masking, Error> {
+        let fields = fields.into();
+        names::check("request header", names_of(&fields), names::is_valid_header)?;
+
+        self.with_request_header_mask(fields, masking_option);
+        Ok(self)
+    }
+
+    /// Same as [with_response_header_mask](Masking::with_response_header_mask) but errors on invalid header names,
+    /// header names are case insensitive
+    pub fn try_with_response_header_mask(
+        &mut self,
+        fields: impl Into<Fields>,
+        masking_option: impl Into<StringMaskingOption>,
+    ) -> Result<&mut Masking, Error> {
+        let fields = fields.into();
+        names::check("response header", names_of(&fields), names::is_valid_header)?;
+
+        self.with_response_header_mask(fields, masking_option);
+        Ok(self)
+    }
+
+    /// Same as [with_request_cookie_mask](Masking::with_request_cookie_mask) but errors on invalid cookie names
+    pub fn try_with_request_cookie_mask(
+        &mut self,
+        fields: impl Into<Fields>,
+        masking_option: impl Into<StringMaskingOption>,
+    ) -> Result<&mut Masking, Error> {
+        let fields = fields.into();
+        names::check("request cookie", names_of(&fields), names::is_valid_cookie)?;
+
+        self.with_request_cookie_mask(fields, masking_option);
+        Ok(self)
+    }
+
+    /// Same as [with_response_cookie_mask](Masking::with_response_cookie_mask) but errors on invalid cookie names
+    pub fn try_with_response_cookie_mask(
+        &mut self,
+        fields: impl Into<Fields>,
+        masking_option: impl Into<StringMaskingOption>,
+    ) -> Result<&mut Masking, Error> {
+        let fields = fields.into();
+        names::check("response cookie", names_of(&fields), names::is_valid_cookie)?;
+
+        self.with_response_cookie_mask(fields, masking_option);
+        Ok(self)
+    }
+
+    /// Same as [with_request_field_mask_string](Masking::with_request_field_mask_string)
+    /// but errors on invalid field names or if the mask can't be built, the previous mask is kept on error
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::{Masking, masking::{Error, StringMaskingOption}};
+    ///
+    /// fn masking() -> Result<Masking, Error> {
+    ///     let mut masking = Masking::default();
+    ///
+    ///     masking
+    ///         .try_with_request_header_mask("Authorization", None)?
+    ///         .try_with_request_field_mask_string(vec!["password", "secret"], None)?
+    ///         .try_with_response_field_mask_string("email", StringMaskingOption::KeepEmailDomain)?;
+    ///
+    ///     Ok(masking)
+    /// }
+    ///
+    /// assert!(masking().is_ok());
+    /// ```
+    pub fn try_with_request_field_mask_string(
+        &mut self,
+        fields: impl Into<Fields>,
+        masking_option: impl Into<StringMaskingOption>,
+    ) -> Result<&mut Masking, Error> {
+        let fields = fields.into();
+        names::check("request field", names_of(&fields), names::is_valid_field)?;
+
+        self.request_masks
+            .set_string_field_masks(fields, masking_option.into())?;
+        Ok(self)
+    }
+
+    /// Same as [with_request_field_mask_number](Masking::with_request_field_mask_number)
+    /// but errors on invalid field names or if the mask can't be built, the previous mask is kept on error
+    pub fn try_with_request_field_mask_number(
+        &mut self,
+        fields: impl Into<Fields>,
+        masking_option: impl Into<NumberMaskingOption>,
+    ) -> Result<&mut Masking, Error> {
+        let fields = fields.into();
+        names::check("request field", names_of(&fields), names::is_valid_field)?;
+
+        self.request_masks
+            .set_number_field_masks(fields, masking_option.into())?;
+        Ok(self)
+    }
+
+    /// Same as [with_response_field_mask_string](Masking::with_response_field_mask_string)
+    /// but errors on invalid field names or if the mask can't be built, the previous mask is kept on error
+    pub fn try_with_response_field_mask_string(
+        &mut self,
+        fields: impl Into<Fields>,
+        masking_option: impl Into<StringMaskingOption>,
+    ) -> Result<&mut Masking, Error> {
+        let fields = fields.into();
+        names::check("response field", names_of(&fields), names::is_valid_field)?;
+
+        self.response_masks
+            .set_string_field_masks(fields, masking_option.into())?;
+        Ok(self)
+    }
+
+    /// Same as [with_response_field_mask_number](Masking::with_response_field_mask_number)
+    /// but errors on invalid field names or if the mask can't be built, the previous mask is kept on error
+    pub fn try_with_response_field_mask_number(
+        &mut self,
+        fields: impl Into<Fields>,
+        masking_option: impl Into<NumberMaskingOption>,
+    ) -> Result<&mut Masking, Error> {
+        let fields = fields.into();
+        names::check("response field", names_of(&fields), names::is_valid_field)?;
+
+        self.response_masks
+            .set_number_field_masks(fields, masking_option.into())?;
+        Ok(self)
+    }
+
+    /// Same as [with_route_masking](Masking::with_route_masking) but errors on invalid routes
+    /// and if the route masking has routes of its own
+    pub fn try_with_route_masking(
+        &mut self,
+        route: &str,
+        masking: Masking,
+    ) -> Result<&mut Masking, Error> {
+        let route =
+            RoutePattern::parse(route).ok_or_else(|| Error::InvalidRoute(route.to_string()))?;
+
+        if !masking.routes.is_empty() {
+            return Err(Error::Route {
+                route: route.as_str().to_string(),
+                error: Box::new(Error::NestedRoute),
+            });
+        }
+
+        match self
+            .routes
+            .iter_mut()
+            .find(|(existing, _)| existing.is_same(&route))
+        {
+            Some((_, existing)) => *existing = masking,
+            None => self.routes.push((route, masking)),
+        }
+
+        Ok(self)
+    }
+
+    /// Checks that every mask set with the `with_*` functions was built and that every masked or removed name can match.
+    /// Every problem found is reported in [Error::Invalid],
+    /// useful to assert on at startup since the `with_*` functions only log errors
+    ///
+    /// # Examples
+    /// ```rust
+    /// use speakeasy_rust_sdk::Masking;
+    ///
+    /// let mut masking = Masking::default();
+    /// masking.with_request_header_mask("X Api Key", None);
+    ///
+    /// // header names can't contain spaces, so this would never be masked
+    /// assert!(masking.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut errors = self.errors.clone();
+
+        let generic_masks = [
+            (
+                "query string",
+                self.query_string_mask.fields(),
+                self.query_string_removal.fields(),
+                names::is_valid_query_string as fn(&str) -> bool,
+            ),
+            (
+                "path param",
+                self.path_param_mask.fields(),
+                Vec::new(),
+                names::is_valid_path_param,
+            ),
+            (
+                "request header",
+                self.request_header_mask.fields(),
+                self.request_header_removal.fields(),
+                names::is_valid_header,
+            ),
+            (
+                "response header",
+                self.response_header_mask.fields(),
+                self.response_header_removal.fields(),
+                names::is_valid_header,
+            ),
+            (
+                "request cookie",
+                self.request_cookie_mask.fields(),
+                self.request_cookie_removal.fields(),
+                names::is_valid_cookie,
+            ),
+            (
+                "response cookie",
+                self.response_cookie_mask.fields(),
+                self.response_cookie_removal.fields(),
+                names::is_valid_cookie,
+            ),
+            (
+                "request field",
+                self.request_masks.string_fields(),
+                self.request_masks.number_fields(),
+                names::is_valid_field,
+            ),
+            (
+                "request field",
+                self.request_field_removal.fields(),
+                Vec::new(),
+                names::is_valid_field,
+            ),
+            (
+                "response field",
+                self.response_masks.string_fields(),
+                self.response_masks.number_fields(),
+                names::is_valid_field,
+            ),
+            (
+                "response field",
+                self.response_field_removal.fields(),
+                Vec::new(),
+                names::is_valid_field,
+            ),
+        ];
+
+        for (location, masked, removed, is_valid) in generic_masks {
+            names::validate(
+                &mut errors,
+                location,
+                masked.into_iter().chain(removed),
+                is_valid,
+            );
+        }
+
+        for (route, masking) in self.routes() {
+            if let Err(Error::Invalid(route_errors)) = masking.validate() {
+                errors.extend(route_errors.into_iter().map(|error| Error::Route {
+                    route: route.to_string(),
+                    error: Box::new(error),
+                }));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Invalid(errors))
         }
     }
 }
 
+fn names_of(fields: &Fields) -> impl Iterator<Item = &str> {
+    fields.iter().map(String::as_str)
+}
+
+fn lowercase(fields: Fields) -> Fields {
+    Vec::from(fields)
+        .into_iter()
+        .map(|field| field.to_lowercase())
+        .collect::<Vec<String>>()
+        .into()
+}
+
+// lowercases the names of associated masks, to match the lowercased header names
+fn lowercase_masks(masking_option: StringMaskingOption) -> StringMaskingOption {
+    match masking_option {
+        StringMaskingOption::AssociatedMasks(masks) => StringMaskingOption::AssociatedMasks(
+            masks
+                .into_iter()
+                .map(|(field, mask)| (field.to_lowercase(), mask))
+                .collect(),
+        ),
+        masking_option => masking_option,
+    }
+}
+
 // private masking functions
 #[doc(hidden)]
 impl Masking {
+    /// The masking used for a request, with the masks of the matching routes merged in
+    pub(crate) fn for_route(&self, method: &str, path_hint: &str) -> Masking {
+        let mut masking = Masking {
+            routes: Vec::new(),
+            ..self.clone()
+        };
+
+        for (route, route_masking) in &self.routes {
+            if route.matches(method, path_hint) {
+                masking.merge(route_masking.clone());
+            }
+        }
+
+        masking
+    }
+
+    /// Adds the parts of `current` that a [MaskingConfig] can't hold to this masking, of its routes too:
+    /// PII detectors, removals, the allowlist, maskers and report hooks. Used when the masking is loaded from a file
+    pub(crate) fn keep_code_registered(&mut self, current: &Masking) {
+        let mut code_registered = current.code_registered();
+
+        for (route, masking) in std::mem::take(&mut code_registered.routes) {
+            match self
+                .routes
+                .iter_mut()
+                .find(|(existing, _)| existing.is_same(&route))
+            {
+                Some((_, existing)) => existing.merge(masking),
+                None => self.routes.push((route, masking)),
+            }
+        }
+
+        self.merge(code_registered);
+    }
+
+    fn code_registered(&self) -> Masking {
+        Masking {
+            pii_detection: self.pii_detection.clone(),
+            query_string_removal: self.query_string_removal.clone(),
+            request_header_removal: self.request_header_removal.clone(),
+            response_header_removal: self.response_header_removal.clone(),
+            request_cookie_removal: self.request_cookie_removal.clone(),
+            response_cookie_removal: self.response_cookie_removal.clone(),
+            request_field_removal: self.request_field_removal.clone(),
+            response_field_removal: self.response_field_removal.clone(),
+            allowlist: self.allowlist.clone(),
+            maskers: self.maskers.clone(),
+            report_hooks: self.report_hooks.clone(),
+            routes: self
+                .routes
+                .iter()
+                .map(|(route, masking)| (route.clone(), masking.code_registered()))
+                .filter(|(_, masking)| !masking.is_empty())
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds the masks, removals, detectors and maskers of `other`,
+    /// names masked by both use the mask of `other`
+    pub(crate) fn merge(&mut self, other: Masking) {
+        self.query_string_mask.merge(other.query_string_mask);
+        self.path_param_mask.merge(other.path_param_mask);
+        self.request_header_mask.merge(other.request_header_mask);
+        self.response_header_mask.merge(other.response_header_mask);
+        self.request_cookie_mask.merge(other.request_cookie_mask);
+        self.response_cookie_mask.merge(other.response_cookie_mask);
+        self.request_masks.merge(other.request_masks);
+        self.response_masks.merge(other.response_masks);
+        self.pii_detection.extend(other.pii_detection);
+        self.query_string_removal.merge(other.query_string_removal);
+        self.request_header_removal
+            .merge(other.request_header_removal);
+        self.response_header_removal
+            .merge(other.response_header_removal);
+        self.request_cookie_removal
+            .merge(other.request_cookie_removal);
+        self.response_cookie_removal
+            .merge(other.response_cookie_removal);
+        self.request_field_removal
+            .merge(other.request_field_removal);
+        self.response_field_removal
+            .merge(other.response_field_removal);
+        if other.allowlist.is_some() {
+            self.allowlist = other.allowlist;
+        }
+        self.maskers.extend(other.maskers);
+        self.report_hooks.extend(other.report_hooks);
+        self.errors.extend(other.errors);
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
         self.query_string_mask.is_empty()
+            && self.path_param_mask.is_empty()
             && self.request_header_mask.is_empty()
             && self.response_header_mask.is_empty()
             && self.request_cookie_mask.is_empty()
             && self.response_cookie_mask.is_empty()
             && self.request_masks.is_empty()
             && self.response_masks.is_empty()
+            && self.query_string_removal.is_empty()
+            && self.request_header_removal.is_empty()
+            && self.response_header_removal.is_empty()
+            && self.request_cookie_removal.is_empty()
+            && self.response_cookie_removal.is_empty()
+            && self.request_field_removal.is_empty()
+            && self.response_field_removal.is_empty()
+            && self.allowlist.is_none()
+            && self.maskers.is_empty()
+            && self.pii_detection.is_empty()
+            && self.report_hooks.is_empty()
     }
 }
 
 impl From<Masking> for MaskingMetadata {
     fn from(masking: Masking) -> Self {
-        let (request_field_masks_string, request_field_masks_number) =
+        let (mut request_field_masks_string, request_field_masks_number) =
             masking.request_masks.into_metadata();
+        let (mut response_field_masks_string, response_field_masks_number) =
+            masking.response_masks.into_metadata();
+
+        // removed fields are reported with the `__removed__` mask
+        request_field_masks_string.extend(HashMap::from(masking.request_field_removal));
+        response_field_masks_string.extend(HashMap::from(masking.response_field_removal));
 
         MaskingMetadata {
-            request_header_masks: masking.request_header_mask.into(),
-            request_cookie_masks: masking.request_cookie_mask.into(),
+            request_header_masks: with_removal(
+                masking.request_header_mask,
+                masking.request_header_removal,
+            ),
+            request_cookie_masks: with_removal(
+                masking.request_cookie_mask,
+                masking.request_cookie_removal,
+            ),
             request_field_masks_string,
             request_field_masks_number,
-            response_header_masks: masking.response_header_mask.into(),
-            response_cookie_masks: masking.response_cookie_mask.into(),
-            response_field_masks_string: HashMap::new(),
-            response_field_masks_number: HashMap::new(),
-            query_string_masks: masking.query_string_mask.into(),
+            response_header_masks: with_removal(
+                masking.response_header_mask,
+                masking.response_header_removal,
+            ),
+            response_cookie_masks: with_removal(
+                masking.response_cookie_mask,
+                masking.response_cookie_removal,
+            ),
+            response_field_masks_string,
+            response_field_masks_number,
+            query_string_masks: with_removal(
+                masking.query_string_mask,
+                masking.query_string_removal,
+            ),
         }
     }
 }
+
+fn with_removal<T>(mask: GenericMask<T>, removal: GenericRemoval<T>) -> HashMap<String, String> {
+    let mut masks: HashMap<String, String> = mask.into();
+    masks.extend(HashMap::from(removal));
+    masks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn try_with_errors_on_invalid_names() {
+        let mut masking = Masking::default();
+
+        let err = masking
+            .try_with_request_header_mask("X-Api-Key", None)
+            .and_then(|masking| masking.try_with_request_field_mask_string("pass\"word", None))
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            r#"invalid request field name: "pass\"word""#
+        );
+        assert!(masking.request_header_mask.is_masked("x-api-key"));
+        assert!(masking.request_masks.string_fields().is_empty());
+    }
+
+    #[test]
+    fn pii_detectors_and_report_hooks_are_not_empty() {
+        let mut pii_masking = Masking::default();
+        pii_masking.with_pii_detector(PiiKind::Email);
+
+        let mut report_masking = Masking::default();
+        report_masking.with_report_hook(|_, _| {});
+
+        assert!(Masking::default().is_empty());
+        assert!(!pii_masking.is_empty());
+        assert!(!report_masking.is_empty());
+    }
+
+    #[test]
+    fn lowercases_header_removals() {
+        let mut masking = Masking::default();
+        masking.with_request_header_removal("X-Debug");
+        masking.with_response_header_removal(vec!["X-Trace-Id", "server"]);
+
+        assert!(masking.request_header_removal.is_removed("x-debug"));
+        assert!(masking.response_header_removal.is_removed("x-trace-id"));
+        assert!(masking.response_header_removal.is_removed("server"));
+    }
+
+    #[test]
+    fn lowercases_header_masks() {
+        let mut masking = Masking::default();
+        masking
+            .try_with_request_header_mask("X-Api-Key", HashMap::from([("X-Api-Key", "__key__")]))
+            .unwrap();
+
+        // the infallible setter normalizes the names the same way
+        let mut unchecked_masking = Masking::default();
+        unchecked_masking
+            .with_request_header_mask("X-Api-Key", HashMap::from([("X-Api-Key", "__key__")]));
+
+        for masking in [masking, unchecked_masking] {
+            assert_eq!(
+                masking.request_header_mask.mask("x-api-key", "secret"),
+                "__key__"
+            );
+            assert!(masking.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn validates_masks() {
+        let mut route_masking = Masking::default();
+        route_masking.with_request_cookie_removal("session id");
+
+        let mut masking = Masking::default();
+        masking.with_request_header_mask(vec!["authorization", "X Api Key"], None);
+        masking.with_response_field_mask_number("", None);
+        masking.with_route_masking("/users/:id", route_masking);
+
+        let errors: Vec<String> = match masking.validate() {
+            Err(Error::Invalid(errors)) => errors.iter().map(ToString::to_string).collect(),
+            result => panic!("unexpected result: {:?}", result),
+        };
+
+        assert_eq!(
+            errors,
+            vec![
+                r#"invalid request header name: "x api key""#,
+                r#"invalid response field name: """#,
+                r#"in route masking for "/users/:id": invalid request cookie name: "session id""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_rules_and_reports_response_masks() {
+        let mut masking = Masking::default();
+        masking.with_request_header_mask("authorization", StringMaskingOption::KeepLast(4));
+        masking.with_response_field_mask_string("email", None);
+        masking.with_response_field_mask_number("balance", 0);
+        masking.with_response_field_removal("avatar");
+
+        assert_eq!(
+            masking.rules(),
+            vec![
+                MaskingRule::new(
+                    Location::RequestHeader,
+                    "authorization",
+                    RuleKind::String,
+                    "__keep_last_4__"
+                ),
+                MaskingRule::new(
+                    Location::ResponseField,
+                    "avatar",
+                    RuleKind::Removal,
+                    "__removed__"
+                ),
+                MaskingRule::new(Location::ResponseField, "balance", RuleKind::Number, "0"),
+                MaskingRule::new(
+                    Location::ResponseField,
+                    "email",
+                    RuleKind::String,
+                    "__masked__"
+                ),
+            ]
+        );
+
+        let metadata = MaskingMetadata::from(masking);
+
+        assert_eq!(
+            metadata.response_field_masks_string,
+            HashMap::from([
+                ("email".to_string(), "__masked__".to_string()),
+                ("avatar".to_string(), "__removed__".to_string())
+            ])
+        );
+        assert_eq!(
+            metadata.response_field_masks_number,
+            HashMap::from([("balance".to_string(), "0".to_string())])
+        );
+    }
+
+    #[test]
+    fn merges_and_removes_masks() {
+        let mut masking = Masking::default();
+        masking.with_request_field_mask_string(vec!["password", "email", "otp"], None);
+        masking.with_request_header_removal("x-api-key");
+
+        let mut request_masking = Masking::default();
+        request_masking
+            .with_request_field_mask_string("email", StringMaskingOption::KeepEmailDomain);
+        request_masking.with_request_field_mask_number("pin", None);
+
+        masking.without(Location::RequestField, "otp");
+        masking.without(Location::RequestHeader, "X-API-KEY");
+        masking.merge(request_masking);
+
+        let metadata = MaskingMetadata::from(masking);
+
+        assert_eq!(
+            metadata.request_field_masks_string,
+            HashMap::from([
+                ("password".to_string(), "__masked__".to_string()),
+                ("email".to_string(), "__keep_email_domain__".to_string())
+            ])
+        );
+        assert_eq!(
+            metadata.request_field_masks_number,
+            HashMap::from([("pin".to_string(), "-12321".to_string())])
+        );
+        assert!(metadata.request_header_masks.is_empty());
+    }
+}
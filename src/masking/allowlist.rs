@@ -0,0 +1,421 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::speakeasy_protos::ingest::ingest_request::MaskingMetadata;
+
+use super::{
+    json::{self, Action, ValueKind},
+    pii::PiiDetection,
+    report::{MaskingReport, MatchedRule},
+    rules::Location,
+    Fields, DEFAULT_NUMBER_MASK, DEFAULT_STRING_MASK, REMOVED_MASK,
+};
+
+/// What happens to the values that are not on the [Allowlist]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AllowlistAction {
+    /// Replace the value with the default mask (`"__masked__"` or `-12321`)
+    #[default]
+    Mask,
+    /// Drop the header, cookie, query string or body field from the capture
+    Remove,
+}
+
+/// Default-deny masking, only the approved headers, cookies, query strings and JSON body fields are captured as is,
+/// everything else is masked or removed depending on the [AllowlistAction].
+///
+/// JSON objects and arrays are always kept so that allowed fields nested under fields that are not allowed are still captured,
+/// string and number fields have to be allowed to be captured, booleans and nulls are always kept.
+/// Request and response bodies that are not JSON, form-urlencoded or multipart are masked completely.
+///
+/// The masks and removals set on [Masking](crate::Masking) still apply to the allowed values.
+///
+/// # Examples
+/// ```rust
+/// use speakeasy_rust_sdk::{Masking, masking::{Allowlist, AllowlistAction}};
+///
+/// let mut allowlist = Allowlist::new(AllowlistAction::Mask);
+/// allowlist.with_request_headers(vec!["content-type", "accept", "user-agent"]);
+/// allowlist.with_response_headers("content-type");
+/// allowlist.with_request_fields(vec!["id", "name"]);
+/// allowlist.with_response_fields(vec!["id", "name", "created_at"]);
+///
+/// let mut masking = Masking::default();
+/// masking.with_allowlist(allowlist);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Allowlist {
+    action: AllowlistAction,
+    query_strings: HashSet<String>,
+    request_headers: HashSet<String>,
+    response_headers: HashSet<String>,
+    request_cookies: HashSet<String>,
+    response_cookies: HashSet<String>,
+    request_fields: HashSet<String>,
+    response_fields: HashSet<String>,
+}
+
+impl Allowlist {
+    /// Creates an empty allowlist, nothing is allowed until it's added
+    pub fn new(action: AllowlistAction) -> Self {
+        Self {
+            action,
+            ..Default::default()
+        }
+    }
+
+    /// Allows the specified query strings
+    pub fn with_query_strings(&mut self, fields: impl Into<Fields>) {
+        self.query_strings = to_set(fields, false);
+    }
+
+    /// Allows the specified request headers, header names are case insensitive
+    pub fn with_request_headers(&mut self, fields: impl Into<Fields>) {
+        self.request_headers = to_set(fields, true);
+    }
+
+    /// Allows the specified response headers, header names are case insensitive
+    pub fn with_response_headers(&mut self, fields: impl Into<Fields>) {
+        self.response_headers = to_set(fields, true);
+    }
+
+    /// Allows the specified request cookies
+    pub fn with_request_cookies(&mut self, fields: impl Into<Fields>) {
+        self.request_cookies = to_set(fields, false);
+    }
+
+    /// Allows the specified response cookies
+    pub fn with_response_cookies(&mut self, fields: impl Into<Fields>) {
+        self.response_cookies = to_set(fields, false);
+    }
+
+    /// Allows the specified request body fields, at any depth
+    pub fn with_request_fields(&mut self, fields: impl Into<Fields>) {
+        self.request_fields = to_set(fields, false);
+    }
+
+    /// Allows the specified response body fields, at any depth
+    pub fn with_response_fields(&mut self, fields: impl Into<Fields>) {
+        self.response_fields = to_set(fields, false);
+    }
+}
+
+fn to_set(fields: impl Into<Fields>, lowercase: bool) -> HashSet<String> {
+    Vec::from(fields.into())
+        .into_iter()
+        .map(|field| {
+            if lowercase {
+                field.to_lowercase()
+            } else {
+                field
+            }
+        })
+        .collect()
+}
+
+/// What the allowlist does with a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decision {
+    Allow,
+    Mask,
+    Remove,
+}
+
+impl Allowlist {
+    pub(crate) fn decide(&self, location: Location, name: &str) -> Decision {
+        let allowed = match location {
+            Location::QueryString => self.query_strings.contains(name),
+            // path params are part of the URL path, which is always captured
+            Location::PathParam => true,
+            Location::RequestHeader => self.request_headers.contains(&name.to_lowercase()),
+            Location::ResponseHeader => self.response_headers.contains(&name.to_lowercase()),
+            Location::RequestCookie => self.request_cookies.contains(name),
+            Location::ResponseCookie => self.response_cookies.contains(name),
+            Location::RequestField => self.request_fields.contains(name),
+            Location::ResponseField => self.response_fields.contains(name),
+        };
+
+        match (allowed, self.action) {
+            (true, _) => Decision::Allow,
+            (false, AllowlistAction::Mask) => Decision::Mask,
+            (false, AllowlistAction::Remove) => Decision::Remove,
+        }
+    }
+
+    /// Masks or removes the string and number fields of a JSON body that are not allowed,
+    /// strings and numbers that aren't in an object (e.g. a root `"token"`) have no name to allow and are always masked.
+    /// Returns `None` if the body is not valid JSON
+    pub(crate) fn mask_json(
+        &self,
+        body: &str,
+        location: Location,
+        masked: &mut MaskedFields,
+    ) -> Option<String> {
+        json::rewrite(body, |key, _, kind| {
+            let key = match (key, kind) {
+                (Some(key), ValueKind::String | ValueKind::Number) => key,
+                (None, ValueKind::Number) => {
+                    return Action::Replace(DEFAULT_NUMBER_MASK.to_string())
+                }
+                (None, ValueKind::String) => {
+                    return Action::Replace(format!("\"{}\"", DEFAULT_STRING_MASK))
+                }
+                _ => return Action::Keep,
+            };
+
+            match self.decide(location, key) {
+                Decision::Allow => Action::Keep,
+                Decision::Remove => {
+                    masked.record_field(location, key, kind, REMOVED_MASK);
+                    Action::Remove
+                }
+                Decision::Mask if kind == ValueKind::Number => {
+                    masked.record_field(location, key, kind, &DEFAULT_NUMBER_MASK.to_string());
+                    Action::Replace(DEFAULT_NUMBER_MASK.to_string())
+                }
+                Decision::Mask => {
+                    masked.record_field(location, key, kind, DEFAULT_STRING_MASK);
+                    Action::Replace(format!("\"{}\"", DEFAULT_STRING_MASK))
+                }
+            }
+        })
+    }
+}
+
+/// Collects the values masked while building a HAR whose masks aren't known up front, e.g. by the [Allowlist],
+/// and the [MaskingReport] of the capture when it is reported
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MaskedFields {
+    metadata: MaskingMetadata,
+    report: Option<MaskingReport>,
+}
+
+impl MaskedFields {
+    /// Also reports the rules that matched while masking
+    pub(crate) fn with_report() -> Self {
+        Self {
+            report: Some(MaskingReport::default()),
+            ..Default::default()
+        }
+    }
+
+    /// Reports a rule that matched a value, if the capture is reported
+    pub(crate) fn report(&mut self, location: Location, path: &str, rule: MatchedRule) {
+        if let Some(report) = &mut self.report {
+            report.record(location, path, rule, 1);
+        }
+    }
+
+    /// Redacts the PII in a value, reporting the detectors that matched
+    pub(crate) fn redact(
+        &mut self,
+        pii_detection: &PiiDetection,
+        location: Location,
+        path: &str,
+        value: &str,
+    ) -> String {
+        if let Some(report) = &mut self.report {
+            report.pii(pii_detection, location, path, value);
+        }
+
+        pii_detection.redact(value).into_owned()
+    }
+
+    /// Runs masking that is already reported without reporting it again
+    pub(crate) fn unreported<R>(&mut self, mask: impl FnOnce(&mut Self) -> R) -> R {
+        let report = self.report.take();
+        let masked = mask(self);
+        self.report = report;
+        masked
+    }
+
+    pub(crate) fn report_mut(&mut self) -> Option<&mut MaskingReport> {
+        self.report.as_mut()
+    }
+
+    pub(crate) fn take_report(&mut self) -> Option<MaskingReport> {
+        self.report.take()
+    }
+
+    /// Records a masked header, cookie, query string or string body field
+    pub(crate) fn record(&mut self, location: Location, name: &str, mask: &str) {
+        self.record_field(location, name, ValueKind::String, mask)
+    }
+
+    pub(crate) fn record_field(
+        &mut self,
+        location: Location,
+        name: &str,
+        kind: ValueKind,
+        mask: &str,
+    ) {
+        let metadata = &mut self.metadata;
+
+        let masks = match (location, kind) {
+            // path params have no masking metadata
+            (Location::PathParam, _) => return,
+            (Location::QueryString, _) => &mut metadata.query_string_masks,
+            (Location::RequestHeader, _) => &mut metadata.request_header_masks,
+            (Location::ResponseHeader, _) => &mut metadata.response_header_masks,
+            (Location::RequestCookie, _) => &mut metadata.request_cookie_masks,
+            (Location::ResponseCookie, _) => &mut metadata.response_cookie_masks,
+            (Location::RequestField, ValueKind::Number) => &mut metadata.request_field_masks_number,
+            (Location::RequestField, _) => &mut metadata.request_field_masks_string,
+            (Location::ResponseField, ValueKind::Number) => {
+                &mut metadata.response_field_masks_number
+            }
+            (Location::ResponseField, _) => &mut metadata.response_field_masks_string,
+        };
+
+        // the first mask recorded for a field wins, explicit masks are recorded by the masking metadata already
+        masks
+            .entry(name.to_string())
+            .or_insert_with(|| mask.to_string());
+    }
+
+    /// Adds the recorded masks to the masking metadata, masks already in the metadata are kept
+    pub(crate) fn extend_metadata(self, metadata: &mut MaskingMetadata) {
+        let masked = self.metadata;
+
+        merge(&mut metadata.query_string_masks, masked.query_string_masks);
+        merge(
+            &mut metadata.request_header_masks,
+            masked.request_header_masks,
+        );
+        merge(
+            &mut metadata.response_header_masks,
+            masked.response_header_masks,
+        );
+        merge(
+            &mut metadata.request_cookie_masks,
+            masked.request_cookie_masks,
+        );
+        merge(
+            &mut metadata.response_cookie_masks,
+            masked.response_cookie_masks,
+        );
+        merge(
+            &mut metadata.request_field_masks_string,
+            masked.request_field_masks_string,
+        );
+        merge(
+            &mut metadata.request_field_masks_number,
+            masked.request_field_masks_number,
+        );
+        merge(
+            &mut metadata.response_field_masks_string,
+            masked.response_field_masks_string,
+        );
+        merge(
+            &mut metadata.response_field_masks_number,
+            masked.response_field_masks_number,
+        );
+    }
+}
+
+fn merge(masks: &mut HashMap<String, String>, recorded: HashMap<String, String>) {
+    for (name, mask) in recorded {
+        masks.entry(name).or_insert(mask);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        action: AllowlistAction,
+        expected_body: &'static str,
+        expected_string_masks: HashMap<String, String>,
+        expected_number_masks: HashMap<String, String>,
+    }
+
+    #[test]
+    fn masks_json_fields_that_are_not_allowed() {
+        let body = r#"{"id": 1, "name": "jane", "email": "jane@example.com", "card": {"id": 2, "number": 4111111111111111, "active": true}}"#;
+
+        let tests = vec![
+            Test {
+                name: "masks fields",
+                action: AllowlistAction::Mask,
+                expected_body: r#"{"id": 1, "name": "jane", "email": "__masked__", "card": {"id": 2, "number": -12321, "active": true}}"#,
+                expected_string_masks: hashmap! {"email".to_string() => "__masked__".to_string()},
+                expected_number_masks: hashmap! {"number".to_string() => "-12321".to_string()},
+            },
+            Test {
+                name: "removes fields",
+                action: AllowlistAction::Remove,
+                expected_body: r#"{"id": 1, "name": "jane", "card": {"id": 2, "active": true}}"#,
+                expected_string_masks: hashmap! {"email".to_string() => "__removed__".to_string()},
+                expected_number_masks: hashmap! {"number".to_string() => "__removed__".to_string()},
+            },
+        ];
+
+        for test in tests {
+            let mut allowlist = Allowlist::new(test.action);
+            allowlist.with_request_fields(vec!["id", "name"]);
+
+            let mut masked = MaskedFields::default();
+            let masked_body = allowlist.mask_json(body, Location::RequestField, &mut masked);
+
+            let mut metadata = MaskingMetadata::default();
+            masked.extend_metadata(&mut metadata);
+
+            assert_eq!(masked_body.as_deref(), Some(test.expected_body));
+            assert_eq!(
+                metadata.request_field_masks_string,
+                test.expected_string_masks
+            );
+            assert_eq!(
+                metadata.request_field_masks_number,
+                test.expected_number_masks
+            );
+        }
+    }
+
+    #[test]
+    fn masks_values_without_a_name() {
+        struct Test {
+            #[allow(dead_code)]
+            name: &'static str,
+            body: &'static str,
+            expected_body: &'static str,
+        }
+
+        let tests = vec![
+            Test {
+                name: "root string",
+                body: r#""secret-token""#,
+                expected_body: r#""__masked__""#,
+            },
+            Test {
+                name: "root number",
+                body: "12345",
+                expected_body: "-12321",
+            },
+            Test {
+                name: "root array",
+                body: r#"["a", 1, true, {"id": "b"}]"#,
+                expected_body: r#"["__masked__", -12321, true, {"id": "b"}]"#,
+            },
+        ];
+
+        for test in tests {
+            for action in [AllowlistAction::Mask, AllowlistAction::Remove] {
+                let mut allowlist = Allowlist::new(action);
+                allowlist.with_response_fields(vec!["id"]);
+
+                let masked_body = allowlist.mask_json(
+                    test.body,
+                    Location::ResponseField,
+                    &mut Default::default(),
+                );
+
+                assert_eq!(masked_body.as_deref(), Some(test.expected_body));
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine as _,
+};
+
+use super::DEFAULT_STRING_MASK;
+
+const DEFAULT_ALLOWED_CLAIMS: [&str; 6] = ["sub", "iss", "aud", "exp", "iat", "nbf"];
+
+/// Structured masking for `Authorization` header values that keeps the scheme and the identity of the caller
+///
+/// - `Basic` credentials keep the username and have the password masked (`jane:__masked__`, re-encoded as base64)
+/// - `Bearer` JWTs keep the header and the allowed claims, the other claims are masked and the signature is stripped
+/// - any other scheme, or a bearer token that is not a JWT, keeps the scheme and has the credentials masked
+///
+/// # Examples
+/// ```rust
+/// use speakeasy_rust_sdk::{Masking, masking::AuthorizationMask};
+///
+/// let mut masking = Masking::default();
+/// masking.with_request_header_mask(
+///     "authorization",
+///     AuthorizationMask::default().with_allowed_claims(["sub", "iss", "exp", "scope"]),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct AuthorizationMask {
+    allowed_claims: HashSet<String>,
+}
+
+impl Default for AuthorizationMask {
+    /// Allows the registered `sub`, `iss`, `aud`, `exp`, `iat` and `nbf` claims
+    fn default() -> Self {
+        Self::new(DEFAULT_ALLOWED_CLAIMS)
+    }
+}
+
+impl AuthorizationMask {
+    /// Creates an AuthorizationMask that only keeps the given JWT claims
+    pub fn new(allowed_claims: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            allowed_claims: allowed_claims.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Replaces the JWT claims that are kept
+    pub fn with_allowed_claims(
+        mut self,
+        allowed_claims: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.allowed_claims = allowed_claims.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub(crate) fn mask(&self, value: &str) -> String {
+        let (scheme, credentials) = match value.trim().split_once(' ') {
+            Some((scheme, credentials)) => (scheme, credentials.trim()),
+            None => return DEFAULT_STRING_MASK.to_string(),
+        };
+
+        let masked_credentials = if scheme.eq_ignore_ascii_case("basic") {
+            mask_basic(credentials)
+        } else if scheme.eq_ignore_ascii_case("bearer") {
+            self.mask_jwt(credentials)
+        } else {
+            None
+        };
+
+        format!(
+            "{} {}",
+            scheme,
+            masked_credentials.unwrap_or_else(|| DEFAULT_STRING_MASK.to_string())
+        )
+    }
+
+    fn mask_jwt(&self, token: &str) -> Option<String> {
+        let mut segments = token.split('.');
+        let (header, payload) = (segments.next()?, segments.next()?);
+
+        // the header has to be a JSON object too, otherwise this isn't a JWT
+        serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(
+            &URL_SAFE_NO_PAD.decode(header).ok()?,
+        )
+        .ok()?;
+
+        let mut claims = serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(
+            &URL_SAFE_NO_PAD.decode(payload).ok()?,
+        )
+        .ok()?;
+
+        for (claim, value) in claims.iter_mut() {
+            if !self.allowed_claims.contains(claim) {
+                *value = serde_json::Value::String(DEFAULT_STRING_MASK.to_string());
+            }
+        }
+
+        let payload = serde_json::to_vec(&claims).ok()?;
+
+        // signature is stripped, leaving an unsecured JWT
+        Some(format!("{}.{}.", header, URL_SAFE_NO_PAD.encode(payload)))
+    }
+}
+
+fn mask_basic(credentials: &str) -> Option<String> {
+    let decoded = String::from_utf8(STANDARD.decode(credentials).ok()?).ok()?;
+    let (username, _password) = decoded.split_once(':')?;
+
+    Some(STANDARD.encode(format!("{}:{}", username, DEFAULT_STRING_MASK)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        value: String,
+        expected: String,
+    }
+
+    fn jwt(claims: serde_json::Value) -> String {
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        )
+    }
+
+    #[test]
+    fn run() {
+        let tests = vec![
+            Test {
+                name: "keeps basic username",
+                value: format!("Basic {}", STANDARD.encode("jane:hunter2")),
+                expected: format!("Basic {}", STANDARD.encode("jane:__masked__")),
+            },
+            Test {
+                name: "keeps allowed jwt claims and strips signature",
+                value: format!(
+                    "Bearer {}.c2lnbmF0dXJl",
+                    jwt(
+                        serde_json::json!({"sub": "123", "email": "jane@example.com", "exp": 1700000000})
+                    )
+                ),
+                expected: format!(
+                    "Bearer {}.",
+                    jwt(
+                        serde_json::json!({"email": "__masked__", "exp": 1700000000, "sub": "123"})
+                    )
+                ),
+            },
+            Test {
+                name: "masks opaque bearer tokens",
+                value: "Bearer sk_live_abcdef".to_string(),
+                expected: "Bearer __masked__".to_string(),
+            },
+            Test {
+                name: "masks other schemes",
+                value: "Digest username=\"jane\"".to_string(),
+                expected: "Digest __masked__".to_string(),
+            },
+            Test {
+                name: "masks values without a scheme",
+                value: "hunter2".to_string(),
+                expected: "__masked__".to_string(),
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(
+                AuthorizationMask::default().mask(&test.value),
+                test.expected
+            );
+        }
+    }
+}
@@ -9,7 +9,7 @@ use crate::util;
 use super::{fields::BodyMaskFieldsSearchMap, Fields, NumberMaskingOption, StringMaskingOption};
 
 /// Errors for creating BodyMasks
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum Error {
     #[error("invalid string field name: {0}")]
     StringField(String),
@@ -23,12 +23,15 @@ pub(crate) struct RequestMask;
 #[derive(Debug, Clone, Default)]
 pub(crate) struct ResponseMask;
 
-/// BodyMasks holds information needed to perform masking on a request or response body
+/// BodyMasks holds information needed to perform masking on a request or response body,
+/// masks are kept in layers and a field is only ever in one layer
 #[derive(Debug, Clone, Default)]
 pub(crate) struct BodyMask<T> {
     phantom: PhantomData<T>,
-    string_masks: Option<BodyMaskInner<StringMaskingOption>>,
-    number_masks: Option<BodyMaskInner<NumberMaskingOption>>,
+    string_masks: Vec<BodyMaskInner<StringMaskingOption>>,
+    number_masks: Vec<BodyMaskInner<NumberMaskingOption>>,
+    // number masks also apply to string values holding a number, e.g. `"123.45"`
+    mask_numeric_strings: bool,
 }
 
 /// BodyMaskInner holds the regex, fields and options for masking
@@ -46,7 +49,7 @@ impl From<BodyMaskInner<StringMaskingOption>> for HashMap<String, String> {
             .map(|(_field, (field_without_quotes, index))| {
                 let value = mask
                     .mask_option
-                    .get_mask_replacement(&field_without_quotes, index)
+                    .get_metadata_replacement(&field_without_quotes, index)
                     .to_string();
 
                 (field_without_quotes, value)
@@ -62,7 +65,7 @@ impl From<BodyMaskInner<NumberMaskingOption>> for HashMap<String, String> {
             .map(|(_field, (field_without_quotes, index))| {
                 let value = mask
                     .mask_option
-                    .get_mask_replacement(&field_without_quotes, index)
+                    .get_metadata_replacement(&field_without_quotes, index)
                     .to_string();
 
                 (field_without_quotes, value)
@@ -80,12 +83,248 @@ impl<T> BodyMaskInner<T> {
             mask_option,
         }
     }
+
+    /// Stops masking the given fields, the regex is rebuilt from the remaining fields.
+    /// Returns `None` if no fields are left
+    fn without(
+        mut self,
+        fields: &[&str],
+        build_regex: fn(&[&str]) -> Option<Regex>,
+    ) -> Option<Self> {
+        if !self.fields.names().any(|field| fields.contains(&field)) {
+            return Some(self);
+        }
+
+        self.fields.retain(|field| !fields.contains(&field));
+
+        if self.fields.is_empty() {
+            return None;
+        }
+
+        let remaining: Vec<&str> = self.fields.names().collect();
+        // a subset of fields that built a regex will always build one
+        self.regex = build_regex(&remaining)?;
+
+        Some(self)
+    }
+}
+
+/// Builds a single regex matching any of the string fields
+fn string_fields_regex(fields: &[&str]) -> Option<Regex> {
+    let mut string_mask_regex = String::with_capacity((fields.len() * 32) + (fields.len() * 24));
+
+    // build up single regex from string field regexes
+    for field_name in fields {
+        let _ = write!(
+            string_mask_regex,
+            r##"(?:("{}"): *)(".*?[^\\]")(?: *[, \n\r}}]?)|"##,
+            regex::escape(field_name)
+        );
+    }
+
+    // drop the last "|"
+    string_mask_regex.pop();
+
+    Regex::new(&string_mask_regex).ok()
+}
+
+/// Builds a single regex matching any of the number fields,
+/// the value can be in exponent notation and is also matched when quoted so numeric strings can be masked
+fn number_fields_regex(fields: &[&str]) -> Option<Regex> {
+    let mut mask_regex = String::with_capacity((fields.len() * 32) + (fields.len() * 48));
+
+    // build up single regex from number field regexes
+    for field_name in fields {
+        let _ = write!(
+            mask_regex,
+            r##"(?:("{}"): *)("?-?[0-9]+\.?[0-9]*(?:[eE][+-]?[0-9]+)?"?)( *[, \n\r}}]?)|"##,
+            regex::escape(field_name)
+        );
+    }
+
+    // drop the last "|"
+    mask_regex.pop();
+
+    Regex::new(&mask_regex).ok()
+}
+
+impl BodyMaskInner<StringMaskingOption> {
+    fn mask<'a>(&self, body: &'a str) -> Cow<'a, str> {
+        self.regex.replace_all(body, |caps: &Captures| {
+            if let Some((field, value)) = util::get_first_capture_and_next(caps) {
+                let (field_without_quotes, index) = self.fields.get(field).unwrap_or_default();
+
+                // unescape the string value so value dependent masks see the real value
+                let value = value.unwrap_or_default();
+                let value = serde_json::from_str::<String>(value)
+                    .map(Cow::Owned)
+                    .unwrap_or(Cow::Borrowed(&value[1..value.len() - 1]));
+
+                let replacement_mask =
+                    self.mask_option
+                        .get_mask_replacement(&field_without_quotes, index, &value);
+
+                // re-escape the replacement, partial masks can keep quotes or backslashes
+                let replacement_mask = serde_json::to_string(&replacement_mask)
+                    .expect("string will serialize to json");
+
+                format!(
+                    r#"{}:{}{}"#,
+                    field,
+                    replacement_mask,
+                    caps[0].chars().last().unwrap()
+                )
+            } else {
+                caps[0].to_string()
+            }
+        })
+    }
+}
+
+impl BodyMaskInner<NumberMaskingOption> {
+    fn mask<'a>(&self, body: &'a str, mask_numeric_strings: bool) -> Cow<'a, str> {
+        self.regex.replace_all(body, |caps: &Captures| {
+            if let Some((field, value)) = util::get_first_capture_and_next(caps) {
+                let (field_without_quotes, index) = self.fields.get(field).unwrap_or_default();
+                let value = value.unwrap_or_default();
+
+                let numeric_string = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'));
+
+                let replacement_mask = match numeric_string {
+                    // numeric strings stay strings
+                    Some(value) if mask_numeric_strings => format!(
+                        r#""{}""#,
+                        self.mask_option
+                            .get_mask_replacement(&field_without_quotes, index, value)
+                    ),
+                    // a string that isn't masked or only starts with a number
+                    _ if value.starts_with('"') || value.ends_with('"') => {
+                        return caps[0].to_string()
+                    }
+                    _ => self
+                        .mask_option
+                        .get_mask_replacement(&field_without_quotes, index, value),
+                };
+
+                format!(
+                    r#"{}:{}{}"#,
+                    field,
+                    replacement_mask,
+                    caps[0].chars().last().unwrap()
+                )
+            } else {
+                caps[0].to_string()
+            }
+        })
+    }
 }
 
 impl<T: Default> BodyMask<T> {
     /// Checks if the masking is empty (not initialized)
     pub(crate) fn is_empty(&self) -> bool {
-        self.string_masks.is_none() && self.number_masks.is_none()
+        self.string_masks.is_empty() && self.number_masks.is_empty()
+    }
+
+    /// The masked string fields, sorted
+    pub(crate) fn string_fields(&self) -> Vec<&str> {
+        let mut fields: Vec<&str> = self
+            .string_masks
+            .iter()
+            .flat_map(|masks| masks.fields.names())
+            .collect();
+
+        fields.sort_unstable();
+        fields
+    }
+
+    /// The masked number fields, sorted
+    pub(crate) fn number_fields(&self) -> Vec<&str> {
+        let mut fields: Vec<&str> = self
+            .number_masks
+            .iter()
+            .flat_map(|masks| masks.fields.names())
+            .collect();
+
+        fields.sort_unstable();
+        fields
+    }
+
+    /// The masked string fields and their masks as reported in the masking metadata
+    pub(crate) fn string_field_masks(&self) -> Vec<(&str, String)> {
+        self.string_masks
+            .iter()
+            .flat_map(|masks| {
+                masks.fields.entries().map(|(field, index)| {
+                    let mask = masks.mask_option.get_metadata_replacement(field, index);
+                    (field, mask.into_owned())
+                })
+            })
+            .collect()
+    }
+
+    /// The masked number fields and their masks as reported in the masking metadata
+    pub(crate) fn number_field_masks(&self) -> Vec<(&str, String)> {
+        self.number_masks
+            .iter()
+            .flat_map(|masks| {
+                masks.fields.entries().map(|(field, index)| {
+                    (
+                        field,
+                        masks.mask_option.get_metadata_replacement(field, index),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Whether number masks also apply to string values holding a number
+    pub(crate) fn masks_numeric_strings(&self) -> bool {
+        self.mask_numeric_strings
+    }
+
+    pub(crate) fn set_mask_numeric_strings(&mut self, enabled: bool) {
+        self.mask_numeric_strings = enabled;
+    }
+
+    /// Adds the string and number masks of `other`, fields masked by both use the mask of `other`
+    pub(crate) fn merge(&mut self, other: BodyMask<T>) {
+        self.mask_numeric_strings |= other.mask_numeric_strings;
+
+        for masks in other.string_masks {
+            let fields: Vec<&str> = masks.fields.names().collect();
+            self.string_masks = remove_fields(
+                std::mem::take(&mut self.string_masks),
+                &fields,
+                string_fields_regex,
+            );
+            self.string_masks.push(masks);
+        }
+
+        for masks in other.number_masks {
+            let fields: Vec<&str> = masks.fields.names().collect();
+            self.number_masks = remove_fields(
+                std::mem::take(&mut self.number_masks),
+                &fields,
+                number_fields_regex,
+            );
+            self.number_masks.push(masks);
+        }
+    }
+
+    /// Stops masking the given string and number fields
+    pub(crate) fn remove(&mut self, fields: &[&str]) {
+        self.string_masks = remove_fields(
+            std::mem::take(&mut self.string_masks),
+            fields,
+            string_fields_regex,
+        );
+        self.number_masks = remove_fields(
+            std::mem::take(&mut self.number_masks),
+            fields,
+            number_fields_regex,
+        );
     }
 
     /// Creates a BodyMask from a list of string fields to mask
@@ -96,27 +335,14 @@ impl<T: Default> BodyMask<T> {
         masks_option: StringMaskingOption,
     ) -> Result<(), Error> {
         let string_masks = if !fields.is_empty() {
-            let mut string_mask_regex =
-                String::with_capacity((fields.len() * 32) + (fields.len() * 24));
-
-            // build up single regex from string field regexes
-            for field_name in fields.iter() {
-                let _ = write!(
-                    string_mask_regex,
-                    r##"(?:("{}"): *)(".*?[^\\]")(?: *[, \n\r}}]?)|"##,
-                    regex::escape(field_name)
-                );
-            }
+            let names: Vec<&str> = fields.iter().map(String::as_str).collect();
 
-            // drop the last "|"
-            string_mask_regex.pop();
+            let string_masks =
+                string_fields_regex(&names).ok_or_else(|| Error::StringField(names.join(", ")))?;
 
-            let string_masks = Regex::new(&string_mask_regex)
-                .map_err(|_| Error::StringField(string_mask_regex))?;
-
-            Some(BodyMaskInner::new(string_masks, fields, masks_option))
+            vec![BodyMaskInner::new(string_masks, fields, masks_option)]
         } else {
-            None
+            Vec::new()
         };
 
         self.string_masks = string_masks;
@@ -132,25 +358,14 @@ impl<T: Default> BodyMask<T> {
         masks_option: NumberMaskingOption,
     ) -> Result<(), Error> {
         let masks = if !fields.is_empty() {
-            let mut mask_regex = String::with_capacity((fields.len() * 32) + (fields.len() * 24));
-
-            // build up single regex from string field regexes
-            for field_name in fields.iter() {
-                let _ = write!(
-                    mask_regex,
-                    r##"(?:("{}"): *)(-?[0-9]+\.?[0-9]*)( *[, \n\r}}]?)|"##,
-                    regex::escape(field_name)
-                );
-            }
+            let names: Vec<&str> = fields.iter().map(String::as_str).collect();
 
-            // drop the last "|"
-            mask_regex.pop();
+            let masks =
+                number_fields_regex(&names).ok_or_else(|| Error::NumberField(names.join(", ")))?;
 
-            let masks = Regex::new(&mask_regex).map_err(|_| Error::NumberField(mask_regex))?;
-
-            Some(BodyMaskInner::new(masks, fields, masks_option))
+            vec![BodyMaskInner::new(masks, fields, masks_option)]
         } else {
-            None
+            Vec::new()
         };
 
         self.number_masks = masks;
@@ -160,69 +375,174 @@ impl<T: Default> BodyMask<T> {
 
     /// Will use the regexes stored in the struct to mask the body
     pub fn mask(&self, body: &str) -> String {
+        let mut body = Cow::Borrowed(body);
+
         // mask string fields
-        let body = if let Some(body_mask) = &self.string_masks {
-            body_mask.regex.replace_all(body, |caps: &Captures| {
-                if let Some(field) = util::get_first_capture(caps) {
-                    let (field_without_quotes, index) =
-                        body_mask.fields.get(field).unwrap_or_default();
+        for body_mask in &self.string_masks {
+            if let Cow::Owned(masked) = body_mask.mask(&body) {
+                body = Cow::Owned(masked);
+            }
+        }
 
-                    let replacement_mask = body_mask
-                        .mask_option
-                        .get_mask_replacement(&field_without_quotes, index);
+        // mask number fields
+        for body_mask in &self.number_masks {
+            if let Cow::Owned(masked) = body_mask.mask(&body, self.mask_numeric_strings) {
+                body = Cow::Owned(masked);
+            }
+        }
 
-                    format!(
-                        r#"{}:"{}"{}"#,
-                        field,
-                        replacement_mask,
-                        caps[0].chars().last().unwrap()
-                    )
-                } else {
-                    caps[0].to_string()
+        body.into_owned()
+    }
+
+    /// Checks if the field is masked as a string field
+    pub(crate) fn is_string_masked(&self, field: &str) -> bool {
+        let quoted_field = format!("\"{}\"", field);
+        self.string_masks
+            .iter()
+            .any(|masks| masks.fields.get(&quoted_field).is_some())
+    }
+
+    /// Checks if the field is masked as a number field
+    pub(crate) fn is_number_masked(&self, field: &str) -> bool {
+        let quoted_field = format!("\"{}\"", field);
+        self.number_masks
+            .iter()
+            .any(|masks| masks.fields.get(&quoted_field).is_some())
+    }
+
+    /// Masks a single JSON object member value, `raw_key` is the quoted key as it appears in the body
+    /// and `raw_value` the JSON text of a string or scalar value.
+    /// Returns the JSON text of the mask, or `None` if the value isn't masked
+    pub(crate) fn mask_json_value(&self, raw_key: &str, raw_value: &str) -> Option<String> {
+        let string_value = raw_value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'));
+
+        if let Some(value) = string_value {
+            for body_mask in &self.string_masks {
+                if let Some((field_without_quotes, index)) = body_mask.fields.get(raw_key) {
+                    let value = serde_json::from_str::<String>(raw_value)
+                        .map(Cow::Owned)
+                        .unwrap_or(Cow::Borrowed(value));
+
+                    let replacement_mask = body_mask.mask_option.get_mask_replacement(
+                        &field_without_quotes,
+                        index,
+                        &value,
+                    );
+
+                    return Some(
+                        serde_json::to_string(&replacement_mask)
+                            .expect("string will serialize to json"),
+                    );
                 }
-            })
-        } else {
-            Cow::Borrowed(body)
+            }
+        }
+
+        let (value, quoted) = match string_value {
+            Some(value) if self.mask_numeric_strings => (value, true),
+            Some(_) => return None,
+            None => (raw_value, false),
         };
 
-        // mask number fields
-        let body = if let Some(body_mask) = &self.number_masks {
-            body_mask.regex.replace_all(&body, |caps: &Captures| {
-                if let Some(field) = util::get_first_capture(caps) {
-                    let (field_without_quotes, index) =
-                        body_mask.fields.get(field).unwrap_or_default();
+        if !is_number(value) {
+            return None;
+        }
 
-                    let replacement_mask = body_mask
+        self.number_masks.iter().find_map(|body_mask| {
+            let (field_without_quotes, index) = body_mask.fields.get(raw_key)?;
+            let replacement_mask =
+                body_mask
+                    .mask_option
+                    .get_mask_replacement(&field_without_quotes, index, value);
+
+            Some(if quoted {
+                format!(r#""{}""#, replacement_mask)
+            } else {
+                replacement_mask
+            })
+        })
+    }
+
+    /// Masks a single named value outside of a JSON document, e.g. a form parameter.
+    /// String masks apply to any value, number masks only to values that parse as a number
+    pub(crate) fn mask_field<'a>(&'a self, field: &str, value: &'a str) -> Cow<'a, str> {
+        let quoted_field = format!("\"{}\"", field);
+
+        for body_mask in &self.string_masks {
+            if let Some((field_without_quotes, index)) = body_mask.fields.get(&quoted_field) {
+                return Cow::Owned(
+                    body_mask
                         .mask_option
-                        .get_mask_replacement(&field_without_quotes, index);
+                        .get_mask_replacement(&field_without_quotes, index, value)
+                        .into_owned(),
+                );
+            }
+        }
 
-                    format!(
-                        r#"{}:{}{}"#,
-                        field,
-                        replacement_mask,
-                        caps[0].chars().last().unwrap()
-                    )
-                } else {
-                    caps[0].to_string()
+        for body_mask in &self.number_masks {
+            if let Some((field_without_quotes, index)) = body_mask.fields.get(&quoted_field) {
+                if value.parse::<f64>().is_ok() {
+                    return Cow::Owned(body_mask.mask_option.get_mask_replacement(
+                        &field_without_quotes,
+                        index,
+                        value,
+                    ));
                 }
-            })
-        } else {
-            body
-        };
+            }
+        }
 
-        body.to_string()
+        Cow::Borrowed(value)
     }
 
     pub(crate) fn into_metadata(self) -> (HashMap<String, String>, HashMap<String, String>) {
-        let string_masks = self.string_masks.map(|m| m.into()).unwrap_or_default();
-        let number_masks = self.number_masks.map(|m| m.into()).unwrap_or_default();
+        let string_masks = self
+            .string_masks
+            .into_iter()
+            .flat_map(HashMap::<String, String>::from)
+            .collect();
+        let number_masks = self
+            .number_masks
+            .into_iter()
+            .flat_map(HashMap::<String, String>::from)
+            .collect();
         (string_masks, number_masks)
     }
 }
 
+/// Checks if the value is a number as matched by the number field regex
+fn is_number(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let (mantissa, exponent) = match digits.find(['e', 'E']) {
+        Some(index) => (&digits[..index], Some(&digits[index + 1..])),
+        None => (digits, None),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    let all_digits = |digits: &str| digits.bytes().all(|b| b.is_ascii_digit());
+    let valid_exponent = exponent.is_none_or(|exponent| {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        !exponent.is_empty() && all_digits(exponent)
+    });
+
+    !integer.is_empty() && all_digits(integer) && all_digits(fraction) && valid_exponent
+}
+
+fn remove_fields<T>(
+    masks: Vec<BodyMaskInner<T>>,
+    fields: &[&str],
+    build_regex: fn(&[&str]) -> Option<Regex>,
+) -> Vec<BodyMaskInner<T>> {
+    masks
+        .into_iter()
+        .filter_map(|masks| masks.without(fields, build_regex))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::masking::{KeyedHash, NumberMask};
     use maplit::hashmap;
     use pretty_assertions::assert_eq;
 
@@ -258,10 +578,7 @@ mod tests {
             }
 
             // setup number field masks
-            body_mask.set_number_field_masks(
-                number_fields.into(),
-                NumberMaskingOption::MultipleMasks(number_masks),
-            )?;
+            body_mask.set_number_field_masks(number_fields.into(), number_masks.into())?;
 
             Ok(body_mask)
         }
@@ -384,4 +701,135 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn partial_masks_keep_shape_of_value() {
+        let mut body_mask = BodyMask::<RequestMask>::default();
+
+        let masks: Vec<(&str, StringMaskingOption)> = vec![
+            ("card", StringMaskingOption::KeepLast(4)),
+            ("email", StringMaskingOption::KeepEmailDomain),
+            ("code", StringMaskingOption::PreserveFormat),
+            ("name", StringMaskingOption::PreserveLength),
+        ];
+
+        let body = r#"{"card": "4111 1111 1111 1234", "email": "jane\"doe@example.com", "code": "AB12-cd", "name": "Jane"}"#;
+        let expected = [
+            r#"{"card":"***************1234", "email": "jane\"doe@example.com", "code": "AB12-cd", "name": "Jane"}"#,
+            r#"{"card": "4111 1111 1111 1234", "email":"********@example.com", "code": "AB12-cd", "name": "Jane"}"#,
+            r#"{"card": "4111 1111 1111 1234", "email": "jane\"doe@example.com", "code":"XX99-xx", "name": "Jane"}"#,
+            r#"{"card": "4111 1111 1111 1234", "email": "jane\"doe@example.com", "code": "AB12-cd", "name":"****"}"#,
+        ];
+
+        for ((field, option), expected) in masks.into_iter().zip(expected) {
+            body_mask
+                .set_string_field_masks(field.into(), option)
+                .unwrap();
+            assert_eq!(body_mask.mask(body), expected);
+        }
+
+        body_mask
+            .set_number_field_masks("amount".into(), NumberMaskingOption::KeepLast(2))
+            .unwrap();
+        assert_eq!(
+            body_mask.mask(r#"{"amount": -1234.56}"#),
+            r#"{"amount":-9999.56}"#
+        );
+
+        body_mask
+            .set_number_field_masks("amount".into(), NumberMaskingOption::PreserveFormat)
+            .unwrap();
+        assert_eq!(
+            body_mask.mask(r#"{"amount": 1234.56}"#),
+            r#"{"amount":9999.99}"#
+        );
+
+        // values that aren't longer than the kept part are masked completely
+        body_mask
+            .set_string_field_masks("pin".into(), StringMaskingOption::KeepLast(4))
+            .unwrap();
+        body_mask
+            .set_number_field_masks("code".into(), NumberMaskingOption::KeepLast(4))
+            .unwrap();
+        assert_eq!(
+            body_mask.mask(r#"{"pin": "1234", "code": 123}"#),
+            r#"{"pin":"****", "code":999}"#
+        );
+    }
+
+    #[test]
+    fn masks_wide_and_string_numbers() {
+        let mut body_mask = BodyMask::<RequestMask>::default();
+        body_mask
+            .set_number_field_masks(
+                vec!["id", "amount"].into(),
+                hashmap! { "id" => NumberMask::from(9_007_199_254_740_993_i64), "amount" => 0.5.into() }.into(),
+            )
+            .unwrap();
+
+        let body = r#"{"id": 1.2e+21, "amount": "123.45", "note": "12 items"}"#;
+
+        assert_eq!(
+            body_mask.mask(body),
+            r#"{"id":9007199254740993, "amount": "123.45", "note": "12 items"}"#
+        );
+        let mut number_field_masks = body_mask.number_field_masks();
+        number_field_masks.sort();
+        assert_eq!(
+            number_field_masks,
+            vec![
+                ("amount", "0.5".to_string()),
+                ("id", "9007199254740993".to_string())
+            ]
+        );
+
+        body_mask.set_mask_numeric_strings(true);
+        assert_eq!(
+            body_mask.mask(body),
+            r#"{"id":9007199254740993, "amount":"0.5", "note": "12 items"}"#
+        );
+
+        body_mask
+            .set_number_field_masks(
+                vec!["id", "amount"].into(),
+                NumberMaskingOption::KeepLast(2),
+            )
+            .unwrap();
+        assert_eq!(
+            body_mask.mask(r#"{"id": -1234E-5, "amount": "12.34", "missing": "12"}"#),
+            r#"{"id":-9934E-5, "amount":"99.34", "missing": "12"}"#
+        );
+    }
+
+    #[test]
+    fn keyed_hash_masks_preserve_correlation() {
+        let mut body_mask = BodyMask::<RequestMask>::default();
+        body_mask
+            .set_string_field_masks("email".into(), KeyedHash::new("secret").into())
+            .unwrap();
+        body_mask
+            .set_number_field_masks("account".into(), KeyedHash::new("secret").into())
+            .unwrap();
+
+        let first = body_mask.mask(r#"{"email": "jane@example.com", "account": 1234}"#);
+        // the same number written differently masks the same
+        let second = body_mask.mask(r#"{"account": 1.234e3, "email": "jane@example.com"}"#);
+        let other = body_mask.mask(r#"{"email": "john@example.com", "account": 4321}"#);
+
+        let email = KeyedHash::new("secret").hash_string("jane@example.com");
+        let account = KeyedHash::new("secret").hash_number("1234");
+
+        assert_eq!(
+            first,
+            format!(r#"{{"email":"{}", "account":{}}}"#, email, account)
+        );
+        assert_eq!(
+            second,
+            format!(r#"{{"account":{}, "email":"{}"}}"#, account, email)
+        );
+        assert!(!other.contains(&email));
+        assert!(!other.contains(&account.to_string()));
+        assert_eq!(email.len(), 16);
+        assert_eq!(account, KeyedHash::new("secret").hash_number("1234.0"));
+    }
 }
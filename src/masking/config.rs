@@ -0,0 +1,535 @@
+//! Declarative masking configuration that can be loaded from any serde format (TOML, YAML, JSON, ...)
+
+use std::collections::HashMap;
+
+use serde::{de, Deserialize, Deserializer};
+
+use super::{
+    body_mask::{BodyMask, RequestMask},
+    lowercase, lowercase_masks,
+    names::{
+        self, is_valid_cookie, is_valid_field, is_valid_header, is_valid_path_param,
+        is_valid_query_string,
+    },
+    route::RoutePattern,
+    AuthorizationMask, Error, KeyedHash, Masking, NumberMask, NumberMaskingOption,
+    StringMaskingOption,
+};
+
+const MAX_KEYED_HASH_LENGTH: usize = 64;
+
+/// Masking rules as a serde document, for masking rules that are maintained as config instead of code.
+///
+/// Every entry lists the names to mask and an optional mask, entries that are left out are not masked.
+/// `routes` add masks for requests matching a method and path hint pattern, see [Masking::with_route_masking].
+/// [Masking] can be deserialized directly from the same document, it's validated while deserializing.
+///
+/// Masks are either a single mask (`"__secret__"` or `0`), a list of masks used in order (`["***", "###"]`),
+/// or a table with a `type`:
+/// - `{ type = "default" }`
+/// - `{ type = "associated", masks = { password = "***" } }`
+/// - `{ type = "keyed_hash", key = "my secret key", length = 32 }`
+/// - `{ type = "keep_last", count = 4 }`
+/// - `{ type = "keep_email_domain" }`, `{ type = "preserve_length" }` (string fields only)
+/// - `{ type = "preserve_format" }`
+/// - `{ type = "authorization", allowed_claims = ["sub", "scope"] }` (string fields only)
+///
+/// # Examples
+/// ```rust
+/// use speakeasy_rust_sdk::{Masking, masking::MaskingConfig};
+///
+/// let config: MaskingConfig = serde_json::from_str(r#"{
+///     "request_headers": { "fields": ["authorization"], "mask": { "type": "authorization" } },
+///     "request_string_fields": { "fields": ["password", "secret"] },
+///     "response_number_fields": { "fields": ["balance"], "mask": 0 },
+///     "routes": [
+///         { "route": "GET /users/*", "masking": { "response_string_fields": { "fields": ["email"], "mask": { "type": "keep_email_domain" } } } }
+///     ]
+/// }"#).unwrap();
+///
+/// config.validate().expect("valid masking config");
+/// let masking = Masking::try_from(config).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaskingConfig {
+    /// `query_strings`, query string masks, see [Masking::with_query_string_mask]
+    pub query_strings: Option<StringMaskConfig>,
+    /// `path_params`, URL path segment masks by path hint param name, see [Masking::with_path_param_mask]
+    pub path_params: Option<StringMaskConfig>,
+    /// `request_headers`, see [Masking::with_request_header_mask]
+    pub request_headers: Option<StringMaskConfig>,
+    /// `response_headers`, see [Masking::with_response_header_mask]
+    pub response_headers: Option<StringMaskConfig>,
+    /// `request_cookies`, see [Masking::with_request_cookie_mask]
+    pub request_cookies: Option<StringMaskConfig>,
+    /// `response_cookies`, see [Masking::with_response_cookie_mask]
+    pub response_cookies: Option<StringMaskConfig>,
+    /// `request_string_fields`, string fields of request bodies, see [Masking::with_request_field_mask_string]
+    pub request_string_fields: Option<StringMaskConfig>,
+    /// `request_number_fields`, number fields of request bodies, see [Masking::with_request_field_mask_number]
+    pub request_number_fields: Option<NumberMaskConfig>,
+    /// `response_string_fields`, string fields of response bodies, see [Masking::with_response_field_mask_string]
+    pub response_string_fields: Option<StringMaskConfig>,
+    /// `response_number_fields`, number fields of response bodies, see [Masking::with_response_field_mask_number]
+    pub response_number_fields: Option<NumberMaskConfig>,
+    /// `mask_numeric_strings`, number field masks also mask string values holding a number, see [Masking::with_mask_numeric_strings]
+    pub mask_numeric_strings: bool,
+    /// `routes`, masks for the requests matching a route
+    pub routes: Vec<RouteMaskingConfig>,
+}
+
+/// Names to mask with a [StringMaskingOption]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StringMaskConfig {
+    /// `fields`, the names to mask
+    pub fields: Vec<String>,
+    /// `mask`, defaults to `"__masked__"`
+    #[serde(default)]
+    pub mask: StringMaskingOption,
+}
+
+/// Names to mask with a [NumberMaskingOption]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NumberMaskConfig {
+    /// `fields`, the names to mask
+    pub fields: Vec<String>,
+    /// `mask`, defaults to `-12321`
+    #[serde(default)]
+    pub mask: NumberMaskingOption,
+}
+
+/// Masks merged with the other masks for requests matching a route, see [Masking::with_route_masking]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteMaskingConfig {
+    /// `route`, an optional method and a path hint pattern, e.g. `POST /auth/*` or `/users/{id}`
+    pub route: String,
+    /// `masking`, the masks of the route, routes can't be nested
+    pub masking: MaskingConfig,
+}
+
+impl MaskingConfig {
+    /// Checks the config for names that can never match and masks that can't be built,
+    /// every problem found is reported in [Error::Invalid]
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut errors = Vec::new();
+        self.collect_errors(&mut errors);
+
+        let mut routes: Vec<RoutePattern> = Vec::new();
+
+        for route in &self.routes {
+            let mut route_errors = Vec::new();
+
+            match RoutePattern::parse(&route.route) {
+                Some(pattern) if routes.iter().any(|existing| existing.is_same(&pattern)) => {
+                    errors.push(Error::DuplicateRoute(route.route.clone()));
+                }
+                Some(pattern) => routes.push(pattern),
+                None => route_errors.push(Error::InvalidRoute(route.route.clone())),
+            }
+
+            if !route.masking.routes.is_empty() {
+                route_errors.push(Error::NestedRoute);
+            }
+
+            route.masking.collect_errors(&mut route_errors);
+
+            errors.extend(route_errors.into_iter().map(|error| Error::Route {
+                route: route.route.clone(),
+                error: Box::new(error),
+            }));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Invalid(errors))
+        }
+    }
+
+    fn collect_errors(&self, errors: &mut Vec<Error>) {
+        let names = [
+            (
+                "query string",
+                &self.query_strings,
+                is_valid_query_string as fn(&str) -> bool,
+            ),
+            ("path param", &self.path_params, is_valid_path_param),
+            ("request header", &self.request_headers, is_valid_header),
+            ("response header", &self.response_headers, is_valid_header),
+            ("request cookie", &self.request_cookies, is_valid_cookie),
+            ("response cookie", &self.response_cookies, is_valid_cookie),
+            ("request field", &self.request_string_fields, is_valid_field),
+            (
+                "response field",
+                &self.response_string_fields,
+                is_valid_field,
+            ),
+        ];
+
+        for (location, config, is_valid) in names {
+            if let Some(config) = config {
+                names::validate(
+                    errors,
+                    location,
+                    config.fields.iter().map(String::as_str),
+                    is_valid,
+                );
+            }
+        }
+
+        for config in [&self.request_number_fields, &self.response_number_fields]
+            .into_iter()
+            .flatten()
+        {
+            names::validate(
+                errors,
+                "number field",
+                config.fields.iter().map(String::as_str),
+                is_valid_field,
+            );
+        }
+
+        // the body masks are built from a regex of the field names
+        for config in [&self.request_string_fields, &self.response_string_fields]
+            .into_iter()
+            .flatten()
+        {
+            if let Err(err) = BodyMask::<RequestMask>::default()
+                .set_string_field_masks(config.fields.clone().into(), StringMaskingOption::None)
+            {
+                errors.push(err.into());
+            }
+        }
+
+        for config in [&self.request_number_fields, &self.response_number_fields]
+            .into_iter()
+            .flatten()
+        {
+            if let Err(err) = BodyMask::<RequestMask>::default()
+                .set_number_field_masks(config.fields.clone().into(), NumberMaskingOption::None)
+            {
+                errors.push(err.into());
+            }
+        }
+    }
+
+    fn into_masking(self) -> Result<Masking, Error> {
+        let mut masking = Masking::default();
+
+        if let Some(config) = non_empty(self.query_strings) {
+            masking.with_query_string_mask(config.fields, config.mask);
+        }
+
+        if let Some(config) = non_empty(self.path_params) {
+            masking.with_path_param_mask(config.fields, config.mask);
+        }
+
+        if let Some(config) = non_empty(self.request_headers) {
+            masking.with_request_header_mask(
+                lowercase(config.fields.into()),
+                lowercase_masks(config.mask),
+            );
+        }
+
+        if let Some(config) = non_empty(self.response_headers) {
+            masking.with_response_header_mask(
+                lowercase(config.fields.into()),
+                lowercase_masks(config.mask),
+            );
+        }
+
+        if let Some(config) = non_empty(self.request_cookies) {
+            masking.with_request_cookie_mask(config.fields, config.mask);
+        }
+
+        if let Some(config) = non_empty(self.response_cookies) {
+            masking.with_response_cookie_mask(config.fields, config.mask);
+        }
+
+        if let Some(config) = non_empty(self.request_string_fields) {
+            masking
+                .request_masks
+                .set_string_field_masks(config.fields.into(), config.mask)?;
+        }
+
+        if let Some(config) = self.request_number_fields {
+            masking
+                .request_masks
+                .set_number_field_masks(config.fields.into(), config.mask)?;
+        }
+
+        if let Some(config) = non_empty(self.response_string_fields) {
+            masking
+                .response_masks
+                .set_string_field_masks(config.fields.into(), config.mask)?;
+        }
+
+        if let Some(config) = self.response_number_fields {
+            masking
+                .response_masks
+                .set_number_field_masks(config.fields.into(), config.mask)?;
+        }
+
+        masking.with_mask_numeric_strings(self.mask_numeric_strings);
+
+        for route in self.routes {
+            masking.try_with_route_masking(&route.route, route.masking.into_masking()?)?;
+        }
+
+        Ok(masking)
+    }
+}
+
+impl TryFrom<MaskingConfig> for Masking {
+    type Error = Error;
+
+    fn try_from(config: MaskingConfig) -> Result<Self, Self::Error> {
+        config.validate()?;
+        config.into_masking()
+    }
+}
+
+fn non_empty(config: Option<StringMaskConfig>) -> Option<StringMaskConfig> {
+    config.filter(|config| !config.fields.is_empty())
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MaskingOptionConfig<T> {
+    SingleMask(T),
+    MultipleMasks(Vec<T>),
+    Typed(TypedMaskingOption<T>),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TypedMaskingOption<T> {
+    Default,
+    Associated { masks: HashMap<String, T> },
+    KeyedHash { key: String, length: Option<usize> },
+    KeepLast { count: usize },
+    KeepEmailDomain,
+    PreserveLength,
+    PreserveFormat,
+    Authorization { allowed_claims: Option<Vec<String>> },
+}
+
+fn keyed_hash<E: de::Error>(key: String, length: Option<usize>) -> Result<KeyedHash, E> {
+    if key.is_empty() {
+        return Err(E::custom("keyed_hash key can't be empty"));
+    }
+
+    match length {
+        Some(length) if length == 0 || length > MAX_KEYED_HASH_LENGTH => Err(E::custom(format!(
+            "keyed_hash length has to be between 1 and {}",
+            MAX_KEYED_HASH_LENGTH
+        ))),
+        Some(length) => Ok(KeyedHash::new(key).with_length(length)),
+        None => Ok(KeyedHash::new(key)),
+    }
+}
+
+impl<'de> Deserialize<'de> for StringMaskingOption {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let config = match Option::<MaskingOptionConfig<String>>::deserialize(deserializer)? {
+            Some(config) => config,
+            None => return Ok(Self::None),
+        };
+
+        let option = match config {
+            MaskingOptionConfig::SingleMask(mask) => Self::SingleMask(mask),
+            MaskingOptionConfig::MultipleMasks(masks) => Self::MultipleMasks(masks),
+            MaskingOptionConfig::Typed(typed) => match typed {
+                TypedMaskingOption::Default => Self::None,
+                TypedMaskingOption::Associated { masks } => Self::AssociatedMasks(masks),
+                TypedMaskingOption::KeyedHash { key, length } => {
+                    Self::KeyedHash(keyed_hash(key, length)?)
+                }
+                TypedMaskingOption::KeepLast { count } => Self::KeepLast(count),
+                TypedMaskingOption::KeepEmailDomain => Self::KeepEmailDomain,
+                TypedMaskingOption::PreserveLength => Self::PreserveLength,
+                TypedMaskingOption::PreserveFormat => Self::PreserveFormat,
+                TypedMaskingOption::Authorization { allowed_claims } => {
+                    Self::Authorization(match allowed_claims {
+                        Some(allowed_claims) => AuthorizationMask::new(allowed_claims),
+                        None => AuthorizationMask::default(),
+                    })
+                }
+            },
+        };
+
+        Ok(option)
+    }
+}
+
+impl<'de> Deserialize<'de> for NumberMaskingOption {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let config = match Option::<MaskingOptionConfig<NumberMask>>::deserialize(deserializer)? {
+            Some(config) => config,
+            None => return Ok(Self::None),
+        };
+
+        let option = match config {
+            MaskingOptionConfig::SingleMask(mask) => Self::SingleMask(mask),
+            MaskingOptionConfig::MultipleMasks(masks) => Self::MultipleMasks(masks),
+            MaskingOptionConfig::Typed(typed) => match typed {
+                TypedMaskingOption::Default => Self::None,
+                TypedMaskingOption::Associated { masks } => Self::AssociatedMasks(masks),
+                TypedMaskingOption::KeyedHash { key, length } => {
+                    Self::KeyedHash(keyed_hash(key, length)?)
+                }
+                TypedMaskingOption::KeepLast { count } => Self::KeepLast(count),
+                TypedMaskingOption::PreserveFormat => Self::PreserveFormat,
+                TypedMaskingOption::KeepEmailDomain
+                | TypedMaskingOption::PreserveLength
+                | TypedMaskingOption::Authorization { .. } => {
+                    return Err(de::Error::custom(
+                        "mask type is only supported for string fields",
+                    ))
+                }
+            },
+        };
+
+        Ok(option)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speakeasy_protos::ingest::ingest_request::MaskingMetadata;
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        config: &'static str,
+        expected_errors: Vec<&'static str>,
+    }
+
+    #[test]
+    fn loads_masking_from_toml() {
+        let masking: Masking = toml::from_str(
+            r#"
+            [request_headers]
+            fields = ["Authorization", "x-api-key"]
+            mask = { type = "authorization" }
+
+            [request_string_fields]
+            fields = ["password", "secret"]
+            mask = ["***", "__secret__"]
+
+            [response_number_fields]
+            fields = ["balance"]
+            mask = 0
+
+            [[routes]]
+            route = "PUT /users/:id"
+
+            [routes.masking.request_string_fields]
+            fields = ["email", "secret"]
+            mask = { type = "keep_email_domain" }
+            "#,
+        )
+        .unwrap();
+
+        let metadata = MaskingMetadata::from(masking.for_route("PUT", "/users/{id}"));
+
+        assert_eq!(
+            metadata.request_header_masks.get("authorization"),
+            Some(&"__authorization__".to_string())
+        );
+        assert_eq!(
+            metadata.request_field_masks_string,
+            HashMap::from([
+                ("password".to_string(), "***".to_string()),
+                ("email".to_string(), "__keep_email_domain__".to_string()),
+                ("secret".to_string(), "__keep_email_domain__".to_string())
+            ])
+        );
+
+        let metadata = MaskingMetadata::from(masking.for_route("GET", "/users/{id}"));
+
+        assert_eq!(
+            metadata.request_field_masks_string,
+            HashMap::from([
+                ("password".to_string(), "***".to_string()),
+                ("secret".to_string(), "__secret__".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_invalid_config() {
+        let tests = vec![
+            Test {
+                name: "valid config",
+                config: r#"
+                [query_strings]
+                fields = ["token"]
+                "#,
+                expected_errors: vec![],
+            },
+            Test {
+                name: "invalid names",
+                config: r#"
+                [request_headers]
+                fields = ["x api key"]
+
+                [request_cookies]
+                fields = ["session=id"]
+
+                [response_string_fields]
+                fields = ["", "pass\"word"]
+                "#,
+                expected_errors: vec![
+                    r#"invalid request header name: "x api key""#,
+                    r#"invalid request cookie name: "session=id""#,
+                    r#"invalid response field name: """#,
+                    r#"invalid response field name: "pass\"word""#,
+                ],
+            },
+            Test {
+                name: "invalid routes",
+                config: r#"
+                [[routes]]
+                route = "POST users/{id}"
+
+                [[routes.masking.routes]]
+                route = "/nested"
+
+                [[routes]]
+                route = "GET /orders/{id}"
+
+                [routes.masking.path_params]
+                fields = ["{id}"]
+
+                [[routes]]
+                route = "get /orders/:id"
+                "#,
+                expected_errors: vec![
+                    r#"in route masking for "POST users/{id}": invalid route: "POST users/{id}", expected an optional method and a path starting with `/`"#,
+                    r#"in route masking for "POST users/{id}": route masking can't have routes of its own"#,
+                    r#"in route masking for "GET /orders/{id}": invalid path param name: "{id}""#,
+                    r#"duplicate route masking for "get /orders/:id""#,
+                ],
+            },
+        ];
+
+        for test in tests {
+            let config: MaskingConfig = toml::from_str(test.config).unwrap();
+
+            let errors = match config.validate() {
+                Ok(()) => vec![],
+                Err(Error::Invalid(errors)) => errors.iter().map(ToString::to_string).collect(),
+                Err(err) => panic!("unexpected error: {}", err),
+            };
+
+            assert_eq!(errors, test.expected_errors);
+        }
+    }
+}
@@ -66,6 +66,27 @@ impl BodyMaskFieldsSearchMap {
         self.0.get(field).cloned()
     }
 
+    /// The field names without quotes
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.values().map(|(field, _)| field.as_str())
+    }
+
+    /// The field names without quotes and their index
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.0
+            .values()
+            .map(|(field, index)| (field.as_str(), *index))
+    }
+
+    /// Keeps the fields whose name without quotes matches the predicate, indexes are kept as is
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.0.retain(|_, (field, _)| keep(field));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub(crate) fn into_iter(self) -> impl Iterator<Item = (String, (String, usize))> {
         self.0.into_iter()
     }
@@ -91,6 +112,23 @@ impl GenericMaskFieldsSearchMap {
         self.0.get(field).copied()
     }
 
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.0.iter().map(|(field, index)| (field.as_str(), *index))
+    }
+
+    /// Keeps the fields matching the predicate, indexes are kept as is
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.0.retain(|field, _| keep(field));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub(crate) fn into_iter(self) -> impl Iterator<Item = (String, usize)> {
         self.0.into_iter()
     }
@@ -1,5 +1,5 @@
 use super::{fields::GenericMaskFieldsSearchMap, Fields, StringMaskingOption};
-use std::{collections::HashMap, marker::PhantomData};
+use std::{borrow::Cow, collections::HashMap, marker::PhantomData};
 
 #[derive(Debug, Clone, Default)]
 pub struct QueryStringMask;
@@ -11,25 +11,82 @@ pub struct ResponseHeaderMask;
 pub struct RequestCookieMask;
 #[derive(Debug, Clone, Default)]
 pub struct ResponseCookieMask;
+#[derive(Debug, Clone, Default)]
+pub struct PathParamMask;
 
+/// Layers of masks, a name is only ever in one layer, merged layers take over the names they mask
 #[derive(Debug, Clone, Default)]
-pub(crate) struct GenericMask<T>(Option<GenericMaskInner<T>>);
+pub(crate) struct GenericMask<T>(Vec<GenericMaskInner<T>>);
 
 impl<T> GenericMask<T> {
     pub(crate) fn new(fields: Fields, mask_option: StringMaskingOption) -> Self {
         let inner = GenericMaskInner::new(fields, mask_option);
-        Self(Some(inner))
+        Self(vec![inner])
     }
 
     pub(crate) fn mask(&self, field: &str, value: &str) -> String {
-        match &self.0 {
+        match self
+            .0
+            .iter()
+            .find(|inner| inner.fields.get(field).is_some())
+        {
             Some(inner) => inner.mask(field, value).to_string(),
             None => value.to_string(),
         }
     }
 
     pub(crate) fn is_empty(&self) -> bool {
-        self.0.is_none()
+        self.0.is_empty()
+    }
+
+    /// The masked names, sorted
+    pub(crate) fn fields(&self) -> Vec<&str> {
+        let mut fields: Vec<&str> = self
+            .0
+            .iter()
+            .flat_map(|inner| inner.fields.names())
+            .collect();
+
+        fields.sort_unstable();
+        fields
+    }
+
+    /// The masked names and their masks as reported in the masking metadata
+    pub(crate) fn masks(&self) -> Vec<(&str, Cow<'_, str>)> {
+        self.0
+            .iter()
+            .flat_map(|inner| {
+                inner.fields.entries().map(|(field, index)| {
+                    (
+                        field,
+                        inner.mask_option.get_metadata_replacement(field, index),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) fn is_masked(&self, field: &str) -> bool {
+        self.0.iter().any(|inner| inner.fields.get(field).is_some())
+    }
+
+    /// Adds the masks of `other`, names masked by both use the mask of `other`
+    pub(crate) fn merge(&mut self, other: GenericMask<T>) {
+        for inner in other.0 {
+            self.remove(inner.fields.names());
+            self.0.push(inner);
+        }
+    }
+
+    /// Stops masking the given names
+    pub(crate) fn remove<'a>(&mut self, fields: impl IntoIterator<Item = &'a str>) {
+        let fields: Vec<&str> = fields.into_iter().collect();
+
+        for inner in &mut self.0 {
+            inner.fields.retain(|field| !fields.contains(&field));
+        }
+
+        self.0.retain(|inner| !inner.fields.is_empty());
     }
 }
 
@@ -49,22 +106,23 @@ impl<T> GenericMaskInner<T> {
         }
     }
 
-    fn mask<'a>(&'a self, field: &str, value: &'a str) -> &str {
+    fn mask<'a>(&'a self, field: &str, value: &'a str) -> Cow<'a, str> {
         // If the field is not in the list of fields to mask, return the value as is.
         if let Some(field_index) = self.fields.get(field) {
-            self.mask_option.get_mask_replacement(field, field_index)
+            self.mask_option
+                .get_mask_replacement(field, field_index, value)
         } else {
-            value
+            Cow::Borrowed(value)
         }
     }
 }
 
 impl<T> From<GenericMask<T>> for HashMap<String, String> {
     fn from(mask: GenericMask<T>) -> Self {
-        match mask.0 {
-            Some(inner) => inner.into(),
-            None => HashMap::new(),
-        }
+        mask.0
+            .into_iter()
+            .flat_map(HashMap::<String, String>::from)
+            .collect()
     }
 }
 
@@ -75,7 +133,7 @@ impl<T> From<GenericMaskInner<T>> for HashMap<String, String> {
             .map(|(field, index)| {
                 let value = mask
                     .mask_option
-                    .get_mask_replacement(&field, index)
+                    .get_metadata_replacement(&field, index)
                     .to_string();
 
                 (field, value)
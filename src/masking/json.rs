@@ -0,0 +1,347 @@
+//! Minimal JSON rewriter used by the masks that need to understand the structure of a body,
+//! the original formatting of everything that is kept is preserved
+
+// nesting deeper than this is treated as invalid JSON instead of overflowing the stack, the same limit as serde_json
+const MAX_DEPTH: usize = 128;
+
+/// The type of a JSON value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValueKind {
+    String,
+    Number,
+    Bool,
+    Null,
+    Object,
+    Array,
+}
+
+/// What to do with a visited object member or array element
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Action {
+    /// Keep the value, objects and arrays are still visited
+    Keep,
+    /// Remove the member (or array element) entirely
+    Remove,
+    /// Replace the raw value with the given JSON text
+    Replace(String),
+}
+
+/// Rewrites a JSON document, calling `visit` for every object member and array element.
+///
+/// `visit` receives the unescaped key (the key of the enclosing member for array elements,
+/// `None` for the root), the raw JSON text of the value and its type.
+/// Returns `None` if the body is not valid JSON or is nested more than 128 levels deep.
+pub(crate) fn rewrite<F>(body: &str, mut visit: F) -> Option<String>
+where
+    F: FnMut(Option<&str>, &str, ValueKind) -> Action,
+{
+    let mut parser = Parser {
+        src: body,
+        pos: 0,
+        depth: 0,
+    };
+    let mut out = String::with_capacity(body.len());
+
+    parser.skip_whitespace(&mut out);
+    parser.value(None, &mut visit, &mut out)?;
+    parser.skip_whitespace(&mut out);
+
+    if parser.pos == body.len() {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    // the number of objects and arrays the current value is nested in
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self, out: &mut String) {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b' ' | b'\n' | b'\r' | b'\t')) {
+            self.pos += 1;
+        }
+        out.push_str(&self.src[start..self.pos]);
+    }
+
+    fn expect(&mut self, byte: u8, out: &mut String) -> Option<()> {
+        if self.peek()? != byte {
+            return None;
+        }
+        out.push(byte as char);
+        self.pos += 1;
+        Some(())
+    }
+
+    /// Returns the raw string token including the quotes
+    fn string(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        if self.peek()? != b'"' {
+            return None;
+        }
+        self.pos += 1;
+
+        loop {
+            match self.peek()? {
+                b'\\' => self.pos += 2,
+                b'"' => {
+                    self.pos += 1;
+                    return Some(&self.src[start..self.pos]);
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    /// Returns the raw scalar token (number, bool or null)
+    fn scalar(&mut self) -> Option<(&'a str, ValueKind)> {
+        let start = self.pos;
+        while let Some(byte) = self.peek() {
+            if matches!(byte, b',' | b'}' | b']' | b' ' | b'\n' | b'\r' | b'\t') {
+                break;
+            }
+            self.pos += 1;
+        }
+
+        let token = &self.src[start..self.pos];
+        let kind = match token {
+            "true" | "false" => ValueKind::Bool,
+            "null" => ValueKind::Null,
+            _ if token.parse::<f64>().is_ok() => ValueKind::Number,
+            _ => return None,
+        };
+
+        Some((token, kind))
+    }
+
+    /// Visits the value at the current position and writes the result to `out`,
+    /// returns `false` if the value should be removed
+    fn value<F>(&mut self, key: Option<&str>, visit: &mut F, out: &mut String) -> Option<bool>
+    where
+        F: FnMut(Option<&str>, &str, ValueKind) -> Action,
+    {
+        let start = self.pos;
+
+        let kind = match self.peek()? {
+            b'{' => ValueKind::Object,
+            b'[' => ValueKind::Array,
+            b'"' => {
+                self.string()?;
+                ValueKind::String
+            }
+            _ => self.scalar()?.1,
+        };
+
+        match kind {
+            ValueKind::Object | ValueKind::Array => {
+                if self.depth == MAX_DEPTH {
+                    return None;
+                }
+
+                // parse into a scratch buffer first so the visitor can see the raw value
+                let mut nested = String::new();
+                self.depth += 1;
+                if kind == ValueKind::Object {
+                    self.object(visit, &mut nested)?;
+                } else {
+                    self.array(key, visit, &mut nested)?;
+                }
+                self.depth -= 1;
+
+                match visit(key, &self.src[start..self.pos], kind) {
+                    Action::Keep => out.push_str(&nested),
+                    Action::Remove => return Some(false),
+                    Action::Replace(replacement) => out.push_str(&replacement),
+                }
+            }
+            _ => match visit(key, &self.src[start..self.pos], kind) {
+                Action::Keep => out.push_str(&self.src[start..self.pos]),
+                Action::Remove => return Some(false),
+                Action::Replace(replacement) => out.push_str(&replacement),
+            },
+        }
+
+        Some(true)
+    }
+
+    fn object<F>(&mut self, visit: &mut F, out: &mut String) -> Option<()>
+    where
+        F: FnMut(Option<&str>, &str, ValueKind) -> Action,
+    {
+        self.expect(b'{', out)?;
+
+        let mut members: Vec<String> = Vec::new();
+        let mut trailing = String::new();
+        let mut last_kept = true;
+
+        loop {
+            let mut member = String::new();
+            self.skip_whitespace(&mut member);
+
+            if self.peek()? == b'}' && members.is_empty() {
+                out.push_str(&member);
+                break;
+            }
+
+            let raw_key = self.string()?;
+            let key: String = serde_json::from_str(raw_key).ok()?;
+            member.push_str(raw_key);
+
+            self.skip_whitespace(&mut member);
+            self.expect(b':', &mut member)?;
+            self.skip_whitespace(&mut member);
+
+            let keep = self.value(Some(&key), visit, &mut member)?;
+
+            trailing.clear();
+            self.skip_whitespace(&mut trailing);
+
+            if keep {
+                member.push_str(&trailing);
+                members.push(member);
+            }
+            last_kept = keep;
+
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b'}' => break,
+                _ => return None,
+            }
+        }
+
+        out.push_str(&members.join(","));
+
+        // keep the formatting before the closing brace if the last member was removed
+        if !last_kept {
+            out.push_str(&trailing);
+        }
+
+        self.expect(b'}', out)
+    }
+
+    fn array<F>(&mut self, key: Option<&str>, visit: &mut F, out: &mut String) -> Option<()>
+    where
+        F: FnMut(Option<&str>, &str, ValueKind) -> Action,
+    {
+        self.expect(b'[', out)?;
+
+        let mut elements: Vec<String> = Vec::new();
+        let mut trailing = String::new();
+        let mut last_kept = true;
+
+        loop {
+            let mut element = String::new();
+            self.skip_whitespace(&mut element);
+
+            if self.peek()? == b']' && elements.is_empty() {
+                out.push_str(&element);
+                break;
+            }
+
+            let keep = self.value(key, visit, &mut element)?;
+
+            trailing.clear();
+            self.skip_whitespace(&mut trailing);
+
+            if keep {
+                element.push_str(&trailing);
+                elements.push(element);
+            }
+            last_kept = keep;
+
+            match self.peek()? {
+                b',' => self.pos += 1,
+                b']' => break,
+                _ => return None,
+            }
+        }
+
+        out.push_str(&elements.join(","));
+
+        if !last_kept {
+            out.push_str(&trailing);
+        }
+
+        self.expect(b']', out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        body: &'static str,
+        expected: Option<&'static str>,
+    }
+
+    #[test]
+    fn run() {
+        let tests = vec![
+            Test {
+                name: "removes member in the middle",
+                body: r#"{"a": 1, "blob": "abc", "b": 2}"#,
+                expected: Some(r#"{"a": 1, "b": 2}"#),
+            },
+            Test {
+                name: "removes last member and keeps formatting",
+                body: "{\n  \"a\": 1,\n  \"blob\": {\"x\": [1, 2]}\n}",
+                expected: Some("{\n  \"a\": 1\n}"),
+            },
+            Test {
+                name: "removes nested members and embedded documents",
+                body: r#"{"doc": {"blob": [1, {"blob": 2}], "keep": {"blob": "x"}}}"#,
+                expected: Some(r#"{"doc": { "keep": {}}}"#),
+            },
+            Test {
+                name: "removes only member",
+                body: r#"[{"blob": "x"}, {"a": "blob"}]"#,
+                expected: Some(r#"[{}, {"a": "blob"}]"#),
+            },
+            Test {
+                name: "returns none for invalid json",
+                body: r#"{"a": 1, "blob": "#,
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(
+                rewrite(test.body, |key, _, _| match key {
+                    Some("blob") => Action::Remove,
+                    _ => Action::Keep,
+                })
+                .as_deref(),
+                test.expected
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_deeply_nested_bodies() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+
+        assert_eq!(
+            rewrite(&nested(MAX_DEPTH), |_, _, _| Action::Keep),
+            Some(nested(MAX_DEPTH))
+        );
+        assert_eq!(
+            rewrite(&nested(MAX_DEPTH + 1), |_, _, _| Action::Keep),
+            None
+        );
+        // large enough to overflow the stack without the limit
+        assert_eq!(rewrite(&"[".repeat(200_000), |_, _, _| Action::Keep), None);
+    }
+}
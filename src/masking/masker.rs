@@ -0,0 +1,92 @@
+use std::{fmt, sync::Arc};
+
+use har::v1_2::{Request as HarRequest, Response as HarResponse};
+use http::{HeaderMap, StatusCode};
+
+/// Custom masking logic that runs after all of the other masks, for redaction rules that depend on the context of the request.
+///
+/// Maskers get the already masked HAR request and response and can rewrite their headers, cookies, query strings and bodies.
+/// The [MaskingContext] has the unmasked request headers and the data set on the controller to base decisions on.
+///
+/// Closures with the same signature as [Masker::mask] implement Masker.
+///
+/// # Examples
+/// ```rust
+/// use speakeasy_rust_sdk::{Masking, har::v1_2::{Request, Response}, masking::MaskingContext};
+///
+/// let mut masking = Masking::default();
+///
+/// // mask the amount in responses for enterprise customers
+/// masking.with_masker(|context: &MaskingContext, _request: &mut Request, response: &mut Response| {
+///     if context.customer_id.map_or(false, |id| id.starts_with("enterprise_")) {
+///         if let Some(text) = response.content.text.as_mut() {
+///             *text = text.replace("\"amount\"", "\"__masked_amount__\"");
+///         }
+///     }
+/// });
+/// ```
+pub trait Masker: Send + Sync {
+    fn mask(&self, context: &MaskingContext, request: &mut HarRequest, response: &mut HarResponse);
+}
+
+impl<F> Masker for F
+where
+    F: Fn(&MaskingContext, &mut HarRequest, &mut HarResponse) + Send + Sync,
+{
+    fn mask(&self, context: &MaskingContext, request: &mut HarRequest, response: &mut HarResponse) {
+        self(context, request, response)
+    }
+}
+
+/// Information about the request that isn't in the masked HAR, passed to every [Masker]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MaskingContext<'a> {
+    /// The normalized path hint of the request (e.g. `/users/{id}`)
+    pub path_hint: Option<&'a str>,
+    /// The customer id set on the controller
+    pub customer_id: Option<&'a str>,
+    /// The request method
+    pub method: &'a str,
+    /// The host the request was sent to
+    pub host: &'a str,
+    /// The unmasked request headers
+    pub request_headers: &'a HeaderMap,
+    /// The response status
+    pub status: StatusCode,
+}
+
+/// The registered maskers, they run in the order they were added
+#[derive(Clone, Default)]
+pub(crate) struct Maskers(Vec<Arc<dyn Masker>>);
+
+impl fmt::Debug for Maskers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Maskers").field(&self.0.len()).finish()
+    }
+}
+
+impl Maskers {
+    pub(crate) fn add(&mut self, masker: impl Masker + 'static) {
+        self.0.push(Arc::new(masker));
+    }
+
+    pub(crate) fn extend(&mut self, other: Maskers) {
+        self.0.extend(other.0);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn mask(
+        &self,
+        context: &MaskingContext,
+        request: &mut HarRequest,
+        response: &mut HarResponse,
+    ) {
+        for masker in &self.0 {
+            masker.mask(context, request, response);
+        }
+    }
+}
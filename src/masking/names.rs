@@ -0,0 +1,61 @@
+//! Checks for names that can never match a header, cookie, query string, path param or body field
+
+use http::HeaderName;
+
+use super::Error;
+
+pub(crate) fn is_valid_query_string(name: &str) -> bool {
+    !name.is_empty()
+}
+
+pub(crate) fn is_valid_path_param(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '{', '}'])
+}
+
+pub(crate) fn is_valid_header(name: &str) -> bool {
+    HeaderName::from_bytes(name.as_bytes()).is_ok()
+}
+
+pub(crate) fn is_valid_cookie(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || matches!(c, ';' | '=' | ','))
+}
+
+// field names are matched against the raw JSON keys, so a name with a quote can never match
+pub(crate) fn is_valid_field(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(|c| c == '"' || c.is_control())
+}
+
+/// Adds an error for every invalid name
+pub(crate) fn validate<'a>(
+    errors: &mut Vec<Error>,
+    location: &'static str,
+    names: impl IntoIterator<Item = &'a str>,
+    is_valid: fn(&str) -> bool,
+) {
+    for name in names {
+        if !is_valid(name) {
+            errors.push(Error::InvalidName {
+                location,
+                name: name.to_string(),
+            });
+        }
+    }
+}
+
+/// Errors on the first invalid name
+pub(crate) fn check<'a>(
+    location: &'static str,
+    names: impl IntoIterator<Item = &'a str>,
+    is_valid: fn(&str) -> bool,
+) -> Result<(), Error> {
+    match names.into_iter().find(|name| !is_valid(name)) {
+        Some(name) => Err(Error::InvalidName {
+            location,
+            name: name.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
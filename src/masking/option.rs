@@ -1,6 +1,21 @@
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap, fmt, sync::Arc};
 
-use super::{DEFAULT_NUMBER_MASK, DEFAULT_STRING_MASK};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use super::{AuthorizationMask, DEFAULT_NUMBER_MASK, DEFAULT_STRING_MASK};
+
+// used in the masking metadata in place of the value dependent masks
+const KEYED_HASH_METADATA: &str = "__hmac_sha256__";
+const KEEP_EMAIL_DOMAIN_METADATA: &str = "__keep_email_domain__";
+const PRESERVE_LENGTH_METADATA: &str = "__preserve_length__";
+const PRESERVE_FORMAT_METADATA: &str = "__preserve_format__";
+const AUTHORIZATION_METADATA: &str = "__authorization__";
+
+// characters used by the partial and format preserving masks
+const MASK_CHAR: char = '*';
+const MASK_DIGIT: char = '9';
 
 #[derive(Debug, Clone)]
 pub enum StringMaskingOption {
@@ -8,6 +23,21 @@ pub enum StringMaskingOption {
     SingleMask(String),
     MultipleMasks(Vec<String>),
     AssociatedMasks(HashMap<String, String>),
+    /// Replace the value with a truncated HMAC-SHA256 of the value, see [KeyedHash]
+    KeyedHash(KeyedHash),
+    /// Keep the last N characters of the value and mask the rest with `*` (e.g. `************1234`),
+    /// values of N characters or less are masked completely
+    KeepLast(usize),
+    /// Keep the domain of an email address and mask the local part with `*` (e.g. `****@example.com`),
+    /// values that are not an email address are masked completely
+    KeepEmailDomain,
+    /// Mask every character with `*`, keeping the length of the value
+    PreserveLength,
+    /// Keep the character classes of the value: digits become `9`, uppercase letters `X`,
+    /// lowercase letters `x` and everything else is kept as is (e.g. `AB12-cd` becomes `XX99-xx`)
+    PreserveFormat,
+    /// Mask the credentials of an `Authorization` header while keeping the scheme and identity, see [AuthorizationMask]
+    Authorization(AuthorizationMask),
 }
 
 impl Default for StringMaskingOption {
@@ -72,10 +102,51 @@ impl From<HashMap<&str, &str>> for StringMaskingOption {
     }
 }
 
+impl From<KeyedHash> for StringMaskingOption {
+    fn from(keyed_hash: KeyedHash) -> Self {
+        StringMaskingOption::KeyedHash(keyed_hash)
+    }
+}
+
+impl From<AuthorizationMask> for StringMaskingOption {
+    fn from(authorization_mask: AuthorizationMask) -> Self {
+        StringMaskingOption::Authorization(authorization_mask)
+    }
+}
+
 impl StringMaskingOption {
-    pub(crate) fn get_mask_replacement(&self, field: &str, index: usize) -> &str {
+    pub(crate) fn get_mask_replacement(
+        &self,
+        field: &str,
+        index: usize,
+        value: &str,
+    ) -> Cow<'_, str> {
+        match self {
+            Self::KeyedHash(keyed_hash) => Cow::Owned(keyed_hash.hash_string(value)),
+            Self::KeepLast(count) => Cow::Owned(keep_last(value, *count)),
+            Self::KeepEmailDomain => Cow::Owned(keep_email_domain(value)),
+            Self::PreserveLength => Cow::Owned(value.chars().map(|_| MASK_CHAR).collect()),
+            Self::PreserveFormat => Cow::Owned(value.chars().map(preserve_format).collect()),
+            Self::Authorization(authorization_mask) => Cow::Owned(authorization_mask.mask(value)),
+            _ => Cow::Borrowed(self.get_constant_replacement(field, index)),
+        }
+    }
+
+    /// Replacement reported in the masking metadata, value dependent masks are described instead
+    pub(crate) fn get_metadata_replacement(&self, field: &str, index: usize) -> Cow<'_, str> {
+        match self {
+            Self::KeyedHash(_) => Cow::Borrowed(KEYED_HASH_METADATA),
+            Self::KeepLast(count) => Cow::Owned(format!("__keep_last_{}__", count)),
+            Self::KeepEmailDomain => Cow::Borrowed(KEEP_EMAIL_DOMAIN_METADATA),
+            Self::PreserveLength => Cow::Borrowed(PRESERVE_LENGTH_METADATA),
+            Self::PreserveFormat => Cow::Borrowed(PRESERVE_FORMAT_METADATA),
+            Self::Authorization(_) => Cow::Borrowed(AUTHORIZATION_METADATA),
+            _ => Cow::Borrowed(self.get_constant_replacement(field, index)),
+        }
+    }
+
+    fn get_constant_replacement(&self, field: &str, index: usize) -> &str {
         match self {
-            Self::None => DEFAULT_STRING_MASK,
             Self::SingleMask(mask) => mask,
             Self::MultipleMasks(ref masks) => masks
                 .get(index)
@@ -85,6 +156,7 @@ impl StringMaskingOption {
                 .get(field)
                 .map(String::as_str)
                 .unwrap_or(DEFAULT_STRING_MASK),
+            _ => DEFAULT_STRING_MASK,
         }
     }
 }
@@ -92,9 +164,16 @@ impl StringMaskingOption {
 #[derive(Debug, Clone)]
 pub enum NumberMaskingOption {
     None,
-    SingleMask(i32),
-    MultipleMasks(Vec<i32>),
-    AssociatedMasks(HashMap<String, i32>),
+    SingleMask(NumberMask),
+    MultipleMasks(Vec<NumberMask>),
+    AssociatedMasks(HashMap<String, NumberMask>),
+    /// Replace the value with a non-negative number derived from the HMAC-SHA256 of the value, see [KeyedHash]
+    KeyedHash(KeyedHash),
+    /// Keep the last N digits of the number and replace the other digits with `9` (e.g. `9999999999991234`),
+    /// the exponent of a number in exponent notation is kept. Numbers of N digits or less have every digit replaced
+    KeepLast(usize),
+    /// Replace every digit with `9`, keeping the sign, decimal point, exponent and number of digits (e.g. `-123.45` becomes `-999.99`)
+    PreserveFormat,
 }
 
 impl Default for NumberMaskingOption {
@@ -103,10 +182,55 @@ impl Default for NumberMaskingOption {
     }
 }
 
+/// A replacement value for number fields, written to the body and the masking metadata as a JSON number.
+///
+/// Floats that aren't finite can't be written as a JSON number and are replaced with the default mask
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum NumberMask {
+    Int(i64),
+    Float(f64),
+}
+
+impl fmt::Display for NumberMask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(mask) => write!(f, "{}", mask),
+            Self::Float(mask) if mask.is_finite() => {
+                // keep floats that are whole numbers distinguishable from integers
+                if mask.fract() == 0.0 && mask.abs() < 1e16 {
+                    write!(f, "{:.1}", mask)
+                } else {
+                    write!(f, "{}", mask)
+                }
+            }
+            Self::Float(_) => write!(f, "{}", DEFAULT_NUMBER_MASK),
+        }
+    }
+}
+
+impl From<i32> for NumberMask {
+    fn from(mask: i32) -> Self {
+        NumberMask::Int(mask.into())
+    }
+}
+
+impl From<i64> for NumberMask {
+    fn from(mask: i64) -> Self {
+        NumberMask::Int(mask)
+    }
+}
+
+impl From<f64> for NumberMask {
+    fn from(mask: f64) -> Self {
+        NumberMask::Float(mask)
+    }
+}
+
 impl From<Option<i32>> for NumberMaskingOption {
     fn from(maybe_mask: Option<i32>) -> Self {
         match maybe_mask {
-            Some(mask) => NumberMaskingOption::SingleMask(mask),
+            Some(mask) => NumberMaskingOption::SingleMask(mask.into()),
             None => NumberMaskingOption::None,
         }
     }
@@ -114,47 +238,265 @@ impl From<Option<i32>> for NumberMaskingOption {
 
 impl From<i32> for NumberMaskingOption {
     fn from(mask: i32) -> Self {
+        NumberMaskingOption::SingleMask(mask.into())
+    }
+}
+
+impl From<i64> for NumberMaskingOption {
+    fn from(mask: i64) -> Self {
+        NumberMaskingOption::SingleMask(mask.into())
+    }
+}
+
+impl From<f64> for NumberMaskingOption {
+    fn from(mask: f64) -> Self {
+        NumberMaskingOption::SingleMask(mask.into())
+    }
+}
+
+impl From<NumberMask> for NumberMaskingOption {
+    fn from(mask: NumberMask) -> Self {
         NumberMaskingOption::SingleMask(mask)
     }
 }
 
-impl From<&[i32]> for NumberMaskingOption {
-    fn from(masks: &[i32]) -> Self {
-        NumberMaskingOption::MultipleMasks(masks.to_vec())
+impl<M: Into<NumberMask> + Copy> From<&[M]> for NumberMaskingOption {
+    fn from(masks: &[M]) -> Self {
+        NumberMaskingOption::MultipleMasks(masks.iter().map(|mask| (*mask).into()).collect())
     }
 }
 
-impl From<Vec<i32>> for NumberMaskingOption {
-    fn from(masks: Vec<i32>) -> Self {
-        NumberMaskingOption::MultipleMasks(masks)
+impl<M: Into<NumberMask>> From<Vec<M>> for NumberMaskingOption {
+    fn from(masks: Vec<M>) -> Self {
+        NumberMaskingOption::MultipleMasks(masks.into_iter().map(Into::into).collect())
     }
 }
 
-impl From<HashMap<String, i32>> for NumberMaskingOption {
-    fn from(masks: HashMap<String, i32>) -> Self {
-        NumberMaskingOption::AssociatedMasks(masks)
+impl<M: Into<NumberMask>> From<HashMap<String, M>> for NumberMaskingOption {
+    fn from(masks: HashMap<String, M>) -> Self {
+        NumberMaskingOption::AssociatedMasks(
+            masks.into_iter().map(|(k, v)| (k, v.into())).collect(),
+        )
     }
 }
 
-impl From<HashMap<&str, i32>> for NumberMaskingOption {
-    fn from(masks: HashMap<&str, i32>) -> Self {
+impl<M: Into<NumberMask>> From<HashMap<&str, M>> for NumberMaskingOption {
+    fn from(masks: HashMap<&str, M>) -> Self {
         NumberMaskingOption::AssociatedMasks(
-            masks.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+            masks
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.into()))
+                .collect(),
         )
     }
 }
 
+impl From<KeyedHash> for NumberMaskingOption {
+    fn from(keyed_hash: KeyedHash) -> Self {
+        NumberMaskingOption::KeyedHash(keyed_hash)
+    }
+}
+
 impl NumberMaskingOption {
-    pub(crate) fn get_mask_replacement(&self, field: &str, index: usize) -> i32 {
+    pub(crate) fn get_mask_replacement(&self, field: &str, index: usize, value: &str) -> String {
+        match self {
+            Self::KeyedHash(keyed_hash) => keyed_hash.hash_number(value).to_string(),
+            Self::KeepLast(count) => keep_last_digits(value, *count),
+            Self::PreserveFormat => keep_last_digits(value, 0),
+            _ => self.get_constant_replacement(field, index).to_string(),
+        }
+    }
+
+    /// Replacement reported in the masking metadata, value dependent masks are described instead
+    pub(crate) fn get_metadata_replacement(&self, field: &str, index: usize) -> String {
+        match self {
+            Self::KeyedHash(_) => KEYED_HASH_METADATA.to_string(),
+            Self::KeepLast(count) => format!("__keep_last_{}__", count),
+            Self::PreserveFormat => PRESERVE_FORMAT_METADATA.to_string(),
+            _ => self.get_constant_replacement(field, index).to_string(),
+        }
+    }
+
+    fn get_constant_replacement(&self, field: &str, index: usize) -> NumberMask {
+        let default_mask = NumberMask::from(DEFAULT_NUMBER_MASK);
+
         match self {
-            Self::None => DEFAULT_NUMBER_MASK,
+            Self::None => default_mask,
             Self::SingleMask(mask) => *mask,
-            Self::MultipleMasks(ref masks) => {
-                masks.get(index).copied().unwrap_or(DEFAULT_NUMBER_MASK)
-            }
+            Self::MultipleMasks(ref masks) => masks.get(index).copied().unwrap_or(default_mask),
             Self::AssociatedMasks(ref masks_map) => {
-                masks_map.get(field).copied().unwrap_or(DEFAULT_NUMBER_MASK)
+                masks_map.get(field).copied().unwrap_or(default_mask)
+            }
+            _ => default_mask,
+        }
+    }
+}
+
+/// Keeps the last `count` characters and masks the others with `*`,
+/// values of `count` characters or less are masked completely
+fn keep_last(value: &str, count: usize) -> String {
+    let masked_count = masked_count(value.chars().count(), count);
+
+    value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i < masked_count { MASK_CHAR } else { c })
+        .collect()
+}
+
+// keeping every character would capture short values (e.g. PINs) in clear
+fn masked_count(len: usize, count: usize) -> usize {
+    if len <= count {
+        len
+    } else {
+        len - count
+    }
+}
+
+fn keep_email_domain(value: &str) -> String {
+    match value.rsplit_once('@') {
+        Some((local, domain)) if !local.is_empty() && !domain.is_empty() => {
+            let masked_local: String = local.chars().map(|_| MASK_CHAR).collect();
+            format!("{}@{}", masked_local, domain)
+        }
+        _ => value.chars().map(|_| MASK_CHAR).collect(),
+    }
+}
+
+fn preserve_format(c: char) -> char {
+    if c.is_ascii_digit() {
+        MASK_DIGIT
+    } else if c.is_uppercase() {
+        'X'
+    } else if c.is_lowercase() {
+        'x'
+    } else {
+        c
+    }
+}
+
+/// Keeps the last `count` digits of a number, the other digits are replaced with `9`
+/// so the result is always a valid number. The exponent of a number in exponent notation is kept as is,
+/// numbers of `count` digits or less have every digit replaced
+fn keep_last_digits(value: &str, count: usize) -> String {
+    let (mantissa, exponent) = match value.find(['e', 'E']) {
+        Some(index) => value.split_at(index),
+        None => (value, ""),
+    };
+
+    let masked_count = masked_count(mantissa.chars().filter(char::is_ascii_digit).count(), count);
+
+    let mut seen = 0;
+    let mut masked: String = mantissa
+        .chars()
+        .map(|c| {
+            if !c.is_ascii_digit() {
+                return c;
+            }
+
+            seen += 1;
+            if seen <= masked_count {
+                MASK_DIGIT
+            } else {
+                c
             }
+        })
+        .collect();
+
+    masked.push_str(exponent);
+    masked
+}
+
+/// Deterministic masking using a truncated HMAC-SHA256 of the value under a secret key.
+///
+/// The same value always masks to the same token, so masked values can still be correlated
+/// across requests (e.g. "same customer across calls") without exposing the raw value.
+/// String values are replaced with the first `length` hex characters of the digest (defaults to `16`),
+/// number values are replaced with a non-negative `i64` taken from the start of the digest of the numeric value,
+/// so `1` and `1.0` are replaced with the same number.
+///
+/// # Examples
+/// ```rust
+/// use speakeasy_rust_sdk::{Masking, masking::KeyedHash};
+///
+/// let mut masking = Masking::default();
+/// masking.with_request_field_mask_string("email", KeyedHash::new("my secret key"));
+/// masking.with_request_header_mask("x-account-id", KeyedHash::new("my secret key").with_length(32));
+/// masking.with_response_field_mask_number("account_number", KeyedHash::new("my secret key"));
+/// ```
+#[derive(Clone)]
+pub struct KeyedHash {
+    key: Arc<Vec<u8>>,
+    length: usize,
+}
+
+impl KeyedHash {
+    const DEFAULT_LENGTH: usize = 16;
+
+    /// Create a new keyed hash mask using the given secret key
+    pub fn new(key: impl AsRef<[u8]>) -> Self {
+        Self {
+            key: Arc::new(key.as_ref().to_vec()),
+            length: Self::DEFAULT_LENGTH,
+        }
+    }
+
+    /// Set the number of hex characters kept from the digest for string values (max `64`)
+    pub fn with_length(mut self, length: usize) -> Self {
+        self.length = length;
+        self
+    }
+
+    fn digest(&self, value: &str) -> [u8; 32] {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC can take key of any size");
+        mac.update(value.as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+
+    pub(crate) fn hash_string(&self, value: &str) -> String {
+        let mut hex = String::with_capacity(64);
+
+        for byte in self.digest(value) {
+            hex.push_str(&format!("{:02x}", byte));
         }
+
+        hex.truncate(self.length);
+        hex
+    }
+
+    /// Hashes the numeric value, so `1`, `1.0` and `1e0` mask to the same number
+    pub(crate) fn hash_number(&self, value: &str) -> i64 {
+        let digest = self.digest(&normalize_number(value));
+        let number = u64::from_be_bytes(digest[..8].try_into().expect("digest has 32 bytes"));
+
+        // drop the top bit so the masked value is never negative
+        (number >> 1) as i64
+    }
+}
+
+/// The canonical text of a number, whole numbers are written as integers.
+/// Values that aren't numbers (e.g. form fields) are kept as is
+fn normalize_number(value: &str) -> Cow<'_, str> {
+    if let Ok(number) = value.parse::<i128>() {
+        return Cow::Owned(number.to_string());
+    }
+
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() && number.fract() == 0.0 && number.abs() < 1e18 => {
+            Cow::Owned((number as i64).to_string())
+        }
+        Ok(number) if number.is_finite() => Cow::Owned(number.to_string()),
+        _ => Cow::Borrowed(value),
+    }
+}
+
+// never print the key
+impl fmt::Debug for KeyedHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyedHash")
+            .field("key", &"**********")
+            .field("length", &self.length)
+            .finish()
     }
 }
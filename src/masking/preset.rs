@@ -0,0 +1,245 @@
+use super::{pii::PiiKind, Fields, Masking};
+
+const RECOMMENDED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-auth-token",
+    "x-access-token",
+    "x-refresh-token",
+    "x-csrf-token",
+    "x-xsrf-token",
+];
+
+const RECOMMENDED_COOKIES: &[&str] = &[
+    "session",
+    "sessionid",
+    "session_id",
+    "sid",
+    "connect.sid",
+    "JSESSIONID",
+    "PHPSESSID",
+    "ASP.NET_SessionId",
+    "csrftoken",
+    "XSRF-TOKEN",
+];
+
+const RECOMMENDED_FIELDS: &[&str] = &[
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "api_key",
+    "apikey",
+    "client_secret",
+    "access_token",
+    "refresh_token",
+    "id_token",
+    "private_key",
+];
+
+const RECOMMENDED_NUMBER_FIELDS: &[&str] = &["pin", "cvv", "cvc"];
+
+const STRICT_HEADERS: &[&str] = &[
+    "x-amz-security-token",
+    "x-goog-api-key",
+    "x-client-secret",
+    "x-session-token",
+    "www-authenticate",
+];
+
+const STRICT_FIELDS: &[&str] = &[
+    "passphrase",
+    "credentials",
+    "code",
+    "code_verifier",
+    "assertion",
+    "signature",
+    "session",
+    "ssn",
+    "card_number",
+    "account_number",
+    "iban",
+];
+
+const STRICT_NUMBER_FIELDS: &[&str] = &["card_number", "account_number", "ssn"];
+
+/// A list of commonly sensitive headers, cookies, query strings and body fields that can be extended
+/// or have entries opted out of before being turned into [Masking].
+///
+/// Everything in the preset is masked with the default masks (`"__masked__"` or `-12321`),
+/// use [Masking::recommended] or [Masking::strict] if there is nothing to change.
+///
+/// # Examples
+/// ```rust
+/// use speakeasy_rust_sdk::{Masking, masking::Preset};
+///
+/// let masking: Masking = Preset::recommended()
+///     .with_headers("x-internal-token")
+///     .with_fields(vec!["ssn", "date_of_birth"])
+///     .without("set-cookie")
+///     .into();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Preset {
+    headers: Vec<String>,
+    cookies: Vec<String>,
+    query_strings: Vec<String>,
+    fields: Vec<String>,
+    number_fields: Vec<String>,
+    pii_detection: bool,
+}
+
+impl Preset {
+    /// Masks the credential headers (`authorization`, `cookie`, `set-cookie`, `x-api-key`, ...), session cookies
+    /// and the secret, token and key query strings and body fields (`password`, `client_secret`, `access_token`, ...)
+    pub fn recommended() -> Self {
+        Self::default()
+            .with_headers(RECOMMENDED_HEADERS)
+            .with_cookies(RECOMMENDED_COOKIES)
+            .with_query_strings(RECOMMENDED_FIELDS)
+            .with_fields(RECOMMENDED_FIELDS)
+            .with_number_fields(RECOMMENDED_NUMBER_FIELDS)
+    }
+
+    /// Everything in [recommended](Preset::recommended), more headers and fields (`code`, `signature`, `ssn`, `iban`, ...),
+    /// and all of the built in [PII detectors](PiiKind)
+    pub fn strict() -> Self {
+        Self {
+            pii_detection: true,
+            ..Self::recommended()
+                .with_headers(STRICT_HEADERS)
+                .with_query_strings(STRICT_FIELDS)
+                .with_fields(STRICT_FIELDS)
+                .with_number_fields(STRICT_NUMBER_FIELDS)
+        }
+    }
+
+    /// Adds request and response headers to mask, header names are case insensitive
+    pub fn with_headers(mut self, fields: impl Into<Fields>) -> Self {
+        let fields = Vec::from(fields.into())
+            .into_iter()
+            .map(|field| field.to_lowercase())
+            .collect::<Vec<String>>();
+
+        extend(&mut self.headers, fields);
+        self
+    }
+
+    /// Adds request and response cookies to mask
+    pub fn with_cookies(mut self, fields: impl Into<Fields>) -> Self {
+        extend(&mut self.cookies, fields);
+        self
+    }
+
+    /// Adds query strings to mask
+    pub fn with_query_strings(mut self, fields: impl Into<Fields>) -> Self {
+        extend(&mut self.query_strings, fields);
+        self
+    }
+
+    /// Adds request and response string body fields to mask
+    pub fn with_fields(mut self, fields: impl Into<Fields>) -> Self {
+        extend(&mut self.fields, fields);
+        self
+    }
+
+    /// Adds request and response number body fields to mask
+    pub fn with_number_fields(mut self, fields: impl Into<Fields>) -> Self {
+        extend(&mut self.number_fields, fields);
+        self
+    }
+
+    /// Opts out of masking the given entries, wherever they are in the preset
+    pub fn without(mut self, fields: impl Into<Fields>) -> Self {
+        let fields = Vec::from(fields.into());
+
+        for list in [
+            &mut self.headers,
+            &mut self.cookies,
+            &mut self.query_strings,
+            &mut self.fields,
+            &mut self.number_fields,
+        ] {
+            list.retain(|field| !fields.iter().any(|f| f.eq_ignore_ascii_case(field)));
+        }
+
+        self
+    }
+}
+
+fn extend(list: &mut Vec<String>, fields: impl Into<Fields>) {
+    for field in Vec::from(fields.into()) {
+        if !list.contains(&field) {
+            list.push(field);
+        }
+    }
+}
+
+impl From<Preset> for Masking {
+    fn from(preset: Preset) -> Self {
+        let mut masking = Masking::default();
+
+        if !preset.headers.is_empty() {
+            masking.with_request_header_mask(preset.headers.clone(), None);
+            masking.with_response_header_mask(preset.headers, None);
+        }
+
+        if !preset.cookies.is_empty() {
+            masking.with_request_cookie_mask(preset.cookies.clone(), None);
+            masking.with_response_cookie_mask(preset.cookies, None);
+        }
+
+        if !preset.query_strings.is_empty() {
+            masking.with_query_string_mask(preset.query_strings, None);
+        }
+
+        if !preset.fields.is_empty() {
+            masking.with_request_field_mask_string(preset.fields.clone(), None);
+            masking.with_response_field_mask_string(preset.fields, None);
+        }
+
+        if !preset.number_fields.is_empty() {
+            masking.with_request_field_mask_number(preset.number_fields.clone(), None);
+            masking.with_response_field_mask_number(preset.number_fields, None);
+        }
+
+        if preset.pii_detection {
+            for kind in PiiKind::ALL {
+                masking.with_pii_detector(kind);
+            }
+        }
+
+        masking
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speakeasy_protos::ingest::ingest_request::MaskingMetadata;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn extends_and_opts_out_of_entries() {
+        let masking: Masking = Preset::recommended()
+            .with_headers("X-Internal-Token")
+            .without(vec!["Set-Cookie", "password"])
+            .into();
+
+        let metadata = MaskingMetadata::from(masking);
+
+        assert!(metadata
+            .request_header_masks
+            .contains_key("x-internal-token"));
+        assert!(metadata.request_header_masks.contains_key("authorization"));
+        assert!(!metadata.response_header_masks.contains_key("set-cookie"));
+        assert!(!metadata.request_field_masks_string.contains_key("password"));
+        assert_eq!(
+            metadata.request_field_masks_string.get("client_secret"),
+            Some(&"__masked__".to_string())
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+};
+
+use super::{
+    json::{self, Action},
+    Fields, DEFAULT_STRING_MASK, REMOVED_MASK,
+};
+
+/// Holds the names of headers, cookies or query strings that are dropped from the HAR entirely
+#[derive(Debug, Clone)]
+pub(crate) struct GenericRemoval<T> {
+    phantom: PhantomData<T>,
+    fields: HashSet<String>,
+}
+
+impl<T> Default for GenericRemoval<T> {
+    fn default() -> Self {
+        Self {
+            phantom: PhantomData,
+            fields: HashSet::new(),
+        }
+    }
+}
+
+impl<T> GenericRemoval<T> {
+    pub(crate) fn new(fields: Fields) -> Self {
+        Self {
+            phantom: PhantomData,
+            fields: Vec::from(fields).into_iter().collect(),
+        }
+    }
+
+    pub(crate) fn is_removed(&self, field: &str) -> bool {
+        self.fields.contains(field)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Adds the removals of `other`
+    pub(crate) fn merge(&mut self, other: GenericRemoval<T>) {
+        self.fields.extend(other.fields);
+    }
+
+    /// Stops removing the given names
+    pub(crate) fn keep<'a>(&mut self, fields: impl IntoIterator<Item = &'a str>) {
+        for field in fields {
+            self.fields.remove(field);
+        }
+    }
+
+    /// The removed names, sorted
+    pub(crate) fn fields(&self) -> Vec<&str> {
+        sorted(&self.fields)
+    }
+}
+
+impl<T> From<GenericRemoval<T>> for HashMap<String, String> {
+    fn from(removal: GenericRemoval<T>) -> Self {
+        removal
+            .fields
+            .into_iter()
+            .map(|field| (field, REMOVED_MASK.to_string()))
+            .collect()
+    }
+}
+
+/// Holds the JSON body fields that are removed from the captured body, at any depth
+#[derive(Debug, Clone)]
+pub(crate) struct BodyRemoval<T> {
+    phantom: PhantomData<T>,
+    fields: HashSet<String>,
+}
+
+impl<T> Default for BodyRemoval<T> {
+    fn default() -> Self {
+        Self {
+            phantom: PhantomData,
+            fields: HashSet::new(),
+        }
+    }
+}
+
+impl<T> BodyRemoval<T> {
+    pub(crate) fn new(fields: Fields) -> Self {
+        Self {
+            phantom: PhantomData,
+            fields: Vec::from(fields).into_iter().collect(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub(crate) fn is_removed(&self, field: &str) -> bool {
+        self.fields.contains(field)
+    }
+
+    /// Adds the removals of `other`
+    pub(crate) fn merge(&mut self, other: BodyRemoval<T>) {
+        self.fields.extend(other.fields);
+    }
+
+    /// Stops removing the given names
+    pub(crate) fn keep<'a>(&mut self, fields: impl IntoIterator<Item = &'a str>) {
+        for field in fields {
+            self.fields.remove(field);
+        }
+    }
+
+    /// The removed fields, sorted
+    pub(crate) fn fields(&self) -> Vec<&str> {
+        sorted(&self.fields)
+    }
+
+    /// Removes the configured fields from a JSON body,
+    /// bodies that are not valid JSON are masked completely as the removed fields can't be found in them
+    pub(crate) fn remove(&self, body: String) -> String {
+        if self.fields.is_empty() {
+            return body;
+        }
+
+        json::rewrite(&body, |key, _, _| match key {
+            Some(key) if self.fields.contains(key) => Action::Remove,
+            _ => Action::Keep,
+        })
+        .unwrap_or_else(|| DEFAULT_STRING_MASK.to_string())
+    }
+}
+
+impl<T> From<BodyRemoval<T>> for HashMap<String, String> {
+    fn from(removal: BodyRemoval<T>) -> Self {
+        removal
+            .fields
+            .into_iter()
+            .map(|field| (field, REMOVED_MASK.to_string()))
+            .collect()
+    }
+}
+
+fn sorted(fields: &HashSet<String>) -> Vec<&str> {
+    let mut fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+    fields.sort_unstable();
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::masking::body_mask::RequestMask;
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        fields: Vec<&'static str>,
+        body: &'static str,
+        expected: &'static str,
+    }
+
+    #[test]
+    fn removes_body_fields() {
+        let tests = vec![
+            Test {
+                name: "removes fields at any depth",
+                fields: vec!["secret"],
+                body: r#"{"secret": 1, "user": {"name": "jane", "secret": {"a": [1, 2]}}}"#,
+                expected: r#"{ "user": {"name": "jane"}}"#,
+            },
+            Test {
+                name: "removes fields of objects in arrays",
+                fields: vec!["blob"],
+                body: r#"[{"id": 1, "blob": "YWJj"}, {"blob": null}]"#,
+                expected: r#"[{"id": 1}, {}]"#,
+            },
+            Test {
+                name: "keeps bodies without the fields",
+                fields: vec!["secret"],
+                body: r#"{"name": "jane"}"#,
+                expected: r#"{"name": "jane"}"#,
+            },
+            Test {
+                name: "masks invalid bodies",
+                fields: vec!["secret"],
+                body: r#"{"secret": "abc""#,
+                expected: DEFAULT_STRING_MASK,
+            },
+            Test {
+                name: "keeps invalid bodies without removals",
+                fields: vec![],
+                body: r#"{"secret": "abc""#,
+                expected: r#"{"secret": "abc""#,
+            },
+        ];
+
+        for test in tests {
+            let removal = BodyRemoval::<RequestMask>::new(test.fields.into());
+
+            assert_eq!(removal.remove(test.body.to_string()), test.expected);
+        }
+    }
+
+    #[test]
+    fn merges_and_keeps_removals() {
+        let mut removal = GenericRemoval::<RequestMask>::new(vec!["a", "b"].into());
+        removal.merge(GenericRemoval::new(vec!["c"].into()));
+        removal.keep(["b"]);
+
+        assert_eq!(removal.fields(), vec!["a", "c"]);
+        assert!(removal.is_removed("a"));
+        assert!(!removal.is_removed("b"));
+        assert_eq!(
+            HashMap::from(removal),
+            HashMap::from([
+                ("a".to_string(), REMOVED_MASK.to_string()),
+                ("c".to_string(), REMOVED_MASK.to_string()),
+            ])
+        );
+    }
+}
@@ -0,0 +1,419 @@
+//! Report of what the masking of a capture matched, to debug masking configuration
+
+use std::{fmt, sync::Arc};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{allowlist::Decision, pii::PiiDetection, Location, Masking, MaskingContext};
+
+/// What matched a value in a [MaskingReport]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchedRule {
+    /// A string mask
+    String,
+    /// A number mask
+    Number,
+    /// A removal
+    Removal,
+    /// The allowlist masked or removed a value that isn't allowed
+    Allowlist,
+    /// A PII detector, with the name of the detector
+    Pii(String),
+}
+
+/// A rule that matched in a capture, see [MaskingReport]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct ReportEntry {
+    pub location: Location,
+    /// The header, cookie, query string or path param name,
+    /// or the path of the body field (e.g. `$.cards[0].number`)
+    pub path: String,
+    pub rule: MatchedRule,
+    /// The number of values masked or removed at the path, or of PII occurrences redacted
+    pub count: usize,
+}
+
+/// Which rules matched while masking a capture, where and how many times.
+///
+/// The report is recorded while the capture is masked, after the bodies over the capture size or capture level are dropped,
+/// use [Masking::with_report_hook] to get it for every capture
+/// or [Settings::har_masking_report](crate::settings::Settings::har_masking_report) to add it to the captured HAR.
+/// XML bodies are only reported for PII and custom [Maskers](super::Masker) aren't reported
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct MaskingReport {
+    entries: Vec<ReportEntry>,
+}
+
+impl MaskingReport {
+    /// The matched rules in the order they were found
+    pub fn entries(&self) -> &[ReportEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Reports the masks, removals, allowlist decisions and PII of a JSON body by the path of its values,
+    /// bodies that aren't valid JSON are only reported for PII
+    pub(crate) fn json_body(&mut self, masking: &Masking, location: Location, body: &str) {
+        match serde_json::from_str::<Value>(body) {
+            Ok(value) => JsonReport {
+                masking,
+                report: self,
+                location,
+            }
+            .json(None, "$".to_string(), &value),
+            Err(_) => self.pii(&masking.pii_detection, location, "$", body),
+        }
+    }
+
+    /// Reports the PII detectors that match a value
+    pub(crate) fn pii(
+        &mut self,
+        pii_detection: &PiiDetection,
+        location: Location,
+        path: &str,
+        value: &str,
+    ) {
+        pii_detection.count(value, |detector, count| {
+            self.record(
+                location,
+                path,
+                MatchedRule::Pii(detector.to_string()),
+                count,
+            )
+        });
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        location: Location,
+        path: &str,
+        rule: MatchedRule,
+        count: usize,
+    ) {
+        let existing = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.location == location && entry.path == path && entry.rule == rule);
+
+        match existing {
+            Some(entry) => entry.count += count,
+            None => self.entries.push(ReportEntry {
+                location,
+                path: path.to_string(),
+                rule,
+                count,
+            }),
+        }
+    }
+}
+
+type ReportHook = Arc<dyn Fn(&MaskingContext, &MaskingReport) + Send + Sync>;
+
+/// The registered report hooks, they are called in the order they were added
+#[derive(Clone, Default)]
+pub(crate) struct ReportHooks(Vec<ReportHook>);
+
+impl fmt::Debug for ReportHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ReportHooks").field(&self.0.len()).finish()
+    }
+}
+
+impl ReportHooks {
+    pub(crate) fn add(
+        &mut self,
+        hook: impl Fn(&MaskingContext, &MaskingReport) + Send + Sync + 'static,
+    ) {
+        self.0.push(Arc::new(hook));
+    }
+
+    pub(crate) fn extend(&mut self, other: ReportHooks) {
+        self.0.extend(other.0);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn call(&self, context: &MaskingContext, report: &MaskingReport) {
+        for hook in &self.0 {
+            hook(context, report);
+        }
+    }
+}
+
+struct JsonReport<'a> {
+    masking: &'a Masking,
+    report: &'a mut MaskingReport,
+    location: Location,
+}
+
+impl JsonReport<'_> {
+    /// Reports a JSON value, `key` is the key of the enclosing member as the body masks see it
+    fn json(&mut self, key: Option<&str>, path: String, value: &Value) {
+        if let Some(key) = key {
+            if let Some(rule) = self.json_rule(key, value) {
+                // removed objects and arrays are dropped with everything in them
+                self.report.record(self.location, &path, rule, 1);
+                return;
+            }
+        }
+
+        match value {
+            Value::Object(members) => {
+                for (key, value) in members {
+                    self.json(Some(key), member_path(&path, key), value);
+                }
+            }
+            Value::Array(elements) => {
+                for (index, element) in elements.iter().enumerate() {
+                    self.json(key, format!("{}[{}]", path, index), element);
+                }
+            }
+            Value::String(value) => {
+                self.report
+                    .pii(&self.masking.pii_detection, self.location, &path, value)
+            }
+            Value::Number(number) => self.report.pii(
+                &self.masking.pii_detection,
+                self.location,
+                &path,
+                &number.to_string(),
+            ),
+            _ => {}
+        }
+    }
+
+    fn json_rule(&self, key: &str, value: &Value) -> Option<MatchedRule> {
+        let masking = self.masking;
+
+        let (is_removed, is_string_masked, is_number_masked, masks_numeric_strings) =
+            match self.location {
+                Location::RequestField => (
+                    masking.request_field_removal.is_removed(key),
+                    masking.request_masks.is_string_masked(key),
+                    masking.request_masks.is_number_masked(key),
+                    masking.request_masks.masks_numeric_strings(),
+                ),
+                _ => (
+                    masking.response_field_removal.is_removed(key),
+                    masking.response_masks.is_string_masked(key),
+                    masking.response_masks.is_number_masked(key),
+                    masking.response_masks.masks_numeric_strings(),
+                ),
+            };
+
+        if is_removed {
+            return Some(MatchedRule::Removal);
+        }
+
+        let is_allowed = match &masking.allowlist {
+            Some(allowlist) => allowlist.decide(self.location, key) == Decision::Allow,
+            None => true,
+        };
+
+        match value {
+            Value::String(_) | Value::Number(_) if !is_allowed => Some(MatchedRule::Allowlist),
+            Value::String(_) if is_string_masked => Some(MatchedRule::String),
+            Value::Number(_) if is_number_masked => Some(MatchedRule::Number),
+            Value::String(value)
+                if is_number_masked
+                    && masks_numeric_strings
+                    && serde_json::from_str::<serde_json::Number>(value).is_ok() =>
+            {
+                Some(MatchedRule::Number)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn member_path(path: &str, key: &str) -> String {
+    let is_identifier = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+    if is_identifier {
+        format!("{}.{}", path, key)
+    } else {
+        format!(
+            "{}[{}]",
+            path,
+            serde_json::to_string(key).expect("string serializes")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        controller::MAX_SIZE,
+        har_builder::{
+            tests::{request, response},
+            HarBuilder,
+        },
+        masking::PiiKind,
+    };
+    use http::{header, HeaderValue};
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        content_type: &'static str,
+        body: &'static [u8],
+        max_capture_size: usize,
+        location: Option<&'static str>,
+        expected: Vec<(Location, &'static str, MatchedRule, usize)>,
+    }
+
+    #[test]
+    fn reports_matched_rules() {
+        let mut masking = Masking::default();
+        masking.with_query_string_mask("token", None);
+        masking.with_request_header_removal("authorization");
+        masking.with_request_field_mask_string("password", None);
+        masking.with_request_field_mask_number("pin", None);
+        masking.with_request_field_removal("secrets");
+        masking.with_pii_detector(PiiKind::Email);
+
+        let tests = vec![
+            Test {
+                name: "reports json body paths",
+                content_type: "application/json",
+                body: br#"{"secrets":{"key":"c"},"users":[{"password":"a","pin":1},{"email":"jane@example.com, joe@example.com","password":"b"}]}"#,
+                max_capture_size: MAX_SIZE,
+                location: None,
+                expected: vec![
+                    (
+                        Location::RequestHeader,
+                        "authorization",
+                        MatchedRule::Removal,
+                        1,
+                    ),
+                    (Location::QueryString, "token", MatchedRule::String, 1),
+                    (Location::RequestField, "$.secrets", MatchedRule::Removal, 1),
+                    (
+                        Location::RequestField,
+                        "$.users[0].password",
+                        MatchedRule::String,
+                        1,
+                    ),
+                    (
+                        Location::RequestField,
+                        "$.users[0].pin",
+                        MatchedRule::Number,
+                        1,
+                    ),
+                    (
+                        Location::RequestField,
+                        "$.users[1].email",
+                        MatchedRule::Pii("email".to_string()),
+                        2,
+                    ),
+                    (
+                        Location::RequestField,
+                        "$.users[1].password",
+                        MatchedRule::String,
+                        1,
+                    ),
+                ],
+            },
+            Test {
+                name: "reports form fields by name",
+                content_type: "application/x-www-form-urlencoded",
+                body: b"password=a&pin=1234&pin=none&secrets=c",
+                max_capture_size: MAX_SIZE,
+                location: None,
+                expected: vec![
+                    (
+                        Location::RequestHeader,
+                        "authorization",
+                        MatchedRule::Removal,
+                        1,
+                    ),
+                    (Location::QueryString, "token", MatchedRule::String, 1),
+                    (Location::RequestField, "password", MatchedRule::String, 1),
+                    (Location::RequestField, "pin", MatchedRule::Number, 1),
+                    (Location::RequestField, "secrets", MatchedRule::Removal, 1),
+                ],
+            },
+            Test {
+                name: "reports url headers and the redirect url",
+                content_type: "text/plain",
+                body: b"",
+                max_capture_size: MAX_SIZE,
+                location: Some("/next?token=abc"),
+                expected: vec![
+                    (
+                        Location::RequestHeader,
+                        "authorization",
+                        MatchedRule::Removal,
+                        1,
+                    ),
+                    (Location::QueryString, "token", MatchedRule::String, 3),
+                ],
+            },
+            Test {
+                name: "doesn't report bodies dropped for their size",
+                content_type: "application/json",
+                body: br#"{"password":"a","email":"jane@example.com"}"#,
+                max_capture_size: 10,
+                location: None,
+                expected: vec![
+                    (
+                        Location::RequestHeader,
+                        "authorization",
+                        MatchedRule::Removal,
+                        1,
+                    ),
+                    (Location::QueryString, "token", MatchedRule::String, 1),
+                ],
+            },
+        ];
+
+        for test in tests {
+            let mut request = request(test.content_type, test.body);
+            request.full_url = Some("https://example.com/login?token=abc".parse().unwrap());
+            request.headers.insert(
+                header::AUTHORIZATION,
+                HeaderValue::from_static("Bearer abc"),
+            );
+
+            let mut response = response("text/plain", b"");
+            if let Some(location) = test.location {
+                response
+                    .headers
+                    .insert(header::LOCATION, HeaderValue::from_static(location));
+            }
+
+            let (_, _, report) = HarBuilder::new(request, response, test.max_capture_size)
+                .with_har_masking_report(true)
+                .build(&masking);
+
+            assert_eq!(
+                report
+                    .unwrap()
+                    .entries()
+                    .iter()
+                    .map(|entry| (
+                        entry.location,
+                        entry.path.as_str(),
+                        entry.rule.clone(),
+                        entry.count
+                    ))
+                    .collect::<Vec<_>>(),
+                test.expected
+            );
+        }
+    }
+}
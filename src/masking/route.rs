@@ -0,0 +1,159 @@
+//! Route patterns for masks that only apply to some requests
+
+use http::Method;
+
+use crate::path_hint;
+
+/// A `[METHOD ]/path` pattern matched against the method and normalized path hint of a request,
+/// e.g. `POST /auth/*` or `/users/{id}`.
+/// A `*` segment matches any single segment, a trailing `*` segment matches the rest of the path
+#[derive(Debug, Clone)]
+pub(crate) struct RoutePattern {
+    route: String,
+    method: Option<Method>,
+    segments: Vec<String>,
+}
+
+impl RoutePattern {
+    /// Returns `None` if the method is invalid or the path doesn't start with `/`
+    pub(crate) fn parse(route: &str) -> Option<Self> {
+        let route = route.trim();
+
+        let (method, path) = match route.split_once(char::is_whitespace) {
+            Some((method, path)) => (
+                Some(Method::from_bytes(method.to_uppercase().as_bytes()).ok()?),
+                path.trim(),
+            ),
+            None => (None, route),
+        };
+
+        let segments = path
+            .strip_prefix('/')?
+            .split('/')
+            .map(|segment| match segment {
+                "*" => segment.to_string(),
+                // path params are matched in the same format as the normalized path hint
+                segment => path_hint::normalize(segment),
+            })
+            .collect();
+
+        Some(Self {
+            route: route.to_string(),
+            method,
+            segments,
+        })
+    }
+
+    /// The route as it was registered
+    pub(crate) fn as_str(&self) -> &str {
+        &self.route
+    }
+
+    /// Checks if both patterns match the same requests
+    pub(crate) fn is_same(&self, other: &RoutePattern) -> bool {
+        self.method == other.method && self.segments == other.segments
+    }
+
+    pub(crate) fn matches(&self, method: &str, path_hint: &str) -> bool {
+        if let Some(route_method) = &self.method {
+            if !route_method.as_str().eq_ignore_ascii_case(method) {
+                return false;
+            }
+        }
+
+        let segments: Vec<&str> = match path_hint.strip_prefix('/') {
+            Some(path) => path.split('/').collect(),
+            None => return false,
+        };
+
+        let matches_segments = |count: usize| {
+            self.segments
+                .iter()
+                .zip(&segments)
+                .take(count)
+                .all(|(pattern, segment)| pattern == "*" || pattern == segment)
+        };
+
+        match self.segments.last().map(String::as_str) {
+            Some("*") => {
+                segments.len() >= self.segments.len() && matches_segments(self.segments.len() - 1)
+            }
+            _ => segments.len() == self.segments.len() && matches_segments(segments.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        route: &'static str,
+        method: &'static str,
+        path_hint: &'static str,
+        expected: bool,
+    }
+
+    #[test]
+    fn matches_routes() {
+        let tests = vec![
+            Test {
+                name: "matches exact path hint",
+                route: "/users/:id",
+                method: "GET",
+                path_hint: "/users/{id}",
+                expected: true,
+            },
+            Test {
+                name: "matches method case insensitively",
+                route: "post /auth/login",
+                method: "POST",
+                path_hint: "/auth/login",
+                expected: true,
+            },
+            Test {
+                name: "doesn't match other methods",
+                route: "POST /auth/login",
+                method: "GET",
+                path_hint: "/auth/login",
+                expected: false,
+            },
+            Test {
+                name: "trailing wildcard matches the rest of the path",
+                route: "POST /auth/*",
+                method: "POST",
+                path_hint: "/auth/password/reset",
+                expected: true,
+            },
+            Test {
+                name: "trailing wildcard needs a segment",
+                route: "/auth/*",
+                method: "POST",
+                path_hint: "/auth",
+                expected: false,
+            },
+            Test {
+                name: "wildcard matches a single segment",
+                route: "/*/{id}",
+                method: "GET",
+                path_hint: "/orders/{id}/items",
+                expected: false,
+            },
+            Test {
+                name: "doesn't match without path hint",
+                route: "/*",
+                method: "GET",
+                path_hint: "",
+                expected: false,
+            },
+        ];
+
+        for test in tests {
+            let route = RoutePattern::parse(test.route).unwrap();
+            assert_eq!(route.matches(test.method, test.path_hint), test.expected);
+        }
+    }
+}
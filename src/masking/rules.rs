@@ -0,0 +1,51 @@
+//! Read-only view of the configured masks and removals, e.g. to audit a masking configuration in tests
+
+use serde::Serialize;
+
+/// Where in the capture a value is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Location {
+    QueryString,
+    PathParam,
+    RequestHeader,
+    ResponseHeader,
+    RequestCookie,
+    ResponseCookie,
+    RequestField,
+    ResponseField,
+}
+
+/// How the value of a [MaskingRule] is masked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+    /// Masked with a [StringMaskingOption](super::StringMaskingOption)
+    String,
+    /// Masked with a [NumberMaskingOption](super::NumberMaskingOption)
+    Number,
+    /// Dropped from the capture
+    Removal,
+}
+
+/// A configured mask or removal, see [Masking::rules](super::Masking::rules)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MaskingRule {
+    pub location: Location,
+    /// The header, cookie, query string, path param or body field name
+    pub name: String,
+    pub kind: RuleKind,
+    /// The mask as reported in the masking metadata, e.g. `__masked__`, `-12321`, `__keep_last_4__` or `__removed__`
+    pub mask: String,
+}
+
+impl MaskingRule {
+    pub(crate) fn new(location: Location, name: &str, kind: RuleKind, mask: &str) -> Self {
+        Self {
+            location,
+            name: name.to_string(),
+            kind,
+            mask: mask.to_string(),
+        }
+    }
+}
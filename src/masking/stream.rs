@@ -0,0 +1,713 @@
+//! Masks a JSON response body chunk by chunk as it is sent, instead of making copies of the whole body while masking it
+
+use super::{
+    body_mask::{BodyMask, ResponseMask},
+    pii::PiiDetection,
+    removal::BodyRemoval,
+    Masking,
+};
+
+/// Where the next token is in the object being masked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Member {
+    Key,
+    Colon,
+    Value,
+    Next,
+}
+
+#[derive(Debug)]
+enum Container {
+    Object {
+        member: Member,
+        // the raw key of the current member, as the body masks match it
+        key: String,
+        // whitespace, key and colon of the current member, written once the value is known
+        prefix: Vec<u8>,
+        // whitespace between the colon and the value
+        value_whitespace: Vec<u8>,
+        // whitespace after the value, dropped with the separator of a removed member
+        trailing: Vec<u8>,
+        kept: usize,
+        last_kept: bool,
+    },
+    Array,
+}
+
+/// What can come next in a container, to check the structure of the body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    // the first key or value, or the end of the container
+    First,
+    Key,
+    Colon,
+    Value,
+    // a comma or the end of the container
+    Next,
+}
+
+/// Checks the structure of the body, removed values that aren't written are checked too
+#[derive(Debug, Default)]
+struct Syntax {
+    // the opening byte of every open container and what can come next in it
+    stack: Vec<(u8, Expect)>,
+    // a body has a single root value, anything but whitespace after it is invalid
+    root_started: bool,
+}
+
+impl Syntax {
+    fn value(&mut self) -> bool {
+        match self.stack.last_mut() {
+            None if self.root_started => false,
+            None => {
+                self.root_started = true;
+                true
+            }
+            Some((b'{', expect @ Expect::Value))
+            | Some((b'[', expect @ (Expect::First | Expect::Value))) => {
+                *expect = Expect::Next;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn token(&mut self, token: &str) -> bool {
+        let is_string = token.starts_with('"');
+
+        match self.stack.last_mut() {
+            Some((b'{', expect @ (Expect::First | Expect::Key))) => {
+                *expect = Expect::Colon;
+                is_string
+            }
+            _ => (is_string || is_scalar(token)) && self.value(),
+        }
+    }
+
+    fn open(&mut self, byte: u8) -> bool {
+        if !self.value() {
+            return false;
+        }
+
+        self.stack.push((byte, Expect::First));
+        true
+    }
+
+    fn close(&mut self, byte: u8) -> bool {
+        matches!(
+            (self.stack.pop(), byte),
+            (Some((b'{', Expect::First | Expect::Next)), b'}')
+                | (Some((b'[', Expect::First | Expect::Next)), b']')
+        )
+    }
+
+    fn colon(&mut self) -> bool {
+        match self.stack.last_mut() {
+            Some((b'{', expect @ Expect::Colon)) => {
+                *expect = Expect::Value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn comma(&mut self) -> bool {
+        match self.stack.last_mut() {
+            Some((open, expect @ Expect::Next)) => {
+                *expect = if *open == b'{' {
+                    Expect::Key
+                } else {
+                    Expect::Value
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.root_started && self.stack.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    None,
+    String { escaped: bool },
+    Scalar,
+}
+
+/// Masks a JSON response body with the response field masks, removals and PII detectors,
+/// applying the same rules as masking the whole body once it is captured.
+///
+/// Only the masked body and the token being read are kept, bodies that aren't valid JSON
+/// are masked once they are captured instead
+#[derive(Debug)]
+pub(crate) struct StreamingMasker {
+    masks: BodyMask<ResponseMask>,
+    removal: BodyRemoval<ResponseMask>,
+    pii_detection: PiiDetection,
+
+    out: Vec<u8>,
+    stack: Vec<Container>,
+    token: Token,
+    token_bytes: Vec<u8>,
+    // depth of the removed value being skipped, 0 if nothing is skipped
+    skip_depth: usize,
+    syntax: Syntax,
+    invalid: bool,
+}
+
+impl StreamingMasker {
+    pub(crate) fn new(masking: &Masking) -> Self {
+        Self {
+            masks: masking.response_masks.clone(),
+            removal: masking.response_field_removal.clone(),
+            pii_detection: masking.pii_detection.clone(),
+            out: Vec::new(),
+            stack: Vec::new(),
+            token: Token::None,
+            token_bytes: Vec::new(),
+            skip_depth: 0,
+            syntax: Syntax::default(),
+            invalid: false,
+        }
+    }
+
+    /// Whether the body was found not to be valid JSON, it can't be masked chunk by chunk then
+    pub(crate) fn is_invalid(&self) -> bool {
+        self.invalid
+    }
+
+    pub(crate) fn push(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            if self.invalid {
+                return;
+            }
+
+            self.byte(byte);
+        }
+    }
+
+    /// The masked body, `None` if the body isn't valid JSON
+    pub(crate) fn finish(mut self) -> Option<String> {
+        if self.token == Token::Scalar {
+            self.end_token();
+        }
+
+        if self.invalid || self.token != Token::None || !self.syntax.is_complete() {
+            return None;
+        }
+
+        Some(
+            String::from_utf8(self.out)
+                .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned()),
+        )
+    }
+
+    fn byte(&mut self, byte: u8) {
+        match self.token {
+            Token::String { escaped } => {
+                self.token_bytes.push(byte);
+                match byte {
+                    b'\\' if !escaped => self.token = Token::String { escaped: true },
+                    b'"' if !escaped => self.end_token(),
+                    _ => self.token = Token::String { escaped: false },
+                }
+                return;
+            }
+            Token::Scalar if is_delimiter(byte) => self.end_token(),
+            Token::Scalar => {
+                self.token_bytes.push(byte);
+                return;
+            }
+            Token::None => {}
+        }
+
+        match byte {
+            b' ' | b'\n' | b'\r' | b'\t' => self.whitespace(byte),
+            b'"' => {
+                self.token = Token::String { escaped: false };
+                self.token_bytes.push(byte);
+            }
+            b'{' | b'[' if self.syntax.open(byte) => self.start_container(byte),
+            b'}' | b']' if self.syntax.close(byte) => self.end_container(byte),
+            b':' if self.syntax.colon() => self.colon(),
+            b',' if self.syntax.comma() => self.comma(),
+            b'{' | b'[' | b'}' | b']' | b':' | b',' => self.invalid = true,
+            _ => {
+                self.token = Token::Scalar;
+                self.token_bytes.push(byte);
+            }
+        }
+    }
+
+    fn whitespace(&mut self, byte: u8) {
+        if self.skip_depth > 0 {
+            return;
+        }
+
+        match self.stack.last_mut() {
+            Some(Container::Object {
+                member,
+                prefix,
+                value_whitespace,
+                trailing,
+                ..
+            }) => match member {
+                Member::Key | Member::Colon => prefix.push(byte),
+                Member::Value => value_whitespace.push(byte),
+                Member::Next => trailing.push(byte),
+            },
+            _ => self.out.push(byte),
+        }
+    }
+
+    fn colon(&mut self) {
+        if self.skip_depth > 0 {
+            return;
+        }
+
+        match self.stack.last_mut() {
+            Some(Container::Object { member, prefix, .. }) if *member == Member::Colon => {
+                prefix.push(b':');
+                *member = Member::Value;
+            }
+            _ => self.invalid = true,
+        }
+    }
+
+    fn comma(&mut self) {
+        if self.skip_depth > 0 {
+            return;
+        }
+
+        match self.stack.last_mut() {
+            Some(Container::Object {
+                member,
+                trailing,
+                last_kept,
+                ..
+            }) if *member == Member::Next => {
+                // the separator is written before the next kept member
+                if *last_kept {
+                    self.out.append(trailing);
+                }
+                trailing.clear();
+                *member = Member::Key;
+            }
+            Some(Container::Array) => self.out.push(b','),
+            _ => self.invalid = true,
+        }
+    }
+
+    fn start_container(&mut self, byte: u8) {
+        if self.skip_depth > 0 {
+            self.skip_depth += 1;
+            return;
+        }
+
+        if !self.start_value(Some(byte)) {
+            return;
+        }
+
+        self.out.push(byte);
+        self.stack.push(if byte == b'{' {
+            Container::Object {
+                member: Member::Key,
+                key: String::new(),
+                prefix: Vec::new(),
+                value_whitespace: Vec::new(),
+                trailing: Vec::new(),
+                kept: 0,
+                last_kept: true,
+            }
+        } else {
+            Container::Array
+        });
+    }
+
+    fn end_container(&mut self, byte: u8) {
+        if self.skip_depth > 0 {
+            self.skip_depth -= 1;
+            return;
+        }
+
+        match (self.stack.pop(), byte) {
+            (
+                Some(Container::Object {
+                    member: Member::Key | Member::Next,
+                    prefix,
+                    trailing,
+                    ..
+                }),
+                b'}',
+            ) => {
+                // whitespace of an empty object or after the last member
+                self.out.extend_from_slice(&prefix);
+                self.out.extend_from_slice(&trailing);
+                self.out.push(b'}');
+            }
+            (Some(Container::Array), b']') => self.out.push(b']'),
+            _ => self.invalid = true,
+        }
+    }
+
+    fn end_token(&mut self) {
+        self.token = Token::None;
+        let token = std::mem::take(&mut self.token_bytes);
+
+        let token = match String::from_utf8(token) {
+            Ok(token) if self.syntax.token(&token) => token,
+            _ => {
+                self.invalid = true;
+                return;
+            }
+        };
+
+        if self.skip_depth > 0 {
+            return;
+        }
+
+        if let Some(Container::Object {
+            member: member @ Member::Key,
+            key,
+            prefix,
+            ..
+        }) = self.stack.last_mut()
+        {
+            if !token.starts_with('"') {
+                self.invalid = true;
+                return;
+            }
+
+            prefix.extend_from_slice(token.as_bytes());
+            *key = token;
+            *member = Member::Colon;
+            return;
+        }
+
+        if !self.start_value(None) {
+            return;
+        }
+
+        let masked = match self.stack.last() {
+            Some(Container::Object { key, .. }) => self.masks.mask_json_value(key, &token),
+            _ => None,
+        };
+
+        let value_whitespace = match self.stack.last_mut() {
+            Some(Container::Object {
+                value_whitespace, ..
+            }) => std::mem::take(value_whitespace),
+            _ => Vec::new(),
+        };
+
+        match masked {
+            // masked values directly follow the colon, as when masking the whole body
+            Some(masked) => self.out.extend_from_slice(masked.as_bytes()),
+            None => {
+                self.out.extend_from_slice(&value_whitespace);
+
+                let redacted = if self.pii_detection.is_empty() {
+                    None
+                } else if token.starts_with('"') {
+                    self.pii_detection.redact_json_string(&token)
+                } else {
+                    self.pii_detection.redact_json_number(&token)
+                };
+
+                self.out
+                    .extend_from_slice(redacted.as_deref().unwrap_or(&token).as_bytes());
+            }
+        }
+    }
+
+    /// Starts a value in the current container, writing the separator and key of object members.
+    /// Returns `false` if the value is removed, containers are then skipped until they end
+    fn start_value(&mut self, container: Option<u8>) -> bool {
+        let (member, key, prefix, value_whitespace, kept, last_kept) = match self.stack.last_mut() {
+            Some(Container::Object {
+                member,
+                key,
+                prefix,
+                value_whitespace,
+                kept,
+                last_kept,
+                ..
+            }) => (member, key, prefix, value_whitespace, kept, last_kept),
+            Some(Container::Array) | None => return true,
+        };
+
+        if *member != Member::Value {
+            self.invalid = true;
+            return false;
+        }
+        *member = Member::Next;
+
+        let removed = serde_json::from_str::<String>(key)
+            .map(|key| self.removal.is_removed(&key))
+            .unwrap_or(false);
+
+        if removed {
+            prefix.clear();
+            value_whitespace.clear();
+            *last_kept = false;
+            if container.is_some() {
+                self.skip_depth = 1;
+            }
+            return false;
+        }
+
+        if *kept > 0 {
+            self.out.push(b',');
+        }
+        self.out.append(prefix);
+        *kept += 1;
+        *last_kept = true;
+
+        // containers are never masked, only their values
+        if container.is_some() {
+            self.out.append(value_whitespace);
+        }
+
+        true
+    }
+}
+
+// `true`, `false`, `null` or a number
+fn is_scalar(token: &str) -> bool {
+    if matches!(token, "true" | "false" | "null") {
+        return true;
+    }
+
+    let digits = |value: &str| value.bytes().take_while(u8::is_ascii_digit).count();
+
+    let rest = token.strip_prefix('-').unwrap_or(token);
+    let integer = digits(rest);
+    // no leading zeros
+    if integer == 0 || (integer > 1 && rest.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &rest[integer..];
+
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = digits(fraction);
+        if len == 0 {
+            return false;
+        }
+        rest = &fraction[len..];
+    }
+
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        let len = digits(exponent);
+        if len == 0 {
+            return false;
+        }
+        rest = &exponent[len..];
+    }
+
+    rest.is_empty()
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(
+        byte,
+        b',' | b'}' | b']' | b':' | b' ' | b'\n' | b'\r' | b'\t' | b'"' | b'{' | b'['
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generic_http::{BodyCapture, ResponseBody, ResponseBodyCapture},
+        har_builder::mask_json_body,
+        masking::PiiKind,
+    };
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        body: &'static str,
+        expected: Option<&'static str>,
+    }
+
+    fn masking() -> Masking {
+        let mut masking = Masking::default();
+        masking.with_response_field_mask_string("token", None);
+        masking.with_response_field_mask_number("balance", -1);
+        masking.with_response_field_removal("secret");
+        masking.with_pii_detector(PiiKind::Email);
+        masking.with_pii_detector(PiiKind::CreditCard);
+        masking
+    }
+
+    #[test]
+    fn masks_chunks_like_whole_bodies() {
+        let masking = masking();
+
+        let tests = vec![
+            Test {
+                name: "masks, removes and redacts at any depth",
+                body: r#"{"secret": {"a": [1, "b"]}, "user": {"token": "abc", "balance": 12.5e3, "emails": ["jane@example.com"]}, "secret": 1}"#,
+                expected: Some(
+                    r#"{ "user": {"token":"__masked__", "balance":-1, "emails": ["__masked_email__"]}}"#,
+                ),
+            },
+            Test {
+                name: "keeps formatting",
+                body: "[\n  {\"id\": 1,\n   \"secret\": \"x\" }\n]",
+                expected: Some("[\n  {\"id\": 1 }\n]"),
+            },
+            Test {
+                name: "escaped quotes in strings",
+                body: r#"{"note": "say \"hi\"", "token": "a\"b"}"#,
+                expected: Some(r#"{"note": "say \"hi\"", "token":"__masked__"}"#),
+            },
+            Test {
+                name: "redacts unescaped strings and numbers",
+                body: r#"{"note": "mail \"jane\u0040example.com\"", "card": 4111111111111111}"#,
+                expected: Some(
+                    r#"{"note": "mail \"__masked_email__\"", "card": "__masked_credit_card__"}"#,
+                ),
+            },
+            Test {
+                name: "keeps whitespace after the root value",
+                body: "{\"token\": \"a\"}\n",
+                expected: Some("{\"token\":\"__masked__\"}\n"),
+            },
+            Test {
+                name: "unfinished body",
+                body: r#"{"token": "abc""#,
+                expected: None,
+            },
+            Test {
+                name: "data after the root value",
+                body: "{} x",
+                expected: None,
+            },
+            Test {
+                name: "several root values",
+                body: "{}{}",
+                expected: None,
+            },
+            Test {
+                name: "several root scalars",
+                body: "1 2",
+                expected: None,
+            },
+            Test {
+                name: "trailing comma in an object",
+                body: r#"{"a":1,}"#,
+                expected: None,
+            },
+            Test {
+                name: "trailing comma in an array",
+                body: "[1,]",
+                expected: None,
+            },
+            Test {
+                name: "leading comma",
+                body: r#"{,"a":1}"#,
+                expected: None,
+            },
+            Test {
+                name: "missing comma in an array",
+                body: "[1 2]",
+                expected: None,
+            },
+            Test {
+                name: "trailing comma in a removed value",
+                body: r#"{"secret": [1,], "a": 1}"#,
+                expected: None,
+            },
+            Test {
+                name: "bad literal",
+                body: r#"{"a": tru}"#,
+                expected: None,
+            },
+            Test {
+                name: "number with a leading zero",
+                body: r#"{"a": 01}"#,
+                expected: None,
+            },
+            Test {
+                name: "number without fraction digits",
+                body: "[1.]",
+                expected: None,
+            },
+            Test {
+                name: "number without exponent digits",
+                body: r#"{"balance": 1e}"#,
+                expected: None,
+            },
+            Test {
+                name: "bad scalar in a removed value",
+                body: r#"{"secret": nul}"#,
+                expected: None,
+            },
+            Test {
+                name: "only whitespace",
+                body: " ",
+                expected: None,
+            },
+        ];
+
+        let without_whitespace =
+            |body: &str| -> String { body.chars().filter(|c| !c.is_whitespace()).collect() };
+
+        for test in tests {
+            let whole_body = test.expected.map(|_| {
+                mask_json_body(
+                    test.body,
+                    &masking.response_masks,
+                    &masking.response_field_removal,
+                    &masking.pii_detection,
+                )
+            });
+
+            // every split of the body into two chunks gives the same result
+            for split in 0..=test.body.len() {
+                let mut masker = StreamingMasker::new(&masking);
+                masker.push(&test.body.as_bytes()[..split]);
+                masker.push(&test.body.as_bytes()[split..]);
+
+                let masked = masker.finish();
+                assert_eq!(masked.as_deref(), test.expected, "{}", test.name);
+                assert_eq!(
+                    masked.as_deref().map(without_whitespace),
+                    whole_body.as_deref().map(without_whitespace)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_captured_body() {
+        let masking = masking();
+
+        let capture = |body: &'static str| {
+            let mut capture = ResponseBodyCapture::new(Some(StreamingMasker::new(&masking)));
+            for chunk in body.as_bytes().chunks(3) {
+                capture.push(chunk, 1024);
+            }
+            capture.finish()
+        };
+
+        assert_eq!(
+            capture(r#"{"token": "abc"}"#),
+            ResponseBody::Masked {
+                text: r#"{"token":"__masked__"}"#.to_string(),
+                size: 16,
+            }
+        );
+        // invalid bodies are masked once they are captured
+        assert_eq!(
+            capture(r#"{"token": "abc",}"#),
+            BodyCapture::Captured(bytes::Bytes::from_static(br#"{"token": "abc",}"#)).into()
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use super::{Fields, NumberMask, NumberMaskingOption, StringMaskingOption};
+
+/// Types whose JSON body fields are masked, so the masks follow the type when its fields change.
+///
+/// Usually derived with `#[derive(SpeakeasyMask)]` from the `derive` feature, the masked fields are
+/// marked with `#[speakeasy(mask)]` or `#[speakeasy(mask = "****")]` (`#[speakeasy(mask = 0)]` or `#[speakeasy(mask = 0.5)]` for numbers)
+/// and are masked by their serialized and deserialized `serde(rename)` and `serde(rename_all)` names and their `serde(alias)` names.
+/// Fields marked with `#[speakeasy(nested)]` add the masks of their own type, recursive types add their masks once.
+/// Register the masks with [Masking::with_request_type_masks](super::Masking::with_request_type_masks)
+/// and [Masking::with_response_type_masks](super::Masking::with_response_type_masks).
+///
+/// # Examples
+/// ```rust
+/// use speakeasy_rust_sdk::{Masking, masking::{NumberMask, SpeakeasyMask}};
+///
+/// // what `#[derive(SpeakeasyMask)]` generates for a type with masked `cardNumber`, `pin` and `amount` fields
+/// struct CreatePayment;
+///
+/// impl SpeakeasyMask for CreatePayment {
+///     fn string_field_masks() -> Vec<(&'static str, Option<&'static str>)> {
+///         vec![("cardNumber", Some("****"))]
+///     }
+///
+///     fn number_field_masks() -> Vec<(&'static str, Option<NumberMask>)> {
+///         vec![("pin", None), ("amount", Some(NumberMask::Float(0.5)))]
+///     }
+/// }
+///
+/// let mut payment_masking = Masking::default();
+/// payment_masking.with_request_type_masks::<CreatePayment>();
+///
+/// let mut masking = Masking::default();
+/// masking.with_route_masking("POST /payments", payment_masking);
+/// ```
+pub trait SpeakeasyMask {
+    /// The JSON names of the string fields to mask and their mask, `None` uses the default mask
+    fn string_field_masks() -> Vec<(&'static str, Option<&'static str>)>;
+
+    /// The JSON names of the number fields to mask and their mask, `None` uses the default mask
+    fn number_field_masks() -> Vec<(&'static str, Option<NumberMask>)>;
+}
+
+thread_local! {
+    // the types whose masks are being collected
+    static COLLECTING: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The string field masks of a `#[speakeasy(nested)]` field's type, used by `#[derive(SpeakeasyMask)]`.
+/// Types referring back to a type whose masks are being collected add no masks, so recursive types don't recurse forever
+#[doc(hidden)]
+pub fn nested_string_field_masks<T: SpeakeasyMask + ?Sized>(
+) -> Vec<(&'static str, Option<&'static str>)> {
+    collect::<T, _>(T::string_field_masks)
+}
+
+/// The number field masks of a `#[speakeasy(nested)]` field's type, see [nested_string_field_masks]
+#[doc(hidden)]
+pub fn nested_number_field_masks<T: SpeakeasyMask + ?Sized>(
+) -> Vec<(&'static str, Option<NumberMask>)> {
+    collect::<T, _>(T::number_field_masks)
+}
+
+fn collect<T: ?Sized, M>(field_masks: fn() -> Vec<M>) -> Vec<M> {
+    struct Collecting;
+
+    impl Drop for Collecting {
+        fn drop(&mut self) {
+            COLLECTING.with(|collecting| collecting.borrow_mut().pop());
+        }
+    }
+
+    let name = std::any::type_name::<T>();
+    let is_collecting = COLLECTING.with(|collecting| {
+        let mut collecting = collecting.borrow_mut();
+        let is_collecting = collecting.contains(&name);
+        if !is_collecting {
+            collecting.push(name);
+        }
+        is_collecting
+    });
+
+    if is_collecting {
+        return Vec::new();
+    }
+
+    let _collecting = Collecting;
+    field_masks()
+}
+
+pub(crate) fn string_field_masks<T: SpeakeasyMask + ?Sized>() -> (Fields, StringMaskingOption) {
+    let masks = nested_string_field_masks::<T>();
+    let fields: Vec<&str> = masks.iter().map(|(field, _)| *field).collect();
+
+    let associated: HashMap<String, String> = masks
+        .iter()
+        .filter_map(|(field, mask)| mask.map(|mask| (field.to_string(), mask.to_string())))
+        .collect();
+
+    let masking_option = if associated.is_empty() {
+        StringMaskingOption::None
+    } else {
+        StringMaskingOption::AssociatedMasks(associated)
+    };
+
+    (fields.into(), masking_option)
+}
+
+pub(crate) fn number_field_masks<T: SpeakeasyMask + ?Sized>() -> (Fields, NumberMaskingOption) {
+    let masks = nested_number_field_masks::<T>();
+    let fields: Vec<&str> = masks.iter().map(|(field, _)| *field).collect();
+
+    let associated: HashMap<String, NumberMask> = masks
+        .iter()
+        .filter_map(|(field, mask)| mask.map(|mask| (field.to_string(), mask)))
+        .collect();
+
+    let masking_option = if associated.is_empty() {
+        NumberMaskingOption::None
+    } else {
+        NumberMaskingOption::AssociatedMasks(associated)
+    };
+
+    (fields.into(), masking_option)
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::*;
+    use crate::{
+        masking::SpeakeasyMask, speakeasy_protos::ingest::ingest_request::MaskingMetadata, Masking,
+    };
+    use pretty_assertions::assert_eq;
+    use serde::Deserialize;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, SpeakeasyMask)]
+    #[serde(rename_all = "camelCase")]
+    struct CreateUser {
+        email: String,
+        #[speakeasy(mask)]
+        card_number: String,
+        #[speakeasy(mask = "****")]
+        #[serde(rename = "pwd")]
+        password: String,
+        #[speakeasy(mask = 0)]
+        pin_code: u32,
+        #[speakeasy(mask)]
+        balance: Option<f64>,
+        #[speakeasy(mask = 1.5)]
+        rate: f64,
+        #[speakeasy(mask = -9_000_000_000)]
+        account_id: i64,
+        #[speakeasy(nested)]
+        address: Option<Address>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, SpeakeasyMask)]
+    struct Address {
+        #[speakeasy(mask)]
+        #[serde(rename(serialize = "street", deserialize = "street_name"))]
+        street: String,
+        city: String,
+    }
+
+    #[test]
+    fn derives_masks_with_serde_names() {
+        let mut masking = Masking::default();
+        masking.with_request_field_mask_string("email", None);
+        masking.with_request_type_masks::<CreateUser>();
+
+        let metadata = MaskingMetadata::from(masking);
+
+        assert_eq!(
+            metadata.request_field_masks_string,
+            HashMap::from([
+                ("email".to_string(), "__masked__".to_string()),
+                ("cardNumber".to_string(), "__masked__".to_string()),
+                ("pwd".to_string(), "****".to_string()),
+                ("street".to_string(), "__masked__".to_string()),
+                ("street_name".to_string(), "__masked__".to_string()),
+            ])
+        );
+        assert_eq!(
+            metadata.request_field_masks_number,
+            HashMap::from([
+                ("pinCode".to_string(), "0".to_string()),
+                ("balance".to_string(), "-12321".to_string()),
+                ("rate".to_string(), "1.5".to_string()),
+                ("accountId".to_string(), "-9000000000".to_string()),
+            ])
+        );
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, SpeakeasyMask)]
+    #[serde(rename_all(serialize = "camelCase", deserialize = "kebab-case"))]
+    struct Node {
+        #[speakeasy(mask)]
+        #[serde(alias = "secret")]
+        api_key: String,
+        #[speakeasy(nested)]
+        children: Vec<Node>,
+        #[speakeasy(nested)]
+        parent: Option<Box<Parent>>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, SpeakeasyMask)]
+    struct Parent {
+        #[speakeasy(mask = 0)]
+        depth: u32,
+        #[speakeasy(nested)]
+        root: Box<Node>,
+    }
+
+    #[test]
+    fn derives_masks_for_aliases_and_recursive_types() {
+        let mut masking = Masking::default();
+        masking.with_request_type_masks::<Node>();
+
+        let metadata = MaskingMetadata::from(masking);
+
+        assert_eq!(
+            metadata.request_field_masks_string,
+            HashMap::from([
+                ("apiKey".to_string(), "__masked__".to_string()),
+                ("api-key".to_string(), "__masked__".to_string()),
+                ("secret".to_string(), "__masked__".to_string()),
+            ])
+        );
+        assert_eq!(
+            metadata.request_field_masks_number,
+            HashMap::from([("depth".to_string(), "0".to_string())])
+        );
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, SpeakeasyMask)]
+    struct Account {
+        #[speakeasy(mask)]
+        #[serde(
+            rename(serialize = "secret", deserialize = "secret_in"),
+            skip_serializing
+        )]
+        secret: String,
+        #[speakeasy(mask = 0)]
+        #[serde(rename(serialize = "pin", deserialize = "pin_in"), skip_deserializing)]
+        pin: u32,
+        #[serde(skip)]
+        cache: Vec<u8>,
+        #[speakeasy(nested)]
+        #[serde(flatten)]
+        address: Address,
+    }
+
+    #[test]
+    fn derives_masks_for_skipped_and_flattened_fields() {
+        let mut masking = Masking::default();
+        masking.with_request_type_masks::<Account>();
+
+        let metadata = MaskingMetadata::from(masking);
+
+        assert_eq!(
+            metadata.request_field_masks_string,
+            HashMap::from([
+                ("secret_in".to_string(), "__masked__".to_string()),
+                ("street".to_string(), "__masked__".to_string()),
+                ("street_name".to_string(), "__masked__".to_string()),
+            ])
+        );
+        assert_eq!(
+            metadata.request_field_masks_number,
+            HashMap::from([("pin".to_string(), "0".to_string())])
+        );
+    }
+}
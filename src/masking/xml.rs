@@ -0,0 +1,261 @@
+//! Streaming XML rewriter used to mask XML and SOAP bodies, namespaces and the original
+//! formatting of everything that isn't masked are preserved
+
+use std::borrow::Cow;
+
+use quick_xml::{
+    events::{attributes::Attribute, BytesCData, BytesStart, BytesText, Event},
+    name::QName,
+    Reader, Writer,
+};
+
+use super::{body_mask::BodyMask, pii::PiiDetection, removal::BodyRemoval};
+
+/// Checks if a content type is an XML content type, e.g. `application/xml`, `text/xml` or `application/soap+xml`
+pub(crate) fn is_xml(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    mime.ends_with("/xml") || mime.ends_with("+xml")
+}
+
+/// Masks the text content of elements and the values of attributes whose name matches a body mask,
+/// names are matched either with their namespace prefix (`soap:Password`) or without (`Password`).
+/// Elements matching a body removal are dropped along with their children.
+///
+/// Returns `None` if the body is not valid XML
+pub(crate) fn mask<T: Default>(
+    body: &str,
+    masker: &BodyMask<T>,
+    removal: &BodyRemoval<T>,
+    pii_detection: &PiiDetection,
+) -> Option<String> {
+    let mut reader = Reader::from_str(body);
+    let mut writer = Writer::new(Vec::with_capacity(body.len()));
+
+    // qualified names of the currently open elements
+    let mut open_elements: Vec<Vec<u8>> = Vec::new();
+    // depth inside of a removed element
+    let mut removed_depth = 0;
+
+    let mask_value = |name: QName, value: &str| -> Option<String> {
+        let masked = match mask_by_name(masker, name, value) {
+            Some(masked) => Cow::Owned(masked),
+            None => Cow::Borrowed(value),
+        };
+
+        match pii_detection.redact(&masked) {
+            Cow::Borrowed(_) if matches!(masked, Cow::Borrowed(_)) => None,
+            redacted => Some(redacted.into_owned()),
+        }
+    };
+
+    loop {
+        let event = reader.read_event().ok()?;
+
+        if removed_depth > 0 {
+            match event {
+                Event::Start(_) => removed_depth += 1,
+                Event::End(_) => removed_depth -= 1,
+                Event::Eof => return None,
+                _ => {}
+            }
+            continue;
+        }
+
+        let event = match event {
+            // truncated bodies end with elements that are still open
+            Event::Eof if !open_elements.is_empty() => return None,
+            Event::Eof => break,
+            Event::Start(element) if is_removed(removal, element.name()) => {
+                removed_depth = 1;
+                continue;
+            }
+            Event::Empty(element) if is_removed(removal, element.name()) => continue,
+            Event::Start(element) => {
+                open_elements.push(element.name().as_ref().to_vec());
+                Event::Start(mask_attributes(element, &mask_value)?)
+            }
+            Event::Empty(element) => Event::Empty(mask_attributes(element, &mask_value)?),
+            Event::End(element) => {
+                open_elements.pop();
+                Event::End(element)
+            }
+            Event::Text(text) => {
+                let value = text.unescape().ok()?;
+
+                match open_elements.last() {
+                    Some(name) if !value.trim().is_empty() => {
+                        match mask_value(QName(name), &value) {
+                            Some(masked) => Event::Text(BytesText::new(&masked).into_owned()),
+                            None => Event::Text(text),
+                        }
+                    }
+                    _ => Event::Text(text),
+                }
+            }
+            Event::CData(data) => {
+                let value = String::from_utf8_lossy(&data).into_owned();
+
+                match open_elements.last() {
+                    Some(name) => match mask_value(QName(name), &value) {
+                        Some(masked) => Event::CData(BytesCData::new(masked)),
+                        None => Event::CData(data),
+                    },
+                    None => Event::CData(data),
+                }
+            }
+            event => event,
+        };
+
+        writer.write_event(event).ok()?;
+    }
+
+    String::from_utf8(writer.into_inner()).ok()
+}
+
+fn mask_attributes<'a, F>(element: BytesStart<'a>, mask_value: &F) -> Option<BytesStart<'a>>
+where
+    F: Fn(QName, &str) -> Option<String>,
+{
+    let mut masked_attributes = Vec::new();
+    let mut changed = false;
+
+    for attribute in element.attributes() {
+        let attribute = attribute.ok()?;
+
+        // namespace declarations are never masked
+        let is_namespace = attribute.key.as_ref() == b"xmlns"
+            || attribute.key.prefix().map(|p| p.as_ref() == b"xmlns") == Some(true);
+
+        let masked = if is_namespace {
+            None
+        } else {
+            mask_value(attribute.key, &attribute.unescape_value().ok()?)
+        };
+
+        match masked {
+            Some(masked) => {
+                changed = true;
+                masked_attributes.push((
+                    attribute.key.as_ref().to_vec(),
+                    quick_xml::escape::escape(&masked).as_bytes().to_vec(),
+                ));
+            }
+            None => {
+                masked_attributes.push((attribute.key.as_ref().to_vec(), attribute.value.to_vec()))
+            }
+        }
+    }
+
+    if !changed {
+        return Some(element);
+    }
+
+    let mut element = element;
+    element.clear_attributes();
+    for (key, value) in &masked_attributes {
+        element.push_attribute(Attribute::from((key.as_slice(), value.as_slice())));
+    }
+
+    Some(element)
+}
+
+fn mask_by_name<T: Default>(masker: &BodyMask<T>, name: QName, value: &str) -> Option<String> {
+    let qualified_name = String::from_utf8_lossy(name.as_ref());
+
+    if let Cow::Owned(masked) = masker.mask_field(&qualified_name, value) {
+        return Some(masked);
+    }
+
+    if name.prefix().is_some() {
+        let local_name = String::from_utf8_lossy(name.local_name().into_inner());
+
+        if let Cow::Owned(masked) = masker.mask_field(&local_name, value) {
+            return Some(masked);
+        }
+    }
+
+    None
+}
+
+fn is_removed<T>(removal: &BodyRemoval<T>, name: QName) -> bool {
+    removal.is_removed(&String::from_utf8_lossy(name.as_ref()))
+        || removal.is_removed(&String::from_utf8_lossy(name.local_name().into_inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::masking::Masking;
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        body: &'static str,
+        expected: Option<&'static str>,
+    }
+
+    #[test]
+    fn run() {
+        let mut masking = Masking::default();
+        masking.with_request_field_mask_string(vec!["Password", "token"], "__masked__");
+        masking.with_request_field_mask_number("Pin", -1);
+        masking.with_request_field_removal("Avatar");
+
+        let tests = vec![
+            Test {
+                name: "masks elements and keeps namespaces",
+                body: r#"<?xml version="1.0"?>
+<soap:Envelope xmlns:soap="http://www.w3.org/2003/05/soap-envelope" xmlns:a="urn:auth">
+  <soap:Body>
+    <a:Login a:token="abc&amp;def">
+      <a:User>jane</a:User>
+      <a:Password>hunter2</a:Password>
+      <a:Pin>1234</a:Pin>
+    </a:Login>
+  </soap:Body>
+</soap:Envelope>"#,
+                expected: Some(
+                    r#"<?xml version="1.0"?>
+<soap:Envelope xmlns:soap="http://www.w3.org/2003/05/soap-envelope" xmlns:a="urn:auth">
+  <soap:Body>
+    <a:Login a:token="__masked__">
+      <a:User>jane</a:User>
+      <a:Password>__masked__</a:Password>
+      <a:Pin>-1</a:Pin>
+    </a:Login>
+  </soap:Body>
+</soap:Envelope>"#,
+                ),
+            },
+            Test {
+                name: "masks cdata and removes elements",
+                body: r#"<Login><Password><![CDATA[a<b]]></Password><Avatar><Data>AAAA</Data></Avatar><Avatar/></Login>"#,
+                expected: Some(r#"<Login><Password><![CDATA[__masked__]]></Password></Login>"#),
+            },
+            Test {
+                name: "returns none for invalid xml",
+                body: r#"<Login><Password>hunter2</Login>"#,
+                expected: None,
+            },
+            Test {
+                name: "returns none for truncated xml",
+                body: r#"<Login><Password>hunter2"#,
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(
+                mask(
+                    test.body,
+                    &masking.request_masks,
+                    &masking.request_field_removal,
+                    &masking.pii_detection
+                )
+                .as_deref(),
+                test.expected
+            );
+        }
+    }
+}
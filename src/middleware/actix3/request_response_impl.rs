@@ -66,7 +66,7 @@ impl GenericResponse {
             http_version,
             headers: get_response_headers(response),
             cookies,
-            body: BodyCapture::Empty,
+            body: BodyCapture::Empty.into(),
         }
     }
 }
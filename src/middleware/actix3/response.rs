@@ -5,7 +5,7 @@ use std::sync::{Arc, RwLock};
 use std::task::{Context, Poll};
 
 use actix3::body::{BodySize, MessageBody, ResponseBody};
-use actix3::web::{Bytes, BytesMut};
+use actix3::web::Bytes;
 use actix3::{dev::ServiceRequest, dev::ServiceResponse, Error};
 use actix_service1::{Service, Transform};
 use futures::future::{ok, Ready};
@@ -13,7 +13,7 @@ use log::error;
 
 use crate::async_runtime;
 use crate::controller::{Controller, MAX_SIZE};
-use crate::generic_http::{BodyCapture, GenericResponse};
+use crate::generic_http::{GenericResponse, ResponseBodyCapture};
 use crate::transport::Transport;
 
 #[derive(Clone)]
@@ -107,14 +107,20 @@ where
             drop(ext);
 
             let generic_response = GenericResponse::new(&res);
+            let body_capture =
+                ResponseBodyCapture::new(controller.as_ref().and_then(|controller| {
+                    controller
+                        .read()
+                        .unwrap()
+                        .response_body_masker(&generic_response)
+                }));
 
             res.map_body(move |_head, body| {
                 ResponseBody::Body(ResponseWithBodySender {
                     body,
                     generic_response,
                     controller,
-                    body_dropped: false,
-                    body_accum: BytesMut::new(),
+                    body_capture,
                 })
             })
         }))
@@ -130,8 +136,7 @@ where
     body: ResponseBody<B>,
     generic_response: GenericResponse,
     controller: Option<Arc<RwLock<Controller<T>>>>,
-    body_accum: BytesMut,
-    body_dropped: bool,
+    body_capture: ResponseBodyCapture,
 }
 
 #[pin_project::pinned_drop]
@@ -140,15 +145,12 @@ where
     T: Transport + Send + Clone + 'static,
 {
     fn drop(self: Pin<&mut Self>) {
-        if let Some(controller) = self.controller.as_ref() {
-            let mut response = self.generic_response.clone();
-
-            // set body field, initialized as empty
-            if self.body_dropped {
-                response.body = BodyCapture::Dropped
-            } else if !self.body_accum.is_empty() {
-                response.body = BodyCapture::Captured(self.body_accum.to_vec().into())
-            }
+        let this = self.project();
+
+        if let Some(controller) = this.controller.as_ref() {
+            let mut response = this.generic_response.clone();
+
+            response.body = this.body_capture.finish();
 
             let controller: Controller<T> = controller.read().unwrap().clone();
 
@@ -180,14 +182,7 @@ where
 
         match this.body.poll_next(cx) {
             Poll::Ready(Some(Ok(chunk))) => {
-                if !*this.body_dropped {
-                    this.body_accum.extend_from_slice(&chunk);
-
-                    if this.body_accum.len() > max_size {
-                        *this.body_dropped = true;
-                        this.body_accum.clear();
-                    }
-                }
+                this.body_capture.push(&chunk, max_size);
 
                 Poll::Ready(Some(Ok(chunk)))
             }
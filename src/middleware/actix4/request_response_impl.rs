@@ -68,7 +68,7 @@ impl GenericResponse {
             http_version,
             headers: get_response_headers(response),
             cookies,
-            body: BodyCapture::Empty,
+            body: BodyCapture::Empty.into(),
         }
     }
 }
@@ -8,7 +8,7 @@ use std::task::{Context, Poll};
 use actix_web::{
     body::{BodySize, MessageBody},
     dev::{Service, ServiceRequest, ServiceResponse, Transform},
-    web::{Bytes, BytesMut},
+    web::Bytes,
     Error,
 };
 use futures::future::{ok, Ready};
@@ -16,7 +16,7 @@ use log::error;
 
 use crate::async_runtime;
 use crate::controller::{Controller, MAX_SIZE};
-use crate::generic_http::{BodyCapture, GenericResponse};
+use crate::generic_http::{GenericResponse, ResponseBodyCapture};
 use crate::transport::Transport;
 
 #[derive(Clone)]
@@ -109,13 +109,19 @@ where
             drop(ext);
 
             let generic_response = GenericResponse::new(&res);
+            let body_capture =
+                ResponseBodyCapture::new(controller.as_ref().and_then(|controller| {
+                    controller
+                        .read()
+                        .unwrap()
+                        .response_body_masker(&generic_response)
+                }));
 
             res.map_body(move |_head, body| ResponseWithBodySender {
                 body,
                 generic_response,
                 controller,
-                body_dropped: false,
-                body_accum: BytesMut::new(),
+                body_capture,
             })
         }))
     }
@@ -130,8 +136,7 @@ where
     body: B,
     generic_response: GenericResponse,
     controller: Option<Arc<RwLock<Controller<T>>>>,
-    body_accum: BytesMut,
-    body_dropped: bool,
+    body_capture: ResponseBodyCapture,
 }
 
 #[pin_project::pinned_drop]
@@ -140,15 +145,12 @@ where
     T: Transport + Send + Clone + 'static,
 {
     fn drop(self: Pin<&mut Self>) {
-        if let Some(controller) = self.controller.as_ref() {
-            let mut response = self.generic_response.clone();
-
-            // set body field, initialized as empty
-            if self.body_dropped {
-                response.body = BodyCapture::Dropped
-            } else if !self.body_accum.is_empty() {
-                response.body = BodyCapture::Captured(self.body_accum.to_vec().into())
-            }
+        let this = self.project();
+
+        if let Some(controller) = this.controller.as_ref() {
+            let mut response = this.generic_response.clone();
+
+            response.body = this.body_capture.finish();
 
             let controller: Controller<T> = controller.read().unwrap().clone();
 
@@ -185,14 +187,7 @@ where
 
         match this.body.poll_next(cx) {
             Poll::Ready(Some(Ok(chunk))) => {
-                if !*this.body_dropped {
-                    this.body_accum.extend_from_slice(&chunk);
-
-                    if this.body_accum.len() > max_size {
-                        *this.body_dropped = true;
-                        this.body_accum.clear();
-                    }
-                }
+                this.body_capture.push(&chunk, max_size);
 
                 Poll::Ready(Some(Ok(chunk)))
             }
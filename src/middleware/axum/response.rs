@@ -1,4 +1,4 @@
-use bytes::{Buf, Bytes, BytesMut};
+use bytes::{Buf, Bytes};
 use futures::ready;
 use http::{Request, Response};
 
@@ -15,7 +15,7 @@ use tower::{Layer, Service};
 
 use crate::async_runtime;
 use crate::controller::{Controller, MAX_SIZE};
-use crate::generic_http::{BodyCapture, GenericResponse};
+use crate::generic_http::{GenericResponse, ResponseBodyCapture};
 use crate::transport::Transport;
 
 /// Alias for a type-erased error type.
@@ -109,14 +109,7 @@ where
 
         match this.body.poll_data(cx) {
             Poll::Ready(Some(Ok(mut chunk))) => {
-                if !*this.body_dropped {
-                    this.body_accum.extend_from_slice(chunk.chunk());
-
-                    if this.body_accum.len() > max_size {
-                        *this.body_dropped = true;
-                        this.body_accum.clear();
-                    }
-                }
+                this.body_capture.push(chunk.chunk(), max_size);
 
                 let bytes = chunk.copy_to_bytes(chunk.remaining());
                 Poll::Ready(Some(Ok(bytes)))
@@ -145,8 +138,7 @@ where
     generic_response: GenericResponse,
     controller: Option<Arc<RwLock<Controller<T>>>>,
     _t: PhantomData<T>,
-    body_accum: BytesMut,
-    body_dropped: bool,
+    body_capture: ResponseBodyCapture,
 }
 
 #[pin_project::pinned_drop]
@@ -155,15 +147,12 @@ where
     T: Transport + Send + Clone + 'static,
 {
     fn drop(self: Pin<&mut Self>) {
-        if let Some(controller) = self.controller.as_ref() {
-            let mut response = self.generic_response.clone();
-
-            // set body field, initialized as empty
-            if self.body_dropped {
-                response.body = BodyCapture::Dropped
-            } else if !self.body_accum.is_empty() {
-                response.body = BodyCapture::Captured(self.body_accum.to_vec().into())
-            }
+        let this = self.project();
+
+        if let Some(controller) = this.controller.as_ref() {
+            let mut response = this.generic_response.clone();
+
+            response.body = this.body_capture.finish();
 
             let controller: Controller<T> = controller.read().unwrap().clone();
 
@@ -204,6 +193,12 @@ where
         }
 
         let generic_response = GenericResponse::new(&res);
+        let body_capture = ResponseBodyCapture::new(controller.as_ref().and_then(|controller| {
+            controller
+                .read()
+                .unwrap()
+                .response_body_masker(&generic_response)
+        }));
         let (parts, body) = res.into_parts();
 
         let body_with_sender = ResponseWithBodySender {
@@ -211,8 +206,7 @@ where
             generic_response,
             controller,
             _t: PhantomData,
-            body_accum: BytesMut::new(),
-            body_dropped: false,
+            body_capture,
         };
 
         let res = Response::from_parts(parts, body_with_sender);
@@ -0,0 +1,157 @@
+//! Minimal multipart/form-data parser used to build HAR params for multipart request bodies
+
+/// A single part of a multipart/form-data body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Part<'a> {
+    pub(crate) name: String,
+    pub(crate) file_name: Option<String>,
+    pub(crate) content_type: Option<String>,
+    pub(crate) body: &'a [u8],
+}
+
+impl Part<'_> {
+    /// Parts with a filename are file uploads, everything else is a text field
+    pub(crate) fn is_file(&self) -> bool {
+        self.file_name.is_some()
+    }
+}
+
+/// Checks if the content type is multipart/form-data
+pub(crate) fn is_multipart(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("multipart/form-data"))
+}
+
+/// Gets the boundary from a multipart/form-data content type
+pub(crate) fn boundary(content_type: &str) -> Option<&str> {
+    if !is_multipart(content_type) {
+        return None;
+    }
+
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"'))
+        .filter(|boundary| !boundary.is_empty())
+}
+
+/// Parses a multipart/form-data body, returns `None` if the body is malformed
+pub(crate) fn parse<'a>(body: &'a [u8], boundary: &str) -> Option<Vec<Part<'a>>> {
+    let delimiter = format!("--{}", boundary);
+    let next_delimiter = format!("\r\n--{}", boundary);
+
+    let mut pos = find(body, delimiter.as_bytes(), 0)? + delimiter.len();
+    let mut parts = Vec::new();
+
+    loop {
+        // closing delimiter
+        if body[pos..].starts_with(b"--") {
+            return Some(parts);
+        }
+
+        pos += find(&body[pos..], b"\r\n", 0)? + 2;
+
+        let headers_end = find(body, b"\r\n\r\n", pos)?;
+        let headers = std::str::from_utf8(&body[pos..headers_end]).ok()?;
+
+        let body_start = headers_end + 4;
+        let body_end = find(body, next_delimiter.as_bytes(), body_start)?;
+
+        let mut part = Part {
+            name: String::new(),
+            file_name: None,
+            content_type: None,
+            body: &body[body_start..body_end],
+        };
+
+        for header in headers.split("\r\n") {
+            let (name, value) = match header.split_once(':') {
+                Some(header) => header,
+                None => continue,
+            };
+
+            if name.trim().eq_ignore_ascii_case("content-disposition") {
+                for param in value.split(';').skip(1) {
+                    match param.split_once('=') {
+                        Some((key, value)) if key.trim() == "name" => {
+                            part.name = value.trim().trim_matches('"').to_string()
+                        }
+                        Some((key, value)) if key.trim() == "filename" => {
+                            part.file_name = Some(value.trim().trim_matches('"').to_string())
+                        }
+                        _ => {}
+                    }
+                }
+            } else if name.trim().eq_ignore_ascii_case("content-type") {
+                part.content_type = Some(value.trim().to_string());
+            }
+        }
+
+        parts.push(part);
+        pos = body_end + next_delimiter.len();
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|index| index + from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        body: &'static [u8],
+        expected: Option<Vec<Part<'static>>>,
+    }
+
+    #[test]
+    fn run() {
+        let tests = vec![
+            Test {
+                name: "parses text and file parts",
+                body: b"preamble\r\n--XyZ\r\nContent-Disposition: form-data; name=\"password\"\r\n\r\nhunter2\r\n--XyZ\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"me.png\"\r\nContent-Type: image/png\r\n\r\n\x89PNG\r\n\x00\r\n--XyZ--\r\n",
+                expected: Some(vec![
+                    Part {
+                        name: "password".to_string(),
+                        file_name: None,
+                        content_type: None,
+                        body: b"hunter2",
+                    },
+                    Part {
+                        name: "avatar".to_string(),
+                        file_name: Some("me.png".to_string()),
+                        content_type: Some("image/png".to_string()),
+                        body: b"\x89PNG\r\n\x00",
+                    },
+                ]),
+            },
+            Test {
+                name: "returns none for a truncated body",
+                body: b"--XyZ\r\nContent-Disposition: form-data; name=\"password\"\r\n\r\nhunter2",
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            assert_eq!(parse(test.body, "XyZ"), test.expected);
+        }
+
+        assert_eq!(
+            boundary("multipart/form-data; boundary=\"XyZ\""),
+            Some("XyZ")
+        );
+        assert_eq!(boundary("application/json; boundary=XyZ"), None);
+    }
+}
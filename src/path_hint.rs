@@ -1,12 +1,27 @@
 use std::borrow::Cow;
 
 use once_cell::sync::Lazy;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
 use regex::{Captures, Regex};
 
 use crate::util;
 
 static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\{(.*?:*.*?)\}/|:(.+?)/|:(.*)|\*"#).unwrap());
 
+// characters that have to be encoded in a single path segment
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
 /// Normalize the path hint given from different web frameworks to a the OpenAPI spec
 pub fn normalize(path_hint: &str) -> String {
     RE.replace_all(path_hint.trim(), |caps: &Captures| {
@@ -29,6 +44,62 @@ pub fn normalize(path_hint: &str) -> String {
     .into_owned()
 }
 
+/// Masks the segments of a path matching a `{param}` of a normalized path hint.
+/// `mask` is called with the parameter name and the decoded segment and returns the replacement if the parameter is masked.
+/// Returns `None` if the path doesn't match the path hint or nothing was masked
+pub(crate) fn mask_params<F>(path_hint: &str, path: &str, mut mask: F) -> Option<String>
+where
+    F: FnMut(&str, &str) -> Option<String>,
+{
+    let hint_segments = path_hint.split('/').collect::<Vec<&str>>();
+    let mut segments = path
+        .split('/')
+        .map(Cow::Borrowed)
+        .collect::<Vec<Cow<str>>>();
+
+    if hint_segments.len() != segments.len() {
+        return None;
+    }
+
+    let mut masked = false;
+
+    for (hint_segment, segment) in hint_segments.into_iter().zip(segments.iter_mut()) {
+        // segments can have a prefix and suffix around the param, e.g. `{id}.json`
+        let (prefix, name, suffix) =
+            match hint_segment.split_once('{').and_then(|(prefix, rest)| {
+                rest.split_once('}')
+                    .map(|(name, suffix)| (prefix, name, suffix))
+            }) {
+                Some(param) => param,
+                None => continue,
+            };
+
+        let value = match segment
+            .strip_prefix(prefix)
+            .and_then(|value| value.strip_suffix(suffix))
+        {
+            Some(value) => value,
+            None => continue,
+        };
+
+        if let Some(replacement) = mask(name, &percent_decode_str(value).decode_utf8_lossy()) {
+            *segment = Cow::Owned(format!(
+                "{}{}{}",
+                prefix,
+                utf8_percent_encode(&replacement, PATH_SEGMENT),
+                suffix
+            ));
+            masked = true;
+        }
+    }
+
+    if masked {
+        Some(segments.join("/"))
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,4 +156,45 @@ mod tests {
             assert_eq!(normalize(test.path_hint), test.expected);
         }
     }
+
+    #[test]
+    fn masks_params() {
+        struct Test {
+            #[allow(dead_code)]
+            name: &'static str,
+            path_hint: &'static str,
+            expected: Option<&'static str>,
+        }
+
+        let tests = vec![
+            Test {
+                name: "masks matching params",
+                path_hint: "/users/{email}/accounts/{iban}.json",
+                expected: Some("/users/__masked__/accounts/__masked__.json"),
+            },
+            Test {
+                name: "does not mask other params",
+                path_hint: "/users/{name}/accounts/{id}.json",
+                expected: None,
+            },
+            Test {
+                name: "does not mask paths with a different shape",
+                path_hint: "/users/{email}",
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            let masked = mask_params(
+                test.path_hint,
+                "/users/jane%40example.com/accounts/GB82WEST12345698765432.json",
+                |name, value| {
+                    assert!(value == "jane@example.com" || value.starts_with("GB82"));
+                    (name == "email" || name == "iban").then(|| "__masked__".to_string())
+                },
+            );
+
+            assert_eq!(masked.as_deref(), test.expected);
+        }
+    }
 }
@@ -0,0 +1,367 @@
+//! Decodes `application/x-protobuf` and `application/grpc` bodies to JSON with [prost_reflect] message descriptors,
+//! so the request and response field masks apply to them and the HAR shows readable content
+
+use log::error;
+use prost_reflect::{DynamicMessage, MessageDescriptor, SerializeOptions, ServiceDescriptor};
+use thiserror::Error;
+
+use crate::masking::route::RoutePattern;
+
+pub use prost_reflect;
+
+// content types of protobuf messages without framing
+const PROTOBUF_CONTENT_TYPES: [&str; 3] = [
+    "application/x-protobuf",
+    "application/protobuf",
+    "application/vnd.google.protobuf",
+];
+
+/// Errors for registering protobuf messages
+#[derive(Debug, Clone, Error)]
+pub enum Error {
+    #[error("invalid route: {0:?}, expected an optional method and a path starting with `/`")]
+    InvalidRoute(String),
+}
+
+#[derive(Debug, Clone)]
+struct ProtobufRoute {
+    route: RoutePattern,
+    request: Option<MessageDescriptor>,
+    response: Option<MessageDescriptor>,
+}
+
+/// A decoded body and the full name of its message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Decoded {
+    pub(crate) json: String,
+    pub(crate) message: String,
+}
+
+/// The protobuf messages of request and response bodies by route, bodies of routes without a message are captured as is.
+/// The first matching route is used.
+///
+/// Routes are an optional method and a path matched against the path hint of the request, as for
+/// [Masking::with_route_masking](crate::Masking::with_route_masking), requests without a path hint are never decoded.
+///
+/// Bodies are decoded to the proto3 JSON mapping using the field names of the `.proto` file, 64 bit integers are
+/// written as JSON numbers so the number field masks apply to them. gRPC bodies with several messages are decoded to a JSON array.
+/// The HAR gets an `application/json` mime type for decoded bodies, the captured `Content-Type` header is kept.
+/// Protobuf bodies of registered messages that can't be decoded (e.g. compressed gRPC messages) are dropped
+///
+/// # Examples
+/// ```rust,ignore
+/// use speakeasy_rust_sdk::protobuf::{prost_reflect::DescriptorPool, ProtobufDecoding};
+///
+/// let pool = DescriptorPool::decode(include_bytes!("file_descriptor_set.bin").as_ref()).unwrap();
+///
+/// let mut protobuf = ProtobufDecoding::default();
+/// // every method of the service on `POST /greeter.Greeter/{method}`
+/// protobuf.with_service(&pool.get_service_by_name("greeter.Greeter").unwrap());
+/// protobuf.with_route(
+///     "POST /v1/users",
+///     pool.get_message_by_name("users.CreateUserRequest"),
+///     pool.get_message_by_name("users.User"),
+/// );
+///
+/// sdk.settings().update(|settings| settings.protobuf = protobuf.clone());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProtobufDecoding {
+    routes: Vec<ProtobufRoute>,
+}
+
+impl ProtobufDecoding {
+    /// with_route sets the messages of the request and response bodies of requests matching the route,
+    /// invalid routes are logged and ignored
+    pub fn with_route(
+        &mut self,
+        route: &str,
+        request: Option<MessageDescriptor>,
+        response: Option<MessageDescriptor>,
+    ) {
+        if let Err(err) = self.try_with_route(route, request, response) {
+            error!("{}", err);
+        }
+    }
+
+    /// Same as [with_route](ProtobufDecoding::with_route) but returns an error for invalid routes
+    pub fn try_with_route(
+        &mut self,
+        route: &str,
+        request: Option<MessageDescriptor>,
+        response: Option<MessageDescriptor>,
+    ) -> Result<(), Error> {
+        let route =
+            RoutePattern::parse(route).ok_or_else(|| Error::InvalidRoute(route.to_string()))?;
+
+        self.routes.push(ProtobufRoute {
+            route,
+            request,
+            response,
+        });
+
+        Ok(())
+    }
+
+    /// with_service sets the messages of every method of a gRPC service, on their `POST /package.Service/Method` route
+    pub fn with_service(&mut self, service: &ServiceDescriptor) {
+        for method in service.methods() {
+            self.with_route(
+                &format!("POST /{}/{}", service.full_name(), method.name()),
+                Some(method.input()),
+                Some(method.output()),
+            );
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// The message of request bodies of the route, `None` if none is registered
+    pub(crate) fn request_message(
+        &self,
+        method: &str,
+        path_hint: Option<&str>,
+    ) -> Option<&MessageDescriptor> {
+        self.route(method, path_hint)?.request.as_ref()
+    }
+
+    /// The message of response bodies of the route, `None` if none is registered
+    pub(crate) fn response_message(
+        &self,
+        method: &str,
+        path_hint: Option<&str>,
+    ) -> Option<&MessageDescriptor> {
+        self.route(method, path_hint)?.response.as_ref()
+    }
+
+    fn route(&self, method: &str, path_hint: Option<&str>) -> Option<&ProtobufRoute> {
+        let path_hint = path_hint?;
+
+        self.routes
+            .iter()
+            .find(|route| route.route.matches(method, path_hint))
+    }
+}
+
+/// Checks if the content type is a protobuf or gRPC body that can be decoded
+pub(crate) fn is_protobuf(content_type: &str) -> bool {
+    is_grpc(content_type)
+        || PROTOBUF_CONTENT_TYPES
+            .iter()
+            .any(|protobuf| content_type.starts_with(protobuf))
+}
+
+// gRPC messages are framed, `application/grpc-web-text` bodies are base64 encoded and JSON payloads aren't protobuf
+fn is_grpc(content_type: &str) -> bool {
+    (content_type.starts_with("application/grpc+proto")
+        || content_type.starts_with("application/grpc-web+proto")
+        || content_type == "application/grpc"
+        || content_type == "application/grpc-web"
+        || content_type.starts_with("application/grpc;")
+        || content_type.starts_with("application/grpc-web;"))
+        && !content_type.contains("json")
+}
+
+/// Decodes a protobuf or gRPC body, `None` if it isn't a protobuf body or can't be decoded as the message
+pub(crate) fn decode(
+    message: &MessageDescriptor,
+    content_type: &str,
+    body: &[u8],
+) -> Option<Decoded> {
+    let json = if is_grpc(content_type) {
+        let messages = grpc_messages(body)?
+            .into_iter()
+            .map(|body| to_json(message, body))
+            .collect::<Option<Vec<_>>>()?;
+
+        match messages.len() {
+            1 => messages.into_iter().next()?,
+            _ => serde_json::Value::Array(messages),
+        }
+    } else if PROTOBUF_CONTENT_TYPES
+        .iter()
+        .any(|protobuf| content_type.starts_with(protobuf))
+    {
+        to_json(message, body)?
+    } else {
+        return None;
+    };
+
+    Some(Decoded {
+        json: json.to_string(),
+        message: message.full_name().to_string(),
+    })
+}
+
+fn to_json(message: &MessageDescriptor, body: &[u8]) -> Option<serde_json::Value> {
+    let message = DynamicMessage::decode(message.clone(), body).ok()?;
+
+    let options = SerializeOptions::new()
+        .use_proto_field_name(true)
+        .stringify_64_bit_integers(false);
+
+    message
+        .serialize_with_options(serde_json::value::Serializer, &options)
+        .ok()
+}
+
+/// Splits a gRPC body into its length prefixed messages, `None` if a message is compressed or the body is truncated
+fn grpc_messages(mut body: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut messages = vec![];
+
+    while !body.is_empty() {
+        let (compressed, rest) = body.split_first()?;
+        if *compressed != 0 || rest.len() < 4 {
+            return None;
+        }
+
+        let (length, rest) = rest.split_at(4);
+        let length = u32::from_be_bytes(length.try_into().ok()?) as usize;
+        if rest.len() < length {
+            return None;
+        }
+
+        let (message, rest) = rest.split_at(length);
+        messages.push(message);
+        body = rest;
+    }
+
+    Some(messages)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use prost_reflect::{
+        prost::Message,
+        prost_types::{
+            field_descriptor_proto::{Label, Type},
+            DescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+        },
+        DescriptorPool, Value,
+    };
+
+    struct Test {
+        #[allow(dead_code)]
+        name: &'static str,
+        method: &'static str,
+        path_hint: Option<&'static str>,
+        content_type: &'static str,
+        body: Vec<u8>,
+        expected: Option<&'static str>,
+    }
+
+    pub(crate) fn user_message() -> MessageDescriptor {
+        let field = |name: &str, number: i32, r#type: Type| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(r#type as i32),
+            ..Default::default()
+        };
+
+        let file = FileDescriptorProto {
+            name: Some("users.proto".to_string()),
+            package: Some("users".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("User".to_string()),
+                field: vec![
+                    field("user_name", 1, Type::String),
+                    field("balance", 2, Type::Int64),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mut pool = DescriptorPool::new();
+        pool.add_file_descriptor_proto(file).unwrap();
+        pool.get_message_by_name("users.User").unwrap()
+    }
+
+    pub(crate) fn grpc_frame(message: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0];
+        frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        frame.extend_from_slice(message);
+        frame
+    }
+
+    #[test]
+    fn decodes_protobuf_bodies() {
+        let user = user_message();
+
+        let mut message = DynamicMessage::new(user.clone());
+        message.set_field_by_name("user_name", Value::String("jane".to_string()));
+        message.set_field_by_name("balance", Value::I64(12345678901));
+        let message = message.encode_to_vec();
+
+        let mut protobuf = ProtobufDecoding::default();
+        protobuf.with_route("POST /users", Some(user.clone()), Some(user));
+
+        let tests = vec![
+            Test {
+                name: "protobuf body",
+                method: "POST",
+                path_hint: Some("/users"),
+                content_type: "application/x-protobuf",
+                body: message.clone(),
+                expected: Some(r#"{"balance":12345678901,"user_name":"jane"}"#),
+            },
+            Test {
+                name: "grpc body with several messages",
+                method: "POST",
+                path_hint: Some("/users"),
+                content_type: "application/grpc+proto",
+                body: [grpc_frame(&message), grpc_frame(&[])].concat(),
+                expected: Some(r#"[{"balance":12345678901,"user_name":"jane"},{}]"#),
+            },
+            Test {
+                name: "truncated grpc body",
+                method: "POST",
+                path_hint: Some("/users"),
+                content_type: "application/grpc",
+                body: grpc_frame(&message)[..8].to_vec(),
+                expected: None,
+            },
+            Test {
+                name: "other route",
+                method: "GET",
+                path_hint: Some("/users"),
+                content_type: "application/x-protobuf",
+                body: message.clone(),
+                expected: None,
+            },
+            Test {
+                name: "no path hint",
+                method: "POST",
+                path_hint: None,
+                content_type: "application/x-protobuf",
+                body: message.clone(),
+                expected: None,
+            },
+            Test {
+                name: "not protobuf",
+                method: "POST",
+                path_hint: Some("/users"),
+                content_type: "application/grpc+json",
+                body: message,
+                expected: None,
+            },
+        ];
+
+        for test in tests {
+            let decoded = protobuf
+                .request_message(test.method, test.path_hint)
+                .and_then(|message| decode(message, test.content_type, &test.body));
+
+            assert_eq!(
+                decoded.as_ref().map(|decoded| decoded.json.as_str()),
+                test.expected
+            );
+        }
+    }
+}
@@ -1,13 +1,14 @@
 use crate::{
+    settings::{MaskingMut, SettingsHandle},
     transport::{GrpcClient, Transport},
-    Config, Error, Masking, RequestConfig, SpeakeasySdk,
+    Config, Error, RequestConfig, SpeakeasySdk,
 };
 
 /// Speakeasy SDK instance
 #[doc(hidden)]
 #[derive(Debug, Clone)]
 pub struct GenericSpeakeasySdk<GrpcClient> {
-    pub(crate) masking: Masking,
+    pub(crate) settings: SettingsHandle,
     pub(crate) config: RequestConfig,
     pub(crate) transport: GrpcClient,
 }
@@ -15,10 +16,10 @@ pub struct GenericSpeakeasySdk<GrpcClient> {
 impl<T: Transport + Send + Clone + 'static> GenericSpeakeasySdk<T> {
     pub fn new_with_transport(config: Config, transport: T) -> Self {
         let config = RequestConfig::from(config);
-        let masking = Masking::default();
+        let settings = SettingsHandle::default();
 
         Self {
-            masking,
+            settings,
             config,
             transport,
         }
@@ -30,6 +31,25 @@ impl<T: Transport + Send + Clone + 'static> GenericSpeakeasySdk<T> {
     }
 }
 
+impl<T> GenericSpeakeasySdk<T> {
+    /// Changes the SDK's masking before the middleware is created, see [SettingsHandle::update] to change it at runtime
+    pub fn masking(&mut self) -> MaskingMut<'_> {
+        MaskingMut::new(&self.settings)
+    }
+
+    /// A handle to update the SDK's masking and capture settings at runtime, shared with the middleware
+    pub fn settings(&self) -> SettingsHandle {
+        self.settings.clone()
+    }
+
+    /// Uses existing settings instead of the SDK's own, e.g. to share them between SDK instances.
+    /// Clones of the SDK share its settings already
+    pub fn with_settings(mut self, settings: SettingsHandle) -> Self {
+        self.settings = settings;
+        self
+    }
+}
+
 #[cfg(feature = "mock")]
 impl GenericSpeakeasySdk<crate::transport::mock::GrpcMock> {
     pub fn into_sdk(self) -> crate::SpeakeasySdk {
@@ -42,7 +62,7 @@ impl GenericSpeakeasySdk<GrpcClient> {
     ///
     /// # Examples:
     /// ```rust
-    /// use speakeasy_rust_sdk::{SpeakeasySdk, Config, masking::StringMaskingOption};
+    /// use speakeasy_rust_sdk::{SpeakeasySdk, Config, Masking, masking::StringMaskingOption};
     ///
     /// let config = Config{
     ///     api_key: "YOUR API KEY HERE".to_string(),       // retrieve from Speakeasy API dashboard.
@@ -62,6 +82,12 @@ impl GenericSpeakeasySdk<GrpcClient> {
     /// sdk.masking().with_query_string_mask("secret", "********");
     /// sdk.masking().with_query_string_mask("password", StringMaskingOption::default());
     ///
+    /// // Configure masks for matching routes only, they are merged with the masks above
+    /// // see [Masking::with_route_masking] for the route format
+    /// let mut auth_masking = Masking::default();
+    /// auth_masking.with_request_field_mask_string("otp", None);
+    /// sdk.masking().with_route_masking("POST /auth/*", auth_masking);
+    ///
     /// // Configure other masks
     /// // see [Masking] for more options
     /// ```
@@ -69,7 +95,7 @@ impl GenericSpeakeasySdk<GrpcClient> {
         Ok(Self {
             transport: GrpcClient::new(config.api_key.clone())?,
             config: config.into(),
-            masking: Default::default(),
+            settings: Default::default(),
         })
     }
 }
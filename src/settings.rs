@@ -0,0 +1,376 @@
+//! [Masking] and capture settings shared by the SDK and every request, they can be updated at runtime
+//! without rebuilding the SDK or restarting the server
+
+#[cfg(feature = "config_watcher")]
+mod watcher;
+
+#[cfg(feature = "config_watcher")]
+pub use self::watcher::ConfigWatcher;
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use arc_swap::ArcSwap;
+use log::warn;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{capture::CaptureRules, controller::MAX_SIZE, Masking};
+
+#[cfg(feature = "protobuf")]
+use crate::protobuf::ProtobufDecoding;
+
+/// Errors for loading settings from a config file
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to read config file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid config file: {0}")]
+    Parse(String),
+    #[cfg(feature = "config_watcher")]
+    #[error("unable to watch config file: {0}")]
+    Watch(#[from] notify::Error),
+}
+
+/// The settings every request starts with, the controller can still change them for a single request.
+///
+/// Can be deserialized from a document with optional `masking` (a [MaskingConfig](crate::masking::MaskingConfig)),
+/// `capture_rules` (see [CaptureRules]), `max_capture_size`, `max_file_capture_size` and `har_masking_report` entries
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// The SDK's masking
+    pub masking: Masking,
+    /// The capture level of responses by status class and route
+    pub capture_rules: CaptureRules,
+    /// Request and response bodies above this size are dropped, defaults to 1MB
+    pub max_capture_size: usize,
+    /// Multipart file uploads above this size only have their name, content type and size captured, defaults to 0
+    pub max_file_capture_size: usize,
+    /// Adds the [MaskingReport](crate::masking::MaskingReport) of every capture to its HAR entry as the custom `_maskingReport` field,
+    /// defaults to false. The report names every masked value, only enable it outside of production
+    pub har_masking_report: bool,
+    /// The protobuf messages of bodies by route, can't be deserialized and are kept by [SettingsHandle::load_file]
+    #[cfg(feature = "protobuf")]
+    #[serde(skip)]
+    pub protobuf: ProtobufDecoding,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            masking: Masking::default(),
+            capture_rules: CaptureRules::default(),
+            max_capture_size: MAX_SIZE,
+            max_file_capture_size: 0,
+            har_masking_report: false,
+            #[cfg(feature = "protobuf")]
+            protobuf: ProtobufDecoding::default(),
+        }
+    }
+}
+
+/// A cheaply cloneable handle to the SDK's [Settings], get it with `sdk.settings()`.
+///
+/// Updates are used by the requests started after them,
+/// requests that are already being captured keep the settings they started with
+///
+/// # Examples
+/// ```rust
+/// use speakeasy_rust_sdk::{settings::SettingsHandle, Masking};
+///
+/// let settings = SettingsHandle::default();
+///
+/// // e.g. from an admin endpoint
+/// let mut masking = Masking::recommended();
+/// masking.with_request_field_mask_string("date_of_birth", None);
+/// settings.set_masking(masking);
+///
+/// settings.update(|settings| settings.max_capture_size = 64 * 1024);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SettingsHandle(Arc<ArcSwap<Settings>>);
+
+impl SettingsHandle {
+    pub fn new(settings: Settings) -> Self {
+        Self(Arc::new(ArcSwap::from_pointee(settings)))
+    }
+
+    /// The current settings
+    pub fn load(&self) -> Arc<Settings> {
+        self.0.load_full()
+    }
+
+    /// Replaces the settings
+    pub fn store(&self, settings: Settings) {
+        self.0.store(Arc::new(settings))
+    }
+
+    /// Changes the current settings, `update` can be called more than once if the settings are changed concurrently
+    pub fn update(&self, update: impl Fn(&mut Settings)) {
+        self.0.rcu(|current| {
+            let mut settings = Settings::clone(current);
+            update(&mut settings);
+            settings
+        });
+    }
+
+    /// Replaces the SDK's masking, the capture settings are kept
+    pub fn set_masking(&self, masking: Masking) {
+        self.update(|settings| settings.masking = masking.clone())
+    }
+
+    /// Loads the settings from a config file, `parse` turns the file contents into [Settings].
+    /// The current settings are kept if the file can't be read or parsed.
+    /// The masks of the file replace the current ones, the parts of the masking that can only be set in code
+    /// (PII detectors, removals, allowlists, maskers and report hooks) and the protobuf messages are always kept
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use speakeasy_rust_sdk::settings::SettingsHandle;
+    ///
+    /// let settings = SettingsHandle::default();
+    /// settings
+    ///     .load_file("speakeasy.json", |contents| serde_json::from_str(contents))
+    ///     .expect("valid settings");
+    /// ```
+    pub fn load_file<E: ToString>(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        parse: impl Fn(&str) -> Result<Settings, E>,
+    ) -> Result<(), Error> {
+        let contents = std::fs::read_to_string(path)?;
+        let settings = parse(&contents).map_err(|err| Error::Parse(err.to_string()))?;
+
+        self.update(|current| {
+            let mut loaded = settings.clone();
+            loaded.masking.keep_code_registered(&current.masking);
+            #[cfg(feature = "protobuf")]
+            {
+                loaded.protobuf = std::mem::take(&mut current.protobuf);
+            }
+            *current = loaded;
+        });
+
+        Ok(())
+    }
+}
+
+/// Changes the SDK's masking in place, the changes are stored in the [SettingsHandle] when this is dropped.
+///
+/// Meant for setting up the masking before the middleware is created: the whole masking is written back,
+/// so updates made to the handle while the guard is alive (e.g. by a `ConfigWatcher`) are overwritten.
+/// Use [SettingsHandle::update] to change the masking at runtime
+#[derive(Debug)]
+pub struct MaskingMut<'a> {
+    handle: &'a SettingsHandle,
+    // the settings the masking was copied from, to detect changes made while the guard is alive
+    loaded: Arc<Settings>,
+    masking: Masking,
+}
+
+impl<'a> MaskingMut<'a> {
+    pub(crate) fn new(handle: &'a SettingsHandle) -> Self {
+        let loaded = handle.load();
+
+        Self {
+            handle,
+            masking: loaded.masking.clone(),
+            loaded,
+        }
+    }
+}
+
+impl Deref for MaskingMut<'_> {
+    type Target = Masking;
+
+    fn deref(&self) -> &Self::Target {
+        &self.masking
+    }
+}
+
+impl DerefMut for MaskingMut<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.masking
+    }
+}
+
+impl Drop for MaskingMut<'_> {
+    fn drop(&mut self) {
+        let masking = std::mem::take(&mut self.masking);
+
+        self.handle.0.rcu(|current| {
+            if !Arc::ptr_eq(current, &self.loaded) {
+                warn!("the settings changed while the masking was being changed, use SettingsHandle::update to change the masking at runtime");
+            }
+
+            Settings {
+                masking: masking.clone(),
+                ..Settings::clone(current)
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speakeasy_protos::ingest::ingest_request::MaskingMetadata;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn updates_settings_in_place() {
+        let handle = SettingsHandle::default();
+        let clone = handle.clone();
+
+        MaskingMut::new(&handle).with_query_string_mask("token", None);
+        clone.update(|settings| settings.max_capture_size = 1024);
+
+        let settings = handle.load();
+        let metadata = MaskingMetadata::from(settings.masking.clone());
+
+        assert_eq!(settings.max_capture_size, 1024);
+        assert!(metadata.query_string_masks.contains_key("token"));
+    }
+
+    #[test]
+    fn loads_settings_from_json() {
+        let settings: Settings = serde_json::from_str(
+            r#"{
+                "masking": { "request_string_fields": { "fields": ["password"] } },
+                "max_file_capture_size": 4096
+            }"#,
+        )
+        .unwrap();
+
+        let metadata = MaskingMetadata::from(settings.masking);
+
+        assert_eq!(settings.max_capture_size, MAX_SIZE);
+        assert_eq!(settings.max_file_capture_size, 4096);
+        assert!(metadata.request_field_masks_string.contains_key("password"));
+
+        let err = serde_json::from_str::<Settings>(
+            r#"{ "masking": { "request_headers": { "fields": ["x api key"] } } }"#,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("invalid request header name"));
+    }
+
+    #[test]
+    fn keeps_settings_when_the_file_is_invalid() {
+        let path = std::env::temp_dir().join(format!("speakeasy-{}.json", uuid::Uuid::new_v4()));
+        let handle = SettingsHandle::default();
+        let load = |handle: &SettingsHandle| {
+            handle.load_file(&path, |contents| serde_json::from_str::<Settings>(contents))
+        };
+
+        assert!(matches!(load(&handle), Err(Error::Io(_))));
+
+        std::fs::write(&path, r#"{ "max_capture_size": 1024 }"#).unwrap();
+        load(&handle).unwrap();
+        assert_eq!(handle.load().max_capture_size, 1024);
+
+        std::fs::write(&path, r#"{ "max_capture_size": "#).unwrap();
+        assert!(matches!(load(&handle), Err(Error::Parse(_))));
+        assert_eq!(handle.load().max_capture_size, 1024);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keeps_code_registered_masking_when_loading_a_file() {
+        use crate::{
+            har_builder::{
+                tests::{request, response},
+                HarBuilder,
+            },
+            masking::{MaskingContext, PiiKind},
+        };
+        use har::v1_2::{Request as HarRequest, Response as HarResponse};
+
+        let path = std::env::temp_dir().join(format!("speakeasy-{}.json", uuid::Uuid::new_v4()));
+        let handle = SettingsHandle::default();
+
+        {
+            let mut masking = MaskingMut::new(&handle);
+            masking.with_pii_detector(PiiKind::Email);
+            masking.with_masker(
+                |_: &MaskingContext, _: &mut HarRequest, response: &mut HarResponse| {
+                    let text = response.content.text.take().unwrap_or_default();
+                    response.content.text = Some(text.replace("__masked__", "__masker__"));
+                },
+            );
+            masking.with_route_masking("/login", {
+                let mut masking = Masking::default();
+                masking.with_request_field_removal("client_secret");
+                masking
+            });
+        }
+
+        std::fs::write(
+            &path,
+            r#"{ "masking": { "response_string_fields": { "fields": ["token"] } } }"#,
+        )
+        .unwrap();
+        handle
+            .load_file(&path, |contents| serde_json::from_str::<Settings>(contents))
+            .unwrap();
+
+        let settings = handle.load();
+        let (har, _, _) = HarBuilder::new(
+            request(
+                "application/json",
+                br#"{"email":"jane@example.com","client_secret":"abc"}"#,
+            ),
+            response("application/json", br#"{"token":"abc"}"#),
+            MAX_SIZE,
+        )
+        .with_path_hint("/login".to_string())
+        .build(&settings.masking.for_route("POST", "/login"));
+
+        let entry = match har.log {
+            har::Spec::V1_2(log) => log.entries.into_iter().next().unwrap(),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            entry.request.post_data.unwrap().text.as_deref(),
+            Some(r#"{"email":"__masked_email__"}"#)
+        );
+        assert_eq!(
+            entry.response.content.text.as_deref(),
+            Some(r#"{"token":"__masker__"}"#)
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn keeps_protobuf_messages_when_loading_a_file() {
+        let path = std::env::temp_dir().join(format!("speakeasy-{}.json", uuid::Uuid::new_v4()));
+        let handle = SettingsHandle::default();
+
+        let mut protobuf = ProtobufDecoding::default();
+        protobuf.with_route(
+            "POST /users",
+            Some(crate::protobuf::tests::user_message()),
+            None,
+        );
+        handle.update(|settings| settings.protobuf = protobuf.clone());
+
+        std::fs::write(&path, r#"{ "max_capture_size": 1024 }"#).unwrap();
+        handle
+            .load_file(&path, |contents| serde_json::from_str::<Settings>(contents))
+            .unwrap();
+
+        let settings = handle.load();
+        assert_eq!(settings.max_capture_size, 1024);
+        assert!(!settings.protobuf.is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+use log::{debug, error};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use super::{Error, Settings, SettingsHandle};
+
+/// Reloads the [Settings] from a config file whenever it changes, until it's dropped.
+///
+/// The file is loaded once when the watcher is created, later changes that can't be read or parsed
+/// are logged and the current settings are kept
+///
+/// # Examples
+/// ```rust,no_run
+/// use speakeasy_rust_sdk::settings::{ConfigWatcher, SettingsHandle};
+///
+/// let settings = SettingsHandle::default();
+///
+/// // keep the watcher around for as long as the file should be watched
+/// let watcher = ConfigWatcher::json(settings, "speakeasy.json").expect("valid settings");
+/// ```
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Watches a JSON config file
+    pub fn json(handle: SettingsHandle, path: impl Into<PathBuf>) -> Result<Self, Error> {
+        Self::new(handle, path, |contents| serde_json::from_str(contents))
+    }
+
+    /// Watches a config file in any format, `parse` turns the file contents into [Settings]
+    pub fn new<E: ToString>(
+        handle: SettingsHandle,
+        path: impl Into<PathBuf>,
+        parse: impl Fn(&str) -> Result<Settings, E> + Send + 'static,
+    ) -> Result<Self, Error> {
+        let path = path.into();
+        handle.load_file(&path, &parse)?;
+
+        // the parent directory is watched since editors often replace the file instead of writing to it
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let file_name = path.file_name().map(ToOwned::to_owned);
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let event = match event {
+                Ok(event) => event,
+                Err(err) => return error!("error watching {}: {}", path.display(), err),
+            };
+
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                return;
+            }
+
+            if !event
+                .paths
+                .iter()
+                .any(|changed| changed.file_name() == file_name.as_deref())
+            {
+                return;
+            }
+
+            match handle.load_file(&path, &parse) {
+                Ok(()) => debug!("reloaded settings from {}", path.display()),
+                Err(err) => error!("unable to reload {}: {}", path.display(), err),
+            }
+        })?;
+
+        watcher.watch(Path::new(&directory), RecursiveMode::NonRecursive)?;
+
+        Ok(Self { _watcher: watcher })
+    }
+}
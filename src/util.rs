@@ -9,3 +9,14 @@ pub fn get_first_capture<'a>(caps: &'a Captures) -> Option<&'a str> {
 
     None
 }
+
+/// Returns the first non-empty capture and the capture directly after it
+pub fn get_first_capture_and_next<'a>(caps: &'a Captures) -> Option<(&'a str, Option<&'a str>)> {
+    for i in 1..caps.len() {
+        if let Some(c) = caps.get(i) {
+            return Some((c.as_str(), caps.get(i + 1).map(|c| c.as_str())));
+        }
+    }
+
+    None
+}